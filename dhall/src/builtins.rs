@@ -1,5 +1,6 @@
 use std::collections::{BTreeMap, HashMap};
 use std::convert::TryInto;
+use std::rc::Rc;
 
 use crate::operations::{BinOp, OpKind};
 use crate::semantics::{nze, Hir, HirKind, Nir, NirKind, NzEnv, VarEnv};
@@ -8,7 +9,7 @@ use crate::syntax::{
     Const, Expr, ExprKind, InterpolatedText, InterpolatedTextContents, Label,
     NaiveDouble, NumKind, Span, UnspannedExpr, V,
 };
-use crate::{Ctxt, Parsed};
+use crate::{Ctxt, HostFunctionId, Parsed};
 
 /// Built-ins
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
@@ -116,6 +117,180 @@ impl<'cx> BuiltinClosure<'cx> {
     }
 }
 
+/// A function implemented in Rust that Dhall code can call like a builtin. Register it with
+/// `Ctxt::push_host_function`; it then resolves like a free variable with the given name.
+#[derive(Clone)]
+pub struct HostFunction {
+    name: Label,
+    ty: Expr,
+    arity: usize,
+    body: HostBody,
+}
+
+type HostFn = dyn for<'cx> Fn(Ctxt<'cx>, &[Nir<'cx>]) -> Option<Nir<'cx>>;
+
+#[derive(Clone)]
+enum HostBody {
+    Call(Rc<HostFn>),
+    /// Resolved once, when the function is registered.
+    Constant(Expr),
+}
+
+impl HostFunction {
+    /// Makes a host function with the given Dhall type. The function gets called once it has been
+    /// applied to as many arguments as there are arrows in `ty`, and those arguments are fully
+    /// normalized. It may return `None` to leave the application unreduced.
+    ///
+    /// The function must return a value of the declared return type.
+    pub fn new<F>(name: Label, ty: Expr, f: F) -> Self
+    where
+        F: for<'cx> Fn(Ctxt<'cx>, &[Nir<'cx>]) -> Option<Nir<'cx>> + 'static,
+    {
+        let mut arity = 0;
+        let mut codomain = &ty;
+        while let ExprKind::Pi(_, _, body) = codomain.kind() {
+            arity += 1;
+            codomain = body;
+        }
+        HostFunction {
+            name,
+            ty,
+            arity,
+            body: HostBody::Call(Rc::new(f)),
+        }
+    }
+    /// Makes a host function that takes no arguments and evaluates to the given closed
+    /// expression. The expression must have type `ty`; it does not get typechecked again.
+    pub fn constant(name: Label, ty: Expr, value: Expr) -> Self {
+        HostFunction {
+            name,
+            ty,
+            // The value may itself be a function, but it is available without arguments.
+            arity: 0,
+            body: HostBody::Constant(value),
+        }
+    }
+    pub fn name(&self) -> &Label {
+        &self.name
+    }
+    pub fn ty(&self) -> &Expr {
+        &self.ty
+    }
+    pub(crate) fn constant_value(&self) -> Option<&Expr> {
+        match &self.body {
+            HostBody::Call(_) => None,
+            HostBody::Constant(value) => Some(value),
+        }
+    }
+}
+
+impl std::fmt::Debug for HostFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("HostFunction")
+            .field("name", &self.name)
+            .field("ty", &self.ty)
+            .finish()
+    }
+}
+
+/// A partially applied host function.
+/// Invariant: the function cannot be called with the given args.
+#[derive(Debug, Clone)]
+pub struct HostClosure<'cx> {
+    cx: Ctxt<'cx>,
+    id: HostFunctionId<'cx>,
+    /// Arguments applied to the closure so far.
+    args: Vec<Nir<'cx>>,
+}
+
+impl<'cx> HostClosure<'cx> {
+    pub fn new(cx: Ctxt<'cx>, id: HostFunctionId<'cx>) -> NirKind<'cx> {
+        apply_host_function(cx, id, Vec::new())
+    }
+    pub fn apply(&self, a: Nir<'cx>) -> NirKind<'cx> {
+        use std::iter::once;
+        let args = self.args.iter().cloned().chain(once(a)).collect();
+        apply_host_function(self.cx, self.id, args)
+    }
+    pub fn to_hirkind(&self, venv: VarEnv) -> HirKind<'cx> {
+        self.args
+            .iter()
+            .fold(HirKind::HostFunction(self.id), |acc, v| {
+                HirKind::Expr(ExprKind::Op(OpKind::App(
+                    Hir::new(acc, Span::Artificial),
+                    v.to_hir(venv),
+                )))
+            })
+    }
+}
+
+/// Whether the value is fully normalized, i.e. contains nothing that further evaluation could
+/// change. Functions are considered normalized.
+fn is_normalized(v: &Nir) -> bool {
+    use InterpolatedTextContents::Text;
+    match v.kind() {
+        NirKind::Var(..)
+        | NirKind::Op(..)
+        | NirKind::AppliedBuiltin(..)
+        | NirKind::AppliedHostFunction(..) => false,
+        NirKind::TextLit(t) => t.iter().all(|x| matches!(x, Text(_))),
+        NirKind::EmptyOptionalLit(x)
+        | NirKind::NEOptionalLit(x)
+        | NirKind::OptionalType(x)
+        | NirKind::EmptyListLit(x)
+        | NirKind::ListType(x)
+        | NirKind::UnionLit(_, x, _)
+        | NirKind::Assert(x) => is_normalized(x),
+        NirKind::NEListLit(xs) => xs.iter().all(is_normalized),
+        NirKind::RecordLit(kvs) | NirKind::RecordType(kvs) => {
+            kvs.values().all(is_normalized)
+        }
+        NirKind::UnionConstructor(_, kts) | NirKind::UnionType(kts) => {
+            kts.values().flatten().all(is_normalized)
+        }
+        NirKind::Equivalence(x, y) => is_normalized(x) && is_normalized(y),
+        NirKind::LamClosure { .. }
+        | NirKind::PiClosure { .. }
+        | NirKind::Const(..)
+        | NirKind::Num(..)
+        | NirKind::BuiltinType(..) => true,
+    }
+}
+
+fn apply_host_function<'cx>(
+    cx: Ctxt<'cx>,
+    id: HostFunctionId<'cx>,
+    args: Vec<Nir<'cx>>,
+) -> NirKind<'cx> {
+    let stored = &cx[id];
+    let func = &stored.func;
+    if args.len() < func.arity
+        || !args.iter().all(is_normalized)
+        || cx.is_exhausted()
+    {
+        return NirKind::AppliedHostFunction(HostClosure { cx, id, args });
+    }
+    let result = match &func.body {
+        HostBody::Call(f) => f(cx, &args),
+        HostBody::Constant(_) => stored.constant.clone(),
+    };
+    match result {
+        Some(v) if cx.try_step() => {
+            let v = v.kind().clone();
+            cx.check_size(&v);
+            v
+        }
+        _ => NirKind::AppliedHostFunction(HostClosure { cx, id, args }),
+    }
+}
+
+impl<'cx> std::cmp::PartialEq for HostClosure<'cx> {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id && self.args == other.args
+    }
+}
+impl<'cx> std::cmp::Eq for HostClosure<'cx> {}
+
 pub fn rc(x: UnspannedExpr) -> Expr {
     Expr::new(x, Span::Artificial)
 }
@@ -323,6 +498,23 @@ fn apply_builtin<'cx>(
         Nir(Nir<'cx>),
         DoneAsIs,
     }
+    // Type constructors always reduce, so that values keep a well-formed type. Other builtins
+    // stay unreduced once the evaluation budget is exhausted.
+    let is_type_constructor = matches!(
+        b,
+        Builtin::Bool
+            | Builtin::Natural
+            | Builtin::Integer
+            | Builtin::Double
+            | Builtin::Text
+            | Builtin::Optional
+            | Builtin::List
+            | Builtin::OptionalNone
+    );
+    if !is_type_constructor && cx.is_exhausted() {
+        return AppliedBuiltin(BuiltinClosure { b, args, env });
+    }
+
     let make_closure = |e| {
         Parsed::from_expr_without_imports(e)
             .resolve(cx)
@@ -564,6 +756,9 @@ fn apply_builtin<'cx>(
 
         (Builtin::NaturalFold, [n, t, succ, zero]) => match &*n.kind() {
             Num(Natural(0)) => Ret::Nir(zero.clone()),
+            Num(Natural(_)) if !(cx.try_step() && cx.try_fold_iteration()) => {
+                Ret::DoneAsIs
+            }
             Num(Natural(n)) => {
                 let fold = Nir::from_builtin(cx, Builtin::NaturalFold)
                     .app(Num(Natural(n - 1)).into_nir())
@@ -576,11 +771,20 @@ fn apply_builtin<'cx>(
         },
         _ => Ret::DoneAsIs,
     };
-    match ret {
+    let v = match ret {
         Ret::NirKind(v) => v,
         Ret::Nir(v) => v.kind().clone(),
-        Ret::DoneAsIs => AppliedBuiltin(BuiltinClosure { b, args, env }),
+        Ret::DoneAsIs => {
+            return AppliedBuiltin(BuiltinClosure { b, args, env })
+        }
+    };
+    // `Natural/fold` takes its steps upfront because it recurses eagerly.
+    if !is_type_constructor && b != Builtin::NaturalFold {
+        // The reduction already happened, so we only need this to update the budget.
+        let _ = cx.try_step();
+        cx.check_size(&v);
     }
+    v
 }
 
 impl<'cx> std::cmp::PartialEq for BuiltinClosure<'cx> {
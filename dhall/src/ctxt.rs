@@ -1,11 +1,18 @@
 use elsa::vec::FrozenVec;
 use once_cell::sync::OnceCell;
+use std::cell::Cell;
 use std::marker::PhantomData;
 use std::ops::{Deref, Index};
+use std::rc::Rc;
+use std::time::Instant;
 
-use crate::semantics::{Import, ImportLocation, ImportNode};
-use crate::syntax::Span;
-use crate::Typed;
+use crate::builtins::HostFunction;
+use crate::error::{Error, EvalLimitError, TypeError, TypeMessage};
+use crate::semantics::{
+    Import, ImportLocation, ImportNode, Nir, NirKind, Type, Universe,
+};
+use crate::syntax::{Label, Span};
+use crate::{Parsed, Typed};
 
 /////////////////////////////////////////////////////////////////////////////////////////////////////
 // Ctxt
@@ -16,6 +23,8 @@ pub struct CtxtS<'cx> {
     imports: FrozenVec<Box<StoredImport<'cx>>>,
     import_alternatives: FrozenVec<Box<StoredImportAlternative<'cx>>>,
     import_results: FrozenVec<Box<StoredImportResult<'cx>>>,
+    host_functions: FrozenVec<Box<StoredHostFunction<'cx>>>,
+    budget: EvalBudget,
 }
 
 /// Context for the dhall compiler. Stores various global maps.
@@ -25,11 +34,30 @@ pub struct Ctxt<'cx>(&'cx CtxtS<'cx>);
 
 impl Ctxt<'_> {
     pub fn with_new<T>(f: impl for<'cx> FnOnce(Ctxt<'cx>) -> T) -> T {
-        let cx = CtxtS::default();
+        Self::with_limits(EvalLimits::default(), f)
+    }
+    /// Like `with_new`, but evaluation within this context will stop once it goes over the
+    /// provided limits. Use `Ctxt::check_limits` to find out if that happened.
+    pub fn with_limits<T>(
+        limits: EvalLimits,
+        f: impl for<'cx> FnOnce(Ctxt<'cx>) -> T,
+    ) -> T {
+        let cx = CtxtS::with_limits(limits);
         let cx = Ctxt(&cx);
         f(cx)
     }
 }
+impl CtxtS<'_> {
+    fn with_limits(limits: EvalLimits) -> Self {
+        CtxtS {
+            budget: EvalBudget {
+                limits,
+                ..EvalBudget::default()
+            },
+            ..CtxtS::default()
+        }
+    }
+}
 impl<'cx> Deref for Ctxt<'cx> {
     type Target = &'cx CtxtS<'cx>;
     fn deref(&self) -> &&'cx CtxtS<'cx> {
@@ -55,6 +83,91 @@ impl<'cx> std::fmt::Debug for Ctxt<'cx> {
     }
 }
 
+/////////////////////////////////////////////////////////////////////////////////////////////////////
+// Shared contexts
+
+/// A context that is reference-counted instead of living on the stack, so that values evaluated
+/// in it can be kept after `with` returns, using `keep`.
+#[derive(Clone)]
+pub struct SharedCtxt(Rc<CtxtS<'static>>);
+
+impl SharedCtxt {
+    pub fn new(limits: EvalLimits) -> Self {
+        SharedCtxt(Rc::new(CtxtS::with_limits(limits)))
+    }
+    /// Runs `f` within this context. Like with `Ctxt::with_new`, nothing that borrows from the
+    /// context can escape `f`, except through `keep`.
+    pub fn with<T>(&self, f: impl for<'cx> FnOnce(Ctxt<'cx>) -> T) -> T {
+        f(self.ctxt())
+    }
+    fn ctxt(&self) -> Ctxt<'_> {
+        // SAFETY: the context lives as long as the `Rc`, so at least as long as `self` is
+        // borrowed. The `'static` is never observed: the context is only accessed through this
+        // shorter lifetime.
+        Ctxt(unsafe { &*Rc::as_ptr(&self.0).cast::<CtxtS<'_>>() })
+    }
+    /// Whether `cx` refers to this context.
+    fn is(&self, cx: Ctxt<'_>) -> bool {
+        std::ptr::eq(
+            Rc::as_ptr(&self.0) as *const u8,
+            cx.0 as *const CtxtS<'_> as *const u8,
+        )
+    }
+    /// Whether the two handles refer to the same context.
+    pub fn ptr_eq(&self, other: &SharedCtxt) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+    /// Keeps a value that was evaluated within this context, so that it can be used again in a
+    /// later call to `with`. Panics if `cx` is not this context.
+    pub fn keep<'cx>(&self, cx: Ctxt<'cx>, nir: Nir<'cx>) -> KeptNir {
+        assert!(self.is(cx), "the value was evaluated in another context");
+        // SAFETY: the value only refers to this context, which the `KeptNir` keeps alive. It is
+        // only handed out again by `KeptNir::get`, with the lifetime of a `Ctxt` that refers to
+        // the same context.
+        let nir = unsafe { std::mem::transmute::<Nir<'cx>, Nir<'static>>(nir) };
+        KeptNir {
+            nir,
+            cx: self.clone(),
+        }
+    }
+}
+
+/// Empty impl, like for `Ctxt`.
+impl std::fmt::Debug for SharedCtxt {
+    fn fmt(&self, _: &mut std::fmt::Formatter) -> std::fmt::Result {
+        Ok(())
+    }
+}
+
+/// A value kept together with the `SharedCtxt` it was evaluated in. See `SharedCtxt::keep`.
+#[derive(Clone)]
+pub struct KeptNir {
+    // Declared before `cx` so that it is dropped first.
+    nir: Nir<'static>,
+    cx: SharedCtxt,
+}
+
+impl KeptNir {
+    /// The context the value was evaluated in.
+    pub fn ctxt(&self) -> &SharedCtxt {
+        &self.cx
+    }
+    /// The value, to be used within `self.ctxt().with(..)`. Panics if `cx` is not the context
+    /// the value was evaluated in.
+    pub fn get<'a, 'cx>(&'a self, cx: Ctxt<'cx>) -> &'a Nir<'cx> {
+        assert!(self.cx.is(cx), "the value was evaluated in another context");
+        // SAFETY: see `SharedCtxt::keep`.
+        unsafe { &*(&self.nir as *const Nir<'static>).cast::<Nir<'cx>>() }
+    }
+}
+
+impl std::fmt::Debug for KeptNir {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.cx
+            .with(|cx| f.debug_tuple("KeptNir").field(self.get(cx)).finish())
+    }
+}
+
 /////////////////////////////////////////////////////////////////////////////////////////////////////
 // Imports
 
@@ -209,3 +322,202 @@ impl<'cx> Index<ImportResultId<'cx>> for CtxtS<'cx> {
         &self.import_results[id.0]
     }
 }
+
+/////////////////////////////////////////////////////////////////////////////////////////////////////
+// Host functions
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct HostFunctionId<'cx>(usize, PhantomData<&'cx ()>);
+
+/// What's stored for each `HostFunctionId`.
+pub struct StoredHostFunction<'cx> {
+    pub func: HostFunction,
+    /// The typechecked type of the function.
+    pub ty: Type<'cx>,
+    /// The value of a constant made with `HostFunction::constant`.
+    pub constant: Option<Nir<'cx>>,
+}
+
+impl<'cx> Ctxt<'cx> {
+    /// Makes a host function available to the code evaluated within this context. It can be
+    /// referred to by name, like a variable bound outside of every file. Fails if its declared
+    /// type is not a valid type, or if the value of a constant fails to resolve.
+    pub fn push_host_function(
+        self,
+        func: HostFunction,
+    ) -> Result<HostFunctionId<'cx>, Error> {
+        let typed = Parsed::from_expr_without_imports(func.ty().clone())
+            .skip_resolve(self)?
+            .typecheck(self)?;
+        let univ = match typed.ty().as_const() {
+            Some(c) => Universe::from_const(c),
+            None => {
+                return Err(TypeError::new(TypeMessage::Custom(format!(
+                    "the declared type of `{}` is not a type",
+                    func.name()
+                )))
+                .into())
+            }
+        };
+        let ty = Type::new(typed.hir.eval_closed_expr(self), univ);
+        let constant = match func.constant_value() {
+            Some(value) => Some(
+                Parsed::from_expr_without_imports(value.clone())
+                    .skip_resolve(self)?
+                    .0
+                    .eval_closed_expr(self),
+            ),
+            None => None,
+        };
+        let id = self.0.host_functions.len();
+        self.0.host_functions.push(Box::new(StoredHostFunction {
+            func,
+            ty,
+            constant,
+        }));
+        Ok(HostFunctionId(id, PhantomData))
+    }
+    /// Finds the most recently registered host function with this name.
+    pub fn lookup_host_function(
+        self,
+        name: &Label,
+    ) -> Option<HostFunctionId<'cx>> {
+        (0..self.0.host_functions.len())
+            .rev()
+            .find(|&id| self.0.host_functions[id].func.name() == name)
+            .map(|id| HostFunctionId(id, PhantomData))
+    }
+}
+impl<'cx> Index<HostFunctionId<'cx>> for CtxtS<'cx> {
+    type Output = StoredHostFunction<'cx>;
+    fn index(&self, id: HostFunctionId<'cx>) -> &StoredHostFunction<'cx> {
+        &self.host_functions[id.0]
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////////////////////////////
+// Evaluation limits
+
+/// Limits on the resources that evaluation may use. `None` means unlimited.
+#[derive(Debug, Clone, Default)]
+pub struct EvalLimits {
+    /// Maximum number of reduction steps, i.e. function applications and builtin reductions.
+    pub max_steps: Option<u64>,
+    /// Maximum total number of iterations of `Natural/fold`.
+    pub max_fold_iterations: Option<u64>,
+    /// Maximum length of a list.
+    pub max_list_length: Option<usize>,
+    /// Maximum size of a text literal, in bytes.
+    pub max_text_size: Option<usize>,
+    /// Time after which evaluation is abandoned.
+    pub deadline: Option<Instant>,
+}
+
+/// Tracks the resources evaluation has used so far.
+#[derive(Debug, Default)]
+struct EvalBudget {
+    limits: EvalLimits,
+    steps: Cell<u64>,
+    fold_iterations: Cell<u64>,
+    /// Set once a limit was exceeded. From then on evaluation stops making progress.
+    exceeded: OnceCell<EvalLimitError>,
+}
+
+impl EvalBudget {
+    fn exceed(&self, err: EvalLimitError) -> bool {
+        let _ = self.exceeded.set(err);
+        false
+    }
+    /// Increments the counter, returning `false` if this went over the limit.
+    fn count(
+        &self,
+        counter: &Cell<u64>,
+        max: Option<u64>,
+        err: impl FnOnce(u64) -> EvalLimitError,
+    ) -> bool {
+        if self.exceeded.get().is_some() {
+            return false;
+        }
+        let n = counter.get() + 1;
+        counter.set(n);
+        match max {
+            Some(max) if n > max => self.exceed(err(max)),
+            _ => match self.limits.deadline {
+                Some(deadline) if Instant::now() >= deadline => {
+                    self.exceed(EvalLimitError::DeadlineExceeded)
+                }
+                _ => true,
+            },
+        }
+    }
+}
+
+impl<'cx> Ctxt<'cx> {
+    /// Accounts for one reduction step. Returns `false` if the step must not be taken because the
+    /// evaluation budget is exhausted.
+    pub fn try_step(self) -> bool {
+        let budget = &self.0.budget;
+        budget.count(
+            &budget.steps,
+            budget.limits.max_steps,
+            EvalLimitError::TooManySteps,
+        )
+    }
+    /// Accounts for one iteration of `Natural/fold`. Returns `false` if the iteration must not
+    /// happen because the evaluation budget is exhausted.
+    pub fn try_fold_iteration(self) -> bool {
+        let budget = &self.0.budget;
+        budget.count(
+            &budget.fold_iterations,
+            budget.limits.max_fold_iterations,
+            EvalLimitError::TooManyFoldIterations,
+        )
+    }
+    /// Whether evaluation has gone over one of the limits.
+    pub fn is_exhausted(self) -> bool {
+        self.0.budget.exceeded.get().is_some()
+    }
+    /// Records an error if the given value is a list or text that is too large.
+    pub fn check_size(self, v: &NirKind<'cx>) {
+        use crate::syntax::InterpolatedTextContents::Text;
+        let budget = &self.0.budget;
+        match v {
+            NirKind::NEListLit(xs) => match budget.limits.max_list_length {
+                Some(max) if xs.len() > max => {
+                    budget.exceed(EvalLimitError::ListTooLong(max));
+                }
+                _ => {}
+            },
+            NirKind::TextLit(t) => {
+                if let Some(max) = budget.limits.max_text_size {
+                    let size: usize = t
+                        .iter()
+                        .map(|x| match x {
+                            Text(s) => s.len(),
+                            _ => 0,
+                        })
+                        .sum();
+                    if size > max {
+                        budget.exceed(EvalLimitError::TextTooLong(max));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    /// Fails if evaluation within this context went over one of the limits. When that happens,
+    /// the results of evaluation are incomplete and should be discarded.
+    pub fn check_limits(self) -> Result<(), EvalLimitError> {
+        match self.0.budget.exceeded.get() {
+            Some(err) => Err(err.clone()),
+            None => Ok(()),
+        }
+    }
+    /// Counts reduction steps and `Natural/fold` iterations from zero again, to evaluate
+    /// something new in a context that is kept around. A limit that was already exceeded stays
+    /// exceeded, since the values evaluated so far may be incomplete.
+    pub fn restart_budget(self) {
+        self.0.budget.steps.set(0);
+        self.0.budget.fold_iterations.set(0);
+    }
+}
@@ -23,6 +23,8 @@ pub enum ErrorKind {
     Resolve(ImportError),
     Typecheck(TypeError),
     Cache(CacheError),
+    EvalLimit(EvalLimitError),
+    Edit(EditError),
 }
 
 #[derive(Debug)]
@@ -32,6 +34,8 @@ pub enum ImportError {
     SanityCheck,
     UnexpectedImport(Import<()>),
     ImportCycle(CyclesStack, ImportLocation),
+    /// This kind of import was disabled in the `ImportOptions`.
+    Disabled(ImportLocation),
     Url(url::ParseError),
 }
 
@@ -58,6 +62,34 @@ pub enum TypeMessage {
     Custom(String),
 }
 
+/// Evaluation went over one of the configured `EvalLimits`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EvalLimitError {
+    TooManySteps(u64),
+    TooManyFoldIterations(u64),
+    ListTooLong(usize),
+    TextTooLong(usize),
+    DeadlineExceeded,
+}
+
+/// An edit of a `Document` could not be made. Paths are given as written by the caller.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EditError {
+    /// The path could not be parsed.
+    InvalidPath(String),
+    /// Nothing is written at this path in the source.
+    NotFound(String),
+    /// The value at this path is not written out in the source, e.g. it comes from a function
+    /// call or from a desugared field like `{ a.b = 1 }`.
+    NotInSource(String),
+    /// The value at this path is not a list.
+    NotAList(String),
+    /// The index is past the end of the list.
+    IndexOutOfBounds(String, usize),
+    /// No `let` binds this name.
+    MissingLet(String),
+}
+
 #[derive(Debug)]
 pub enum CacheError {
     MissingConfiguration,
@@ -103,6 +135,63 @@ impl std::fmt::Display for EncodeError {
 
 impl std::error::Error for EncodeError {}
 
+impl std::fmt::Display for EvalLimitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        use EvalLimitError::*;
+        match self {
+            TooManySteps(max) => {
+                write!(f, "Evaluation took more than {} steps", max)
+            }
+            TooManyFoldIterations(max) => write!(
+                f,
+                "Evaluation took more than {} iterations of `Natural/fold`",
+                max
+            ),
+            ListTooLong(max) => write!(
+                f,
+                "Evaluation produced a list longer than {} elements",
+                max
+            ),
+            TextTooLong(max) => write!(
+                f,
+                "Evaluation produced a text larger than {} bytes",
+                max
+            ),
+            DeadlineExceeded => write!(f, "Evaluation ran out of time"),
+        }
+    }
+}
+
+impl std::error::Error for EvalLimitError {}
+
+impl std::fmt::Display for EditError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        use EditError::*;
+        match self {
+            InvalidPath(path) => write!(f, "invalid path: `{}`", path),
+            NotFound(path) => write!(f, "nothing is written at `{}`", path),
+            NotInSource(path) => write!(
+                f,
+                "the value at `{}` is not written out in the source",
+                path
+            ),
+            NotAList(path) => {
+                write!(f, "the value at `{}` is not a list", path)
+            }
+            IndexOutOfBounds(path, i) => {
+                write!(
+                    f,
+                    "index {} is out of bounds for the list at `{}`",
+                    i, path
+                )
+            }
+            MissingLet(name) => write!(f, "no `let` binds `{}`", name),
+        }
+    }
+}
+
+impl std::error::Error for EditError {}
+
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match &self.kind {
@@ -113,6 +202,8 @@ impl std::fmt::Display for Error {
             ErrorKind::Resolve(err) => write!(f, "{:?}", err),
             ErrorKind::Typecheck(err) => write!(f, "{}", err),
             ErrorKind::Cache(err) => write!(f, "{:?}", err),
+            ErrorKind::EvalLimit(err) => write!(f, "{}", err),
+            ErrorKind::Edit(err) => write!(f, "{}", err),
         }
     }
 }
@@ -163,3 +254,13 @@ impl From<CacheError> for Error {
         ErrorKind::Cache(err).into()
     }
 }
+impl From<EvalLimitError> for Error {
+    fn from(err: EvalLimitError) -> Error {
+        ErrorKind::EvalLimit(err).into()
+    }
+}
+impl From<EditError> for Error {
+    fn from(err: EditError) -> Error {
+        ErrorKind::Edit(err).into()
+    }
+}
@@ -24,10 +24,10 @@ pub mod utils;
 use std::path::Path;
 use url::Url;
 
-use crate::error::{Error, TypeError};
+use crate::error::{Error, EvalLimitError, TypeError};
 use crate::semantics::parse;
 use crate::semantics::resolve;
-use crate::semantics::resolve::ImportLocation;
+use crate::semantics::resolve::{ImportLocation, ImportOptions};
 use crate::semantics::{typecheck, typecheck_with, Hir, Nir, Tir, Type};
 use crate::syntax::Expr;
 
@@ -80,7 +80,6 @@ impl Parsed {
     pub fn parse_binary_file(f: &Path) -> Result<Parsed, Error> {
         parse::parse_binary_file(f)
     }
-    #[allow(dead_code)]
     pub fn parse_binary(data: &[u8]) -> Result<Parsed, Error> {
         parse::parse_binary(data)
     }
@@ -88,6 +87,15 @@ impl Parsed {
     pub fn resolve<'cx>(self, cx: Ctxt<'cx>) -> Result<Resolved<'cx>, Error> {
         resolve::resolve(cx, self)
     }
+    /// Like `resolve`, but with control over which imports are allowed and how they are
+    /// fetched.
+    pub fn resolve_with_options<'cx>(
+        self,
+        cx: Ctxt<'cx>,
+        options: ImportOptions,
+    ) -> Result<Resolved<'cx>, Error> {
+        resolve::resolve_with_options(cx, self, options)
+    }
     pub fn skip_resolve<'cx>(
         self,
         cx: Ctxt<'cx>,
@@ -100,10 +108,27 @@ impl Parsed {
         self.0.clone()
     }
 
+    /// Sets the location relative to which the imports of this expression get resolved.
+    pub fn with_location(self, location: ImportLocation) -> Parsed {
+        Parsed(self.0, location)
+    }
+
     pub fn add_let_binding(self, label: syntax::Label, value: Expr) -> Parsed {
         let Parsed(expr, import_location) = self;
         Parsed(expr.add_let_binding(label, value), import_location)
     }
+
+    /// Merges the record `defaults` under this expression, as in `defaults // expr`. This is what
+    /// record completion does with the `default` field of a schema.
+    pub fn add_defaults(self, defaults: Expr) -> Parsed {
+        let Parsed(expr, import_location) = self;
+        let kind = syntax::ExprKind::Op(operations::OpKind::BinOp(
+            operations::BinOp::RightBiasedRecordMerge,
+            defaults,
+            expr,
+        ));
+        Parsed(Expr::new(kind, syntax::Span::Artificial), import_location)
+    }
 }
 
 impl<'cx> Resolved<'cx> {
@@ -134,6 +159,20 @@ impl<'cx> Typed<'cx> {
     pub fn normalize(&self, cx: Ctxt<'cx>) -> Normalized<'cx> {
         Normalized(self.hir.eval_closed_expr(cx))
     }
+    /// Reduce an expression to its normal form, failing if this goes over the limits of the
+    /// context (see `Ctxt::with_limits`). Unlike `normalize`, this evaluates the whole expression
+    /// upfront.
+    pub fn normalize_within_limits(
+        &self,
+        cx: Ctxt<'cx>,
+    ) -> Result<Normalized<'cx>, EvalLimitError> {
+        cx.check_limits()?;
+        let nf = self.normalize(cx);
+        // Converting to `Hir` forces evaluation of all the subexpressions.
+        let _ = nf.to_hir();
+        cx.check_limits()?;
+        Ok(nf)
+    }
 
     /// Converts a value back to the corresponding AST expression.
     fn to_expr(&self, cx: Ctxt<'cx>) -> Expr {
@@ -151,6 +190,18 @@ impl<'cx> Typed<'cx> {
     }
 }
 
+/// Compares two normalized values structurally. See [`Diff`](crate::syntax::Diff).
+///
+/// Like [`Normalized::to_expr`], this needs the context that the values were evaluated in, to
+/// read them back as expressions.
+pub fn diff<'cx>(
+    cx: Ctxt<'cx>,
+    old: &Normalized<'cx>,
+    new: &Normalized<'cx>,
+) -> syntax::Diff {
+    old.to_expr(cx).diff(&new.to_expr(cx))
+}
+
 impl<'cx> Normalized<'cx> {
     /// Converts a value back to the corresponding AST expression.
     pub fn to_expr(&self, cx: Ctxt<'cx>) -> Expr {
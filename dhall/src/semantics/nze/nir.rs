@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 use std::rc::Rc;
 
-use crate::builtins::{Builtin, BuiltinClosure};
+use crate::builtins::{Builtin, BuiltinClosure, HostClosure};
 use crate::operations::{BinOp, OpKind};
 use crate::semantics::nze::lazy;
 use crate::semantics::{
@@ -67,6 +67,7 @@ pub enum NirKind<'cx> {
         closure: Closure<'cx>,
     },
     AppliedBuiltin(BuiltinClosure<'cx>),
+    AppliedHostFunction(HostClosure<'cx>),
 
     Var(NzVar),
     Const(Const),
@@ -179,10 +180,11 @@ impl<'cx> Nir<'cx> {
         let hir = match self.kind() {
             NirKind::Var(v) => HirKind::Var(venv.lookup(*v)),
             NirKind::AppliedBuiltin(closure) => closure.to_hirkind(venv),
+            NirKind::AppliedHostFunction(closure) => closure.to_hirkind(venv),
             self_kind => HirKind::Expr(match self_kind {
-                NirKind::Var(..) | NirKind::AppliedBuiltin(..) => {
-                    unreachable!()
-                }
+                NirKind::Var(..)
+                | NirKind::AppliedBuiltin(..)
+                | NirKind::AppliedHostFunction(..) => unreachable!(),
                 NirKind::LamClosure {
                     binder,
                     annot,
@@ -322,6 +324,14 @@ impl<'cx> Closure<'cx> {
         Closure::ConstantClosure { body }
     }
 
+    /// The context the closure was created in, if it needs one to be applied.
+    pub fn cx(&self) -> Option<Ctxt<'cx>> {
+        match self {
+            Closure::Closure { env, .. } => Some(env.cx()),
+            Closure::ConstantClosure { .. } => None,
+        }
+    }
+
     pub fn apply(&self, val: Nir<'cx>) -> Nir<'cx> {
         match self {
             Closure::Closure { env, body, .. } => {
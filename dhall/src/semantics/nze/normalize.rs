@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 
+use crate::builtins::HostClosure;
 use crate::operations::{normalize_operation, OpKind};
 use crate::semantics::NzEnv;
 use crate::semantics::{Binder, Closure, Hir, HirKind, Nir, NirKind, TextLit};
@@ -7,8 +8,15 @@ use crate::syntax::{ExprKind, InterpolatedTextContents};
 
 pub fn apply_any<'cx>(f: &Nir<'cx>, a: Nir<'cx>) -> NirKind<'cx> {
     match f.kind() {
-        NirKind::LamClosure { closure, .. } => closure.apply(a).kind().clone(),
+        NirKind::LamClosure { closure, .. } => match closure.cx() {
+            // Leave the application unreduced once the evaluation budget is exhausted.
+            Some(cx) if !cx.try_step() => {
+                NirKind::Op(OpKind::App(f.clone(), a))
+            }
+            _ => closure.apply(a).kind().clone(),
+        },
         NirKind::AppliedBuiltin(closure) => closure.apply(a),
+        NirKind::AppliedHostFunction(closure) => closure.apply(a),
         NirKind::UnionConstructor(l, kts) => {
             NirKind::UnionLit(l.clone(), a, kts.clone())
         }
@@ -168,6 +176,7 @@ pub fn normalize_hir<'cx>(env: &NzEnv<'cx>, hir: &Hir<'cx>) -> NirKind<'cx> {
             };
             normalize_hir(env, hir)
         }
+        HirKind::HostFunction(id) => HostClosure::new(env.cx(), *id),
         HirKind::Expr(ExprKind::Lam(binder, annot, body)) => {
             let annot = annot.eval(env);
             NirKind::LamClosure {
@@ -193,7 +202,9 @@ pub fn normalize_hir<'cx>(env: &NzEnv<'cx>, hir: &Hir<'cx>) -> NirKind<'cx> {
         }
         HirKind::Expr(e) => {
             let e = e.map_ref(|hir| hir.eval(env));
-            normalize_one_layer(e)
+            let v = normalize_one_layer(e);
+            env.cx().check_size(&v);
+            v
         }
     }
 }
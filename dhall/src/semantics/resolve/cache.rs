@@ -40,7 +40,11 @@ pub struct Cache {
 
 impl Cache {
     pub fn new() -> Result<Cache, Error> {
-        let cache_dir = default_cache_dir()?;
+        Cache::new_in(default_cache_dir()?)
+    }
+
+    /// Use the given directory as the cache, creating it if needed.
+    pub fn new_in(cache_dir: PathBuf) -> Result<Cache, Error> {
         if !cache_dir.exists() {
             std::fs::create_dir_all(&cache_dir)
                 .map_err(|e| CacheError::InitialisationError { cause: e })?;
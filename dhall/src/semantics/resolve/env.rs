@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::path::PathBuf;
 
 use crate::error::{Error, ImportError};
 use crate::semantics::{check_hash, AlphaVar, Cache, ImportLocation, VarEnv};
@@ -13,9 +14,37 @@ pub struct NameEnv {
 
 pub type CyclesStack = Vec<ImportLocation>;
 
+/// Controls which imports are allowed and where their contents come from.
+#[derive(Debug, Clone)]
+pub struct ImportOptions {
+    /// Whether to allow importing from URLs.
+    pub remote: bool,
+    /// Whether to allow importing environment variables.
+    pub env: bool,
+    /// Whether to read and write the cache of imports protected by a hash.
+    pub cache: bool,
+    /// Where the cache lives. `None` means the default location.
+    pub cache_dir: Option<PathBuf>,
+    /// If set, environment variables are looked up here instead of in the process environment.
+    pub env_vars: Option<HashMap<String, String>>,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        ImportOptions {
+            remote: true,
+            env: true,
+            cache: true,
+            cache_dir: None,
+            env_vars: None,
+        }
+    }
+}
+
 /// Environment for resolving imports
 pub struct ImportEnv<'cx> {
     cx: Ctxt<'cx>,
+    options: ImportOptions,
     disk_cache: Option<Cache>, // `None` if it failed to initialize
     mem_cache: HashMap<ImportLocation, ImportResultId<'cx>>,
     stack: CyclesStack,
@@ -52,6 +81,10 @@ impl NameEnv {
             .nth(*idx)?;
         Some(AlphaVar::new(idx))
     }
+    /// The number of bound variables with this name.
+    pub fn count(&self, name: &Label) -> usize {
+        self.names.iter().filter(|n| *n == name).count()
+    }
     pub fn label_var(&self, var: AlphaVar) -> V {
         let name = &self.names[self.names.len() - 1 - var.idx()];
         let idx = self
@@ -67,9 +100,18 @@ impl NameEnv {
 
 impl<'cx> ImportEnv<'cx> {
     pub fn new(cx: Ctxt<'cx>) -> Self {
+        Self::with_options(cx, ImportOptions::default())
+    }
+    pub fn with_options(cx: Ctxt<'cx>, options: ImportOptions) -> Self {
+        let disk_cache = match &options.cache_dir {
+            _ if !options.cache => None,
+            Some(dir) => Cache::new_in(dir.clone()).ok(),
+            None => Cache::new().ok(),
+        };
         ImportEnv {
             cx,
-            disk_cache: Cache::new().ok(),
+            options,
+            disk_cache,
             mem_cache: Default::default(),
             stack: Default::default(),
         }
@@ -78,6 +120,17 @@ impl<'cx> ImportEnv<'cx> {
     pub fn cx(&self) -> Ctxt<'cx> {
         self.cx
     }
+    pub fn options(&self) -> &ImportOptions {
+        &self.options
+    }
+
+    /// Looks up an environment variable, taking the overrides into account.
+    pub fn env_var(&self, name: &str) -> Option<String> {
+        match &self.options.env_vars {
+            Some(vars) => vars.get(name).cloned(),
+            None => std::env::var(name).ok(),
+        }
+    }
 
     pub fn get_from_mem_cache(
         &self,
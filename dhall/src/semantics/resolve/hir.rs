@@ -1,7 +1,9 @@
 use crate::error::TypeError;
 use crate::semantics::{type_with, typecheck, NameEnv, Nir, NzEnv, Tir, TyEnv};
 use crate::syntax::{Expr, ExprKind, Span, V};
-use crate::{Ctxt, ImportAlternativeId, ImportId, ToExprOptions};
+use crate::{
+    Ctxt, HostFunctionId, ImportAlternativeId, ImportId, ToExprOptions,
+};
 
 /// Stores an alpha-normalized variable.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -19,6 +21,9 @@ pub enum HirKind<'cx> {
     Import(ImportId<'cx>),
     /// An import alternative. It must have been decided after resolution.
     ImportAlternative(ImportAlternativeId<'cx>, Hir<'cx>, Hir<'cx>),
+    /// A function provided by the host application. Resolved from a variable with that name that
+    /// isn't otherwise bound.
+    HostFunction(HostFunctionId<'cx>),
     // Forbidden ExprKind variants: Var, Import, Completion
     Expr(ExprKind<Hir<'cx>>),
 }
@@ -121,6 +126,11 @@ fn hir_to_expr<'cx>(
             };
             return hir_to_expr(cx, hir, opts, env);
         }
+        HirKind::HostFunction(id) => {
+            // Skip the binders of the same name, like for any free variable.
+            let name = cx[id].func.name();
+            ExprKind::Var(V(name.clone(), env.count(name)))
+        }
         HirKind::Expr(e) => {
             let e = e.map_ref_maybe_binder(|l, hir| {
                 if let Some(l) = l {
@@ -1,7 +1,6 @@
 use itertools::Itertools;
 use std::borrow::Cow;
 use std::collections::BTreeMap;
-use std::env;
 use std::path::PathBuf;
 use url::Url;
 
@@ -9,7 +8,9 @@ use crate::builtins::Builtin;
 use crate::error::ErrorBuilder;
 use crate::error::{Error, ImportError};
 use crate::operations::{BinOp, OpKind};
-use crate::semantics::{mkerr, Hir, HirKind, ImportEnv, NameEnv, Type};
+use crate::semantics::{
+    mkerr, Hir, HirKind, ImportEnv, ImportOptions, NameEnv, Type,
+};
 use crate::syntax;
 use crate::syntax::{
     Expr, ExprKind, FilePath, FilePrefix, Hash, ImportMode, ImportTarget, Span,
@@ -34,6 +35,8 @@ enum ImportLocationKind {
     Env(String),
     /// Data without a location; chaining will start from current directory.
     Missing,
+    /// Data without a location; chaining will start from the given directory.
+    Dir(PathBuf),
     /// Token to signal that thi sfile should contain no imports.
     NoImport,
 }
@@ -54,11 +57,13 @@ impl ImportLocationKind {
         Ok(match self {
             ImportLocationKind::Local(..)
             | ImportLocationKind::Env(..)
-            | ImportLocationKind::Missing => {
+            | ImportLocationKind::Missing
+            | ImportLocationKind::Dir(..) => {
                 let dir = match self {
                     ImportLocationKind::Local(path) => {
                         path.parent().unwrap().to_owned()
                     }
+                    ImportLocationKind::Dir(dir) => dir.clone(),
                     ImportLocationKind::Env(..)
                     | ImportLocationKind::Missing => std::env::current_dir()?,
                     _ => unreachable!(),
@@ -110,35 +115,35 @@ impl ImportLocationKind {
         })
     }
 
-    fn fetch_dhall(&self) -> Result<Parsed, Error> {
+    fn fetch_dhall(&self, env: &ImportEnv) -> Result<Parsed, Error> {
         Ok(match self {
             ImportLocationKind::Local(path) => Parsed::parse_file(path)?,
             ImportLocationKind::Remote(url) => {
                 Parsed::parse_remote(url.clone())?
             }
             ImportLocationKind::Env(var_name) => {
-                let val = match env::var(var_name) {
-                    Ok(val) => val,
-                    Err(_) => return Err(ImportError::MissingEnvVar.into()),
+                let val = match env.env_var(var_name) {
+                    Some(val) => val,
+                    None => return Err(ImportError::MissingEnvVar.into()),
                 };
                 Parsed::parse_str(&val)?
             }
-            ImportLocationKind::Missing => {
+            ImportLocationKind::Missing | ImportLocationKind::Dir(..) => {
                 return Err(ImportError::Missing.into())
             }
             ImportLocationKind::NoImport => unreachable!(),
         })
     }
 
-    fn fetch_text(&self) -> Result<String, Error> {
+    fn fetch_text(&self, env: &ImportEnv) -> Result<String, Error> {
         Ok(match self {
             ImportLocationKind::Local(path) => std::fs::read_to_string(path)?,
             ImportLocationKind::Remote(url) => download_http_text(url.clone())?,
-            ImportLocationKind::Env(var_name) => match env::var(var_name) {
-                Ok(val) => val,
-                Err(_) => return Err(ImportError::MissingEnvVar.into()),
+            ImportLocationKind::Env(var_name) => match env.env_var(var_name) {
+                Some(val) => val,
+                None => return Err(ImportError::MissingEnvVar.into()),
             },
-            ImportLocationKind::Missing => {
+            ImportLocationKind::Missing | ImportLocationKind::Dir(..) => {
                 return Err(ImportError::Missing.into())
             }
             ImportLocationKind::NoImport => unreachable!(),
@@ -156,7 +161,9 @@ impl ImportLocationKind {
             ImportLocationKind::Env(name) => {
                 ("Environment", Some(name.clone()))
             }
-            ImportLocationKind::Missing => ("Missing", None),
+            ImportLocationKind::Missing | ImportLocationKind::Dir(..) => {
+                ("Missing", None)
+            }
             ImportLocationKind::NoImport => unreachable!(),
         };
 
@@ -186,6 +193,13 @@ impl ImportLocation {
             mode: ImportMode::Code,
         }
     }
+    /// Dhall code without a location, whose relative imports are resolved from `dir`.
+    pub fn dhall_code_in_dir(dir: PathBuf) -> Self {
+        ImportLocation {
+            kind: ImportLocationKind::Dir(dir),
+            mode: ImportMode::Code,
+        }
+    }
     pub fn local_dhall_code(path: PathBuf) -> Self {
         ImportLocation {
             kind: ImportLocationKind::Local(path),
@@ -245,6 +259,23 @@ impl ImportLocation {
         })
     }
 
+    /// Errors if the options forbid fetching this location.
+    fn check_allowed(&self, env: &ImportEnv) -> Result<(), Error> {
+        if matches!(self.mode, ImportMode::Location) {
+            return Ok(());
+        }
+        let allowed = match self.kind {
+            ImportLocationKind::Remote(..) => env.options().remote,
+            ImportLocationKind::Env(..) => env.options().env,
+            _ => true,
+        };
+        if allowed {
+            Ok(())
+        } else {
+            Err(ImportError::Disabled(self.clone()).into())
+        }
+    }
+
     /// Fetches the expression corresponding to this location.
     fn fetch<'cx>(
         &self,
@@ -254,7 +285,7 @@ impl ImportLocation {
         let cx = env.cx();
         let typed = match self.mode {
             ImportMode::Code => {
-                let parsed = self.kind.fetch_dhall()?;
+                let parsed = self.kind.fetch_dhall(env)?;
                 let typed = parsed.resolve_with_env(env)?.typecheck(cx)?;
                 Typed {
                     // TODO: manage to keep the Nir around. Will need fixing variables.
@@ -263,7 +294,7 @@ impl ImportLocation {
                 }
             }
             ImportMode::RawText => {
-                let text = self.kind.fetch_text()?;
+                let text = self.kind.fetch_text(env)?;
                 Typed {
                     hir: Hir::new(
                         HirKind::Expr(ExprKind::TextLit(text.into())),
@@ -396,6 +427,7 @@ fn fetch_import<'cx>(
     let res_id = if let Some(res_id) = env.get_from_mem_cache(&location) {
         res_id
     } else {
+        location.check_allowed(env)?;
         // Resolve this import, making sure that recursive imports don't cycle back to the
         // current one.
         let res = env.with_cycle_detection(location.clone(), |env| {
@@ -444,7 +476,13 @@ fn traverse_accumulate<'cx>(
     let kind = match expr.kind() {
         ExprKind::Var(var) => match name_env.unlabel_var(&var) {
             Some(v) => HirKind::Var(v),
-            None => HirKind::MissingVar(var.clone()),
+            // Host functions behave like variables bound outside of every file.
+            None => match cx.lookup_host_function(&var.0) {
+                Some(id) if var.1 == name_env.count(&var.0) => {
+                    HirKind::HostFunction(id)
+                }
+                _ => HirKind::MissingVar(var.clone()),
+            },
         },
         ExprKind::Op(OpKind::BinOp(BinOp::ImportAlt, l, r)) => {
             let mut imports_l = Vec::new();
@@ -552,6 +590,15 @@ pub fn resolve<'cx>(
     parsed.resolve_with_env(&mut ImportEnv::new(cx))
 }
 
+/// Like `resolve`, but with control over which imports are allowed and how they are fetched.
+pub fn resolve_with_options<'cx>(
+    cx: Ctxt<'cx>,
+    parsed: Parsed,
+    options: ImportOptions,
+) -> Result<Resolved<'cx>, Error> {
+    parsed.resolve_with_env(&mut ImportEnv::with_options(cx, options))
+}
+
 /// Resolves names, and errors if we find any imports.
 pub fn skip_resolve<'cx>(
     cx: Ctxt<'cx>,
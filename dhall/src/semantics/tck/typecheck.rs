@@ -193,6 +193,9 @@ pub fn type_with<'cx, 'hir>(
             let typed = env.cx()[import].unwrap_result();
             Tir::from_hir(hir, typed.ty.clone())
         }
+        HirKind::HostFunction(id) => {
+            Tir::from_hir(hir, env.cx()[id].ty.clone())
+        }
         HirKind::ImportAlternative(alt, left, right) => {
             let hir = if env.cx()[alt].unwrap_selected() {
                 left
@@ -0,0 +1,332 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+
+use ansi_term::Colour;
+
+use crate::operations::OpKind;
+use crate::syntax::text::printer::FmtLabel;
+use crate::syntax::{Expr, ExprKind, Label};
+
+/// Above this many pairs of list elements to compare, lists are compared element by element
+/// instead of looking for insertions and deletions.
+const MAX_LIST_COMPARISONS: usize = 1_000_000;
+
+/// Whether [`Diff::render`] colors removed values in red and added ones in green, like the
+/// `colored-diff` output of the test suite.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorChoice {
+    Always,
+    Never,
+    /// Colors the output if stdout is a terminal and the `NO_COLOR` environment variable is not
+    /// set.
+    Auto,
+}
+
+impl ColorChoice {
+    fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                let no_color = matches!(
+                    std::env::var_os("NO_COLOR"),
+                    Some(v) if !v.is_empty()
+                );
+                !no_color && atty::is(atty::Stream::Stdout) && enable_ansi()
+            }
+        }
+    }
+}
+
+#[cfg(windows)]
+fn enable_ansi() -> bool {
+    ansi_term::enable_ansi_support().is_ok()
+}
+
+#[cfg(not(windows))]
+fn enable_ansi() -> bool {
+    true
+}
+
+/// The differences between two Dhall values, as computed by [`Expr::diff`].
+///
+/// This is a tree that follows the structure of the values: only the parts that differ are
+/// broken down, down to the smallest expressions that changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Diff {
+    /// The two values are equal.
+    Same,
+    /// A value that is only in the new expression, e.g. a new record field or list element.
+    Added(Expr),
+    /// A value that is only in the old expression.
+    Removed(Expr),
+    /// Two values that differ and can't be compared further, e.g. two different numbers, a value
+    /// whose type changed, or a union value that changed alternatives.
+    Changed { old: Expr, new: Expr },
+    /// Two records, with the fields that differ. Fields that are the same are omitted.
+    Record(BTreeMap<Label, Diff>),
+    /// Two record types, with the fields that differ.
+    RecordType(BTreeMap<Label, Diff>),
+    /// Two lists. This contains an entry for each element of either list, in order: elements
+    /// that were inserted or deleted are `Added` or `Removed`, and other elements are compared
+    /// with their counterpart in the other list.
+    List(Vec<Diff>),
+    /// Two values of the same union alternative, with different contents.
+    Union(Label, Box<Diff>),
+    /// Two `Some` values with different contents.
+    Optional(Box<Diff>),
+}
+
+impl Expr {
+    /// Compares two expressions structurally. The expressions should be in normal form (see
+    /// [`crate::diff`]), so that the differences are differences in values rather than in how
+    /// they were written.
+    pub fn diff(&self, new: &Expr) -> Diff {
+        use ExprKind::*;
+        if self == new {
+            return Diff::Same;
+        }
+        match (self.kind(), new.kind()) {
+            (RecordLit(old), RecordLit(new)) => {
+                Diff::Record(diff_fields(old, new))
+            }
+            (RecordType(old), RecordType(new)) => {
+                Diff::RecordType(diff_fields(old, new))
+            }
+            (NEListLit(old), NEListLit(new)) => {
+                Diff::List(diff_lists(old, new))
+            }
+            (EmptyListLit(_), NEListLit(new)) => {
+                Diff::List(new.iter().cloned().map(Diff::Added).collect())
+            }
+            (NEListLit(old), EmptyListLit(_)) => {
+                Diff::List(old.iter().cloned().map(Diff::Removed).collect())
+            }
+            (SomeLit(old), SomeLit(new)) => {
+                Diff::Optional(Box::new(old.diff(new)))
+            }
+            (Op(OpKind::App(f, old)), Op(OpKind::App(g, new))) if f == g => {
+                match f.kind() {
+                    Op(OpKind::Field(ty, l))
+                        if matches!(ty.kind(), UnionType(_)) =>
+                    {
+                        Diff::Union(l.clone(), Box::new(old.diff(new)))
+                    }
+                    _ => self.changed(new),
+                }
+            }
+            _ => self.changed(new),
+        }
+    }
+
+    fn changed(&self, new: &Expr) -> Diff {
+        Diff::Changed {
+            old: self.clone(),
+            new: new.clone(),
+        }
+    }
+}
+
+fn diff_fields(
+    old: &BTreeMap<Label, Expr>,
+    new: &BTreeMap<Label, Expr>,
+) -> BTreeMap<Label, Diff> {
+    let mut fields = BTreeMap::new();
+    for (k, x) in old {
+        let diff = match new.get(k) {
+            Some(y) => x.diff(y),
+            None => Diff::Removed(x.clone()),
+        };
+        if diff != Diff::Same {
+            fields.insert(k.clone(), diff);
+        }
+    }
+    for (k, y) in new {
+        if !old.contains_key(k) {
+            fields.insert(k.clone(), Diff::Added(y.clone()));
+        }
+    }
+    fields
+}
+
+// Matches the elements of the two lists with a longest common subsequence. The elements in
+// between are compared pairwise.
+fn diff_lists(old: &[Expr], new: &[Expr]) -> Vec<Diff> {
+    let prefix = old.iter().zip(new).take_while(|(x, y)| x == y).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let (old_mid, new_mid) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+
+    let mut diffs = vec![Diff::Same; prefix];
+    if old_mid.len() * new_mid.len() > MAX_LIST_COMPARISONS {
+        diff_unmatched(old_mid, new_mid, &mut diffs);
+    } else {
+        // lcs[i][j] is the length of the longest common subsequence of old_mid[i..] and
+        // new_mid[j..].
+        let mut lcs = vec![vec![0usize; new_mid.len() + 1]; old_mid.len() + 1];
+        for i in (0..old_mid.len()).rev() {
+            for j in (0..new_mid.len()).rev() {
+                lcs[i][j] = if old_mid[i] == new_mid[j] {
+                    lcs[i + 1][j + 1] + 1
+                } else {
+                    lcs[i + 1][j].max(lcs[i][j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        let (mut old_start, mut new_start) = (0, 0);
+        while i < old_mid.len() && j < new_mid.len() {
+            if old_mid[i] == new_mid[j] {
+                diff_unmatched(
+                    &old_mid[old_start..i],
+                    &new_mid[new_start..j],
+                    &mut diffs,
+                );
+                diffs.push(Diff::Same);
+                i += 1;
+                j += 1;
+                old_start = i;
+                new_start = j;
+            } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        diff_unmatched(
+            &old_mid[old_start..],
+            &new_mid[new_start..],
+            &mut diffs,
+        );
+    }
+    diffs.extend(vec![Diff::Same; suffix]);
+    diffs
+}
+
+// Elements that have no equal counterpart are compared pairwise, and the rest are added or
+// removed.
+fn diff_unmatched(old: &[Expr], new: &[Expr], diffs: &mut Vec<Diff>) {
+    for (x, y) in old.iter().zip(new) {
+        diffs.push(x.diff(y));
+    }
+    for x in old.iter().skip(new.len()) {
+        diffs.push(Diff::Removed(x.clone()));
+    }
+    for y in new.iter().skip(old.len()) {
+        diffs.push(Diff::Added(y.clone()));
+    }
+}
+
+impl Diff {
+    /// Renders the differences, one line per changed value, with lines starting with `-` for
+    /// old values and `+` for new ones. Each line shows where the value is, in the same format
+    /// as the paths in deserialization errors, e.g. `.servers[2].port`. Depending on `color`, old
+    /// values are shown in red and new values in green, using terminal escape codes.
+    ///
+    /// ```text
+    /// - .servers[1].port = 80
+    /// + .servers[1].port = 8080
+    /// + .servers[2] = { host = "c", port = 80 }
+    /// ```
+    pub fn render(&self, color: ColorChoice) -> String {
+        let color = color.enabled();
+        let mut lines = Vec::new();
+        self.render_at(&mut String::new(), " = ", &mut lines);
+        let lines: Vec<String> = lines
+            .into_iter()
+            .map(|(sign, line)| {
+                let line = format!("{} {}", sign, line);
+                match (color, sign) {
+                    (false, _) => line,
+                    (true, '-') => Colour::Red.paint(line).to_string(),
+                    (true, _) => Colour::Green.paint(line).to_string(),
+                }
+            })
+            .collect();
+        lines.join("\n")
+    }
+
+    /// Whether the two values were equal.
+    pub fn is_same(&self) -> bool {
+        *self == Diff::Same
+    }
+
+    fn render_at(
+        &self,
+        path: &mut String,
+        sep: &str,
+        lines: &mut Vec<(char, String)>,
+    ) {
+        let line = |e: &Expr| {
+            if path.is_empty() {
+                e.to_string()
+            } else {
+                format!("{}{}{}", path, sep, e)
+            }
+        };
+        match self {
+            Diff::Same => {}
+            Diff::Added(e) => lines.push(('+', line(e))),
+            Diff::Removed(e) => lines.push(('-', line(e))),
+            Diff::Changed { old, new } => {
+                lines.push(('-', line(old)));
+                lines.push(('+', line(new)));
+            }
+            Diff::Record(fields) | Diff::RecordType(fields) => {
+                let sep = match self {
+                    Diff::RecordType(_) => " : ",
+                    _ => " = ",
+                };
+                for (k, diff) in fields {
+                    let len = path.len();
+                    path.push_str(&format!(".{}", FmtLabel(k)));
+                    diff.render_at(path, sep, lines);
+                    path.truncate(len);
+                }
+            }
+            Diff::List(elems) => {
+                // Removed elements are numbered as in the old list, and others as in the new
+                // list.
+                let (mut old_i, mut new_i) = (0, 0);
+                for diff in elems {
+                    let i = match diff {
+                        Diff::Removed(_) => old_i,
+                        _ => new_i,
+                    };
+                    let len = path.len();
+                    path.push_str(&format!("[{}]", i));
+                    diff.render_at(path, sep, lines);
+                    path.truncate(len);
+                    match diff {
+                        Diff::Removed(_) => old_i += 1,
+                        Diff::Added(_) => new_i += 1,
+                        _ => {
+                            old_i += 1;
+                            new_i += 1;
+                        }
+                    }
+                }
+            }
+            Diff::Union(l, diff) => {
+                let len = path.len();
+                path.push_str(&format!(".{}", FmtLabel(l)));
+                diff.render_at(path, sep, lines);
+                path.truncate(len);
+            }
+            Diff::Optional(diff) => diff.render_at(path, sep, lines),
+        }
+    }
+}
+
+impl Display for Diff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.render(ColorChoice::Never))
+    }
+}
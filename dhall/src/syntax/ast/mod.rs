@@ -1,3 +1,5 @@
+mod diff;
+pub use diff::*;
 mod expr;
 pub use expr::*;
 mod import;
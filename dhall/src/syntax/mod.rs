@@ -8,7 +8,9 @@
 mod ast;
 pub use crate::syntax::ast::visitor;
 pub use crate::syntax::ast::*;
+pub use crate::syntax::text::cst::*;
+pub use crate::syntax::text::edit::*;
+pub use crate::syntax::text::layout::*;
 pub use crate::syntax::text::parser::*;
-pub use crate::syntax::text::printer::*;
 pub mod binary;
 pub mod text;
@@ -0,0 +1,352 @@
+use std::fmt;
+
+use crate::syntax::text::parser::Rule;
+
+/// Source text that doesn't change the meaning of the expression around it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Trivia {
+    /// Spaces and tabs, after at most one line break.
+    Whitespace(String),
+    /// A `-- ...` comment, without the line break that ends it.
+    LineComment(String),
+    /// A `{- ... -}` comment, which can contain nested ones.
+    BlockComment(String),
+}
+
+/// A child of a [`SyntaxNode`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    /// Keywords and punctuation, or the whole text of a node that has no children.
+    Token(String),
+    /// Trivia that is not next to a node, e.g. between two tokens.
+    Trivia(Trivia),
+}
+
+/// A node of the concrete syntax tree of some Dhall source text, as produced by
+/// [`parse_cst`](crate::syntax::parse_cst).
+///
+/// There is one node for each rule of the grammar that [`parse_expr`](crate::syntax::parse_expr)
+/// looks at, and the tree keeps every byte of the text: printing a node gives back the text it
+/// was parsed from. Comments and whitespace are attached to the nodes next to them: the ones
+/// before a node are its leading trivia, and a comment at the end of the line of a node is its
+/// trailing trivia. Where several nodes start or end at the same place, trivia goes to the
+/// outermost one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxNode {
+    kind: Rule,
+    leading: Vec<Trivia>,
+    children: Vec<SyntaxElement>,
+    trailing: Vec<Trivia>,
+}
+
+impl Trivia {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Trivia::Whitespace(s)
+            | Trivia::LineComment(s)
+            | Trivia::BlockComment(s) => s,
+        }
+    }
+
+    pub fn is_comment(&self) -> bool {
+        !matches!(self, Trivia::Whitespace(_))
+    }
+
+    /// Whether this is whitespace that starts a new line.
+    pub(crate) fn starts_line(&self) -> bool {
+        match self {
+            Trivia::Whitespace(s) => {
+                s.starts_with('\n') || s.starts_with("\r\n")
+            }
+            _ => false,
+        }
+    }
+}
+
+impl SyntaxElement {
+    pub(crate) fn text_len(&self) -> usize {
+        match self {
+            SyntaxElement::Node(n) => n.text_len(),
+            SyntaxElement::Token(s) => s.len(),
+            SyntaxElement::Trivia(t) => t.as_str().len(),
+        }
+    }
+}
+
+/// Splits text that lies between the nodes of the tree into trivia and tokens.
+pub(crate) fn lex_trivia(text: &str) -> Vec<SyntaxElement> {
+    let is_blank = |c: char| c == ' ' || c == '\t';
+    let mut elements = Vec::new();
+    let mut rest = text;
+    while !rest.is_empty() {
+        let (len, element): (usize, fn(String) -> SyntaxElement) =
+            if rest.starts_with("--") {
+                let end = rest.find('\n').unwrap_or(rest.len());
+                let end = if rest[..end].ends_with('\r') {
+                    end - 1
+                } else {
+                    end
+                };
+                (end, |s| SyntaxElement::Trivia(Trivia::LineComment(s)))
+            } else if rest.starts_with("{-") {
+                (block_comment_len(rest), |s| {
+                    SyntaxElement::Trivia(Trivia::BlockComment(s))
+                })
+            } else if rest.starts_with(&[' ', '\t', '\r', '\n'][..]) {
+                // A line break always starts a new run of whitespace, so that what is at the
+                // end of a line can be told apart from the lines after it.
+                let line_break = if rest.starts_with("\r\n") {
+                    2
+                } else if rest.starts_with('\n') {
+                    1
+                } else {
+                    0
+                };
+                let blanks = rest[line_break..]
+                    .find(|c| !is_blank(c))
+                    .unwrap_or_else(|| rest.len() - line_break);
+                let len = std::cmp::max(line_break + blanks, 1);
+                (len, |s| SyntaxElement::Trivia(Trivia::Whitespace(s)))
+            } else {
+                let len = rest
+                    .char_indices()
+                    .skip(1)
+                    .find(|(i, c)| {
+                        is_blank(*c)
+                            || *c == '\r'
+                            || *c == '\n'
+                            || rest[*i..].starts_with("--")
+                            || rest[*i..].starts_with("{-")
+                    })
+                    .map_or(rest.len(), |(i, _)| i);
+                (len, SyntaxElement::Token)
+            };
+        elements.push(element(rest[..len].to_owned()));
+        rest = &rest[len..];
+    }
+    elements
+}
+
+/// The length of the block comment at the start of `text`, or the length of `text` if the
+/// comment is not closed.
+fn block_comment_len(text: &str) -> usize {
+    let bytes = text.as_bytes();
+    let mut depth = 0;
+    let mut i = 0;
+    while i + 1 < bytes.len() {
+        match &bytes[i..i + 2] {
+            b"{-" => {
+                depth += 1;
+                i += 2;
+            }
+            b"-}" => {
+                depth -= 1;
+                i += 2;
+                if depth == 0 {
+                    return i;
+                }
+            }
+            _ => i += 1,
+        }
+    }
+    text.len()
+}
+
+/// Moves trivia from between `elements` to the nodes next to it.
+fn attach_trivia(elements: Vec<SyntaxElement>) -> Vec<SyntaxElement> {
+    fn flush(
+        out: &mut Vec<SyntaxElement>,
+        run: &mut Vec<Trivia>,
+        next: Option<&mut SyntaxElement>,
+    ) {
+        let mut run = std::mem::take(run);
+        if let Some(SyntaxElement::Node(prev)) = out.last_mut() {
+            let same_line = run
+                .iter()
+                .position(Trivia::starts_line)
+                .unwrap_or(run.len());
+            if run[..same_line].iter().any(Trivia::is_comment) {
+                prev.trailing.extend(run.drain(..same_line));
+            }
+        }
+        match next {
+            Some(SyntaxElement::Node(next)) => {
+                run.append(&mut next.leading);
+                next.leading = run;
+            }
+            _ => out.extend(run.into_iter().map(SyntaxElement::Trivia)),
+        }
+    }
+
+    let mut out = Vec::with_capacity(elements.len());
+    let mut run = Vec::new();
+    for mut element in elements {
+        match element {
+            SyntaxElement::Trivia(t) => run.push(t),
+            _ => {
+                flush(&mut out, &mut run, Some(&mut element));
+                out.push(element);
+            }
+        }
+    }
+    flush(&mut out, &mut run, None);
+    out
+}
+
+impl SyntaxNode {
+    /// Makes a node out of its children, attaching the trivia among them to the nodes next to it.
+    pub(crate) fn new(kind: Rule, children: Vec<SyntaxElement>) -> Self {
+        SyntaxNode {
+            kind,
+            leading: Vec::new(),
+            children: attach_trivia(children),
+            trailing: Vec::new(),
+        }
+    }
+
+    /// The rule of the grammar that this node was parsed with.
+    pub fn kind(&self) -> Rule {
+        self.kind
+    }
+
+    pub fn leading_trivia(&self) -> &[Trivia] {
+        &self.leading
+    }
+
+    pub fn children(&self) -> &[SyntaxElement] {
+        &self.children
+    }
+
+    pub fn trailing_trivia(&self) -> &[Trivia] {
+        &self.trailing
+    }
+
+    /// The child nodes, with their indices among all the children.
+    pub(crate) fn nodes(&self) -> impl Iterator<Item = (usize, &SyntaxNode)> {
+        self.children
+            .iter()
+            .enumerate()
+            .filter_map(|(i, c)| match c {
+                SyntaxElement::Node(n) => Some((i, n)),
+                _ => None,
+            })
+    }
+
+    pub(crate) fn child(&self, i: usize) -> &SyntaxNode {
+        match &self.children[i] {
+            SyntaxElement::Node(n) => n,
+            _ => panic!("child {} of a {:?} node is not a node", i, self.kind),
+        }
+    }
+
+    pub(crate) fn child_mut(&mut self, i: usize) -> &mut SyntaxNode {
+        match &mut self.children[i] {
+            SyntaxElement::Node(n) => n,
+            _ => panic!("child {} of a {:?} node is not a node", i, self.kind),
+        }
+    }
+
+    pub(crate) fn children_mut(&mut self) -> &mut Vec<SyntaxElement> {
+        &mut self.children
+    }
+
+    pub(crate) fn trailing_mut(&mut self) -> &mut Vec<Trivia> {
+        &mut self.trailing
+    }
+
+    /// The node alone, without its leading and trailing trivia.
+    pub(crate) fn without_trivia(mut self) -> Self {
+        self.leading.clear();
+        self.trailing.clear();
+        self
+    }
+
+    /// Takes the place of `old` in the tree, along with its trivia.
+    pub(crate) fn replace(&mut self, old: &mut SyntaxNode) {
+        self.leading = std::mem::take(&mut old.leading);
+        self.trailing = std::mem::take(&mut old.trailing);
+        std::mem::swap(self, old);
+    }
+
+    /// Moves the trivia of the child nodes back among the children, so that they can be moved
+    /// around. [`attach_trivia`](SyntaxNode::attach_trivia) undoes this.
+    pub(crate) fn detach_trivia(&mut self) {
+        let mut out = Vec::with_capacity(self.children.len());
+        for element in std::mem::take(&mut self.children) {
+            match element {
+                SyntaxElement::Node(mut n) => {
+                    let leading = std::mem::take(&mut n.leading);
+                    let trailing = std::mem::take(&mut n.trailing);
+                    out.extend(leading.into_iter().map(SyntaxElement::Trivia));
+                    out.push(SyntaxElement::Node(n));
+                    out.extend(trailing.into_iter().map(SyntaxElement::Trivia));
+                }
+                element => out.push(element),
+            }
+        }
+        self.children = out;
+    }
+
+    pub(crate) fn attach_trivia(&mut self) {
+        self.children = attach_trivia(std::mem::take(&mut self.children));
+    }
+
+    /// The length of the printed node, trivia included.
+    pub(crate) fn text_len(&self) -> usize {
+        self.leading_len() + self.content_len() + trivia_len(&self.trailing)
+    }
+
+    pub(crate) fn leading_len(&self) -> usize {
+        trivia_len(&self.leading)
+    }
+
+    /// The length of the printed node, without its own trivia.
+    pub(crate) fn content_len(&self) -> usize {
+        self.children.iter().map(SyntaxElement::text_len).sum()
+    }
+
+    /// The printed node, without its own trivia.
+    pub(crate) fn content(&self) -> String {
+        let mut out = String::with_capacity(self.content_len());
+        for c in &self.children {
+            out.push_str(&c.to_string());
+        }
+        out
+    }
+}
+
+fn trivia_len(trivia: &[Trivia]) -> usize {
+    trivia.iter().map(|t| t.as_str().len()).sum()
+}
+
+impl fmt::Display for Trivia {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Display for SyntaxElement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SyntaxElement::Node(n) => n.fmt(f),
+            SyntaxElement::Token(s) => f.write_str(s),
+            SyntaxElement::Trivia(t) => t.fmt(f),
+        }
+    }
+}
+
+impl fmt::Display for SyntaxNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for t in &self.leading {
+            t.fmt(f)?;
+        }
+        for c in &self.children {
+            c.fmt(f)?;
+        }
+        for t in &self.trailing {
+            t.fmt(f)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,774 @@
+use std::fmt;
+
+use crate::error::{EditError, Result};
+use crate::syntax::text::parser::OPERATORS;
+use crate::syntax::text::printer::FmtLabel;
+use crate::syntax::{
+    lex_trivia, parse_cst, Expr, Rule, SyntaxElement, SyntaxNode, Trivia,
+};
+
+/// Dhall source text, kept as a concrete syntax tree that can be edited without losing the
+/// comments, `let`-bindings and formatting of the parts that don't change.
+///
+/// The tree holds every byte of the text, with comments and whitespace attached to the nodes
+/// next to them (see [`SyntaxNode`]). Each edit only prints the expression it adds or replaces,
+/// and puts its nodes in the tree: a replaced node keeps the comments around it, and the rest of
+/// the tree is left as it is. Printing an unedited document gives back the text it was parsed
+/// from.
+///
+/// Values are found by their path, e.g. `.services.web.port` or `.hosts[2]`: record fields (`.x`,
+/// or ``.`x y` `` for labels that need quoting), list indices (`[2]`) and union alternatives
+/// (`.Foo`, which looks into the contents of `Foo x`). The empty path points to the whole
+/// document. Paths look through `let`s, type annotations and `Some`, and through variables bound
+/// by a `let` of the document: editing the contents of such a variable edits the `let`. They also
+/// look into the record of a completion `T::{ x = 1 }`, and into the record of `a // b` that the
+/// field comes from; fields that neither side has are added to the record of the completion, or
+/// to `b`.
+#[derive(Debug, Clone)]
+pub struct Document {
+    text: String,
+    root: SyntaxNode,
+}
+
+#[derive(Debug, Clone)]
+enum Step {
+    Field(String),
+    Index(usize),
+}
+
+/// The position of a node in the tree, as the indices of the children that lead to it.
+type NodePath = Vec<usize>;
+
+/// Something that a path can point to.
+#[derive(Debug, Clone)]
+enum Place {
+    Node(NodePath),
+    /// The operands of the operator expression at the path, between two of its children
+    /// (included).
+    Operands(NodePath, usize, usize),
+    /// The record entry at the path, like `a.b.c = 1`, after its first labels.
+    Dotted(NodePath, usize),
+    /// A field that is written in several entries, like `{ a.b = 1, a.c = 2 }`.
+    Scattered,
+}
+
+/// The `let`-bindings in scope, with the path of their value.
+type Env = Vec<(String, NodePath)>;
+
+fn parse_path(path: &str) -> Result<Vec<Step>> {
+    let invalid = || EditError::InvalidPath(path.to_owned());
+    let mut steps = Vec::new();
+    let mut rest = path;
+    while !rest.is_empty() {
+        if let Some(r) = rest.strip_prefix('[') {
+            let end = r.find(']').ok_or_else(invalid)?;
+            let i = r[..end].parse().map_err(|_| invalid())?;
+            steps.push(Step::Index(i));
+            rest = &r[end + 1..];
+        } else if let Some(r) = rest.strip_prefix('.') {
+            let (label, r) = if let Some(r) = r.strip_prefix('`') {
+                let end = r.find('`').ok_or_else(invalid)?;
+                (&r[..end], &r[end + 1..])
+            } else {
+                let end = r.find(&['.', '['][..]).unwrap_or(r.len());
+                (&r[..end], &r[end..])
+            };
+            steps.push(Step::Field(label.to_owned()));
+            rest = r;
+        } else {
+            return Err(invalid().into());
+        }
+    }
+    Ok(steps)
+}
+
+fn child_path(path: &[usize], i: usize) -> NodePath {
+    let mut path = path.to_vec();
+    path.push(i);
+    path
+}
+
+/// The name written by a label node.
+fn label(node: &SyntaxNode) -> String {
+    match node.nodes().next() {
+        Some((_, n)) => label(n),
+        None => node.content(),
+    }
+}
+
+/// The labels of a record entry, and the index of its value unless it is a pun like `{ x }`.
+fn entry(node: &SyntaxNode) -> (Vec<String>, Option<usize>) {
+    let mut labels = Vec::new();
+    let mut value = None;
+    for (i, n) in node.nodes() {
+        if n.kind() == Rule::expression {
+            value = Some(i);
+        } else {
+            labels.push(label(n));
+        }
+    }
+    (labels, value)
+}
+
+/// The precedence of an operator node, if it is one.
+fn precedence(node: &SyntaxNode) -> Option<usize> {
+    OPERATORS.iter().position(|op| *op == node.kind())
+}
+
+/// The node of kind `kind` that `node` is made of, if there is one, so that it can take the place
+/// of a node of that kind.
+fn fitting(mut node: SyntaxNode, kind: Rule) -> Option<SyntaxNode> {
+    loop {
+        if node.kind() == kind {
+            return Some(node);
+        }
+        let i = {
+            let mut nodes = node.nodes();
+            match (nodes.next(), nodes.next()) {
+                (Some((i, _)), None) if is_wrapper(node.kind()) => i,
+                _ => return None,
+            }
+        };
+        node = node.child(i).clone();
+    }
+}
+
+/// Whether a node of that kind with a single child node means the same as that child, e.g. an
+/// `application_expression` with no argument or a `primitive_expression` in parentheses.
+fn is_wrapper(kind: Rule) -> bool {
+    matches!(
+        kind,
+        Rule::final_expression
+            | Rule::expression
+            | Rule::operator_expression
+            | Rule::application_expression
+            | Rule::first_application_expression
+            | Rule::completion_expression
+            | Rule::selector_expression
+            | Rule::primitive_expression
+            | Rule::record_type_or_literal
+            | Rule::identifier
+    )
+}
+
+/// The node that `node` at `path` stands for, looking through the nodes that only wrap another
+/// one, and its path.
+fn inner(mut node: &SyntaxNode, mut path: NodePath) -> (NodePath, &SyntaxNode) {
+    while is_wrapper(node.kind()) {
+        let mut nodes = node.nodes();
+        match (nodes.next(), nodes.next()) {
+            (Some((i, n)), None) => {
+                path.push(i);
+                node = n;
+            }
+            _ => break,
+        }
+    }
+    (path, node)
+}
+
+/// Parses the printed `value`, as the node of kind `kind` that it replaces. The value is put in
+/// parentheses if it would not parse as such a node otherwise.
+fn value_node(value: &str, kind: Rule) -> Result<SyntaxNode> {
+    if let Some(node) = fitting(parse_cst(value)?, kind) {
+        return Ok(node);
+    }
+    let node = parse_cst(&format!("({})", value))?;
+    Ok(fitting(node, kind).expect("a value in parentheses fits anywhere"))
+}
+
+/// Parses the printed list or record `text`, which has a single item, and returns the node of
+/// that item.
+fn item_node(text: &str) -> Result<SyntaxNode> {
+    let node = parse_cst(text)?;
+    let (_, items) = inner(&node, Vec::new());
+    let item = items.nodes().next().unwrap().1.clone();
+    Ok(item.without_trivia())
+}
+
+impl Document {
+    /// Parses Dhall source text. Imports are kept as they are written.
+    pub fn parse(text: &str) -> Result<Document> {
+        Ok(Document {
+            text: text.to_owned(),
+            root: parse_cst(text)?,
+        })
+    }
+
+    /// The current source text.
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// The concrete syntax tree of the current source text.
+    pub fn syntax(&self) -> &SyntaxNode {
+        &self.root
+    }
+
+    /// Sets the value at `path`. If the path points to a missing field of a record, the field is
+    /// added at the end of the record.
+    pub fn set(&mut self, path: &str, value: &Expr) -> Result<()> {
+        let mut steps = parse_path(path)?;
+        let not_found = || EditError::NotFound(path.to_owned());
+        let not_in_source = || EditError::NotInSource(path.to_owned());
+        let last = match steps.pop() {
+            Some(last) => last,
+            None => {
+                let (i, _) = self.root.nodes().next().unwrap();
+                return self.replace(&[i], value);
+            }
+        };
+        let mut env = Env::new();
+        let parent = self.locate(path, &steps, &mut env)?;
+        let parent = match &last {
+            Step::Field(l) => self.field_record(parent, l, &mut env),
+            Step::Index(_) => parent,
+        };
+        let parent = match parent {
+            Place::Node(parent) => parent,
+            Place::Dotted(entry_path, n) => {
+                let (labels, value_index) = entry(self.node(&entry_path));
+                return match &last {
+                    Step::Field(l)
+                        if labels[n] == *l && n + 1 == labels.len() =>
+                    {
+                        let i = value_index.unwrap();
+                        self.replace(&child_path(&entry_path, i), value)
+                    }
+                    _ => Err(not_in_source().into()),
+                };
+            }
+            Place::Operands(..) => return Err(not_found().into()),
+            Place::Scattered => return Err(not_in_source().into()),
+        };
+        let (inner, node) = self.inner(&parent);
+        match (node.kind(), last) {
+            (Rule::non_empty_record_literal, Step::Field(l)) => {
+                match self.field(&inner, &l, &mut env) {
+                    Some(Place::Node(x)) => self.replace(&x, value),
+                    Some(_) => Err(not_in_source().into()),
+                    None => {
+                        let field =
+                            format!("{} = {}", FmtLabel(&l.into()), value);
+                        let item = item_node(&format!("{{ {} }}", field))?;
+                        let last = node.nodes().last().unwrap().0;
+                        self.append_after(&parent, &inner, last, item)
+                    }
+                }
+            }
+            (Rule::empty_record_literal, Step::Field(l)) => {
+                let field = format!("{} = {}", FmtLabel(&l.into()), value);
+                self.replace_with(&parent, &format!("{{ {} }}", field))
+            }
+            (Rule::non_empty_list_literal, Step::Index(i)) => {
+                let (x, _) = node.nodes().nth(i).ok_or_else(not_found)?;
+                self.replace(&child_path(&inner, x), value)
+            }
+            (Rule::application_expression, Step::Field(l)) => {
+                match self.alternative(&inner, &l) {
+                    Some(x) => self.replace(&x, value),
+                    None => Err(not_found().into()),
+                }
+            }
+            _ => Err(not_found().into()),
+        }
+    }
+
+    /// Inserts `value` in the list at `path`, so that it ends up at position `index`.
+    pub fn insert(
+        &mut self,
+        path: &str,
+        index: usize,
+        value: &Expr,
+    ) -> Result<()> {
+        let steps = parse_path(path)?;
+        let list = match self.locate(path, &steps, &mut Env::new())? {
+            Place::Node(list) => list,
+            _ => return Err(EditError::NotAList(path.to_owned()).into()),
+        };
+        let (inner, node) = self.inner(&list);
+        let items: Vec<usize> = node.nodes().map(|(i, _)| i).collect();
+        match node.kind() {
+            // This replaces the type annotation too.
+            Rule::empty_list_literal if index == 0 => {
+                self.replace_with(&list, &format!("[ {} ]", value))
+            }
+            Rule::non_empty_list_literal if index == 0 => {
+                let item = item_node(&format!("[ {} ]", value))?;
+                let last =
+                    self.range(&child_path(&inner, *items.last().unwrap())).1;
+                let (sep, _) = self.separator(self.range(&list).0, last);
+                let list_node = self.node_mut(&inner);
+                list_node.detach_trivia();
+                let first = list_node.nodes().next().map(|(i, _)| i).unwrap();
+                let mut new = vec![SyntaxElement::Node(item)];
+                new.extend(lex_trivia(&sep));
+                list_node.children_mut().splice(first..first, new);
+                list_node.attach_trivia();
+                self.refresh();
+                Ok(())
+            }
+            Rule::non_empty_list_literal if index <= items.len() => {
+                let item = item_node(&format!("[ {} ]", value))?;
+                self.append_after(&list, &inner, items[index - 1], item)
+            }
+            Rule::empty_list_literal | Rule::non_empty_list_literal => {
+                Err(EditError::IndexOutOfBounds(path.to_owned(), index).into())
+            }
+            _ => Err(EditError::NotAList(path.to_owned()).into()),
+        }
+    }
+
+    /// Adds `value` at the end of the list at `path`.
+    pub fn push(&mut self, path: &str, value: &Expr) -> Result<()> {
+        let steps = parse_path(path)?;
+        let not_a_list = || EditError::NotAList(path.to_owned());
+        let list = match self.locate(path, &steps, &mut Env::new())? {
+            Place::Node(list) => list,
+            _ => return Err(not_a_list().into()),
+        };
+        let (_, node) = self.inner(&list);
+        let len = match node.kind() {
+            Rule::empty_list_literal => 0,
+            Rule::non_empty_list_literal => node.nodes().count(),
+            _ => return Err(not_a_list().into()),
+        };
+        self.insert(path, len, value)
+    }
+
+    /// Replaces the value bound by the first `let` named `name`, from the outside in. A type
+    /// annotation on the binding is kept.
+    pub fn set_let(&mut self, name: &str, value: &Expr) -> Result<()> {
+        fn find_let(
+            node: &SyntaxNode,
+            name: &str,
+            path: &mut NodePath,
+        ) -> bool {
+            if node.kind() == Rule::let_binding {
+                let mut nodes = node.nodes();
+                if label(nodes.next().unwrap().1) == name {
+                    path.push(nodes.last().unwrap().0);
+                    return true;
+                }
+            }
+            for (i, n) in node.nodes() {
+                path.push(i);
+                if find_let(n, name, path) {
+                    return true;
+                }
+                path.pop();
+            }
+            false
+        }
+        let mut path = NodePath::new();
+        if !find_let(&self.root, name, &mut path) {
+            return Err(EditError::MissingLet(name.to_owned()).into());
+        }
+        self.replace(&path, value)
+    }
+
+    fn node(&self, path: &[usize]) -> &SyntaxNode {
+        path.iter().fold(&self.root, |node, i| node.child(*i))
+    }
+
+    fn node_mut(&mut self, path: &[usize]) -> &mut SyntaxNode {
+        path.iter()
+            .fold(&mut self.root, |node, i| node.child_mut(*i))
+    }
+
+    fn inner(&self, path: &[usize]) -> (NodePath, &SyntaxNode) {
+        inner(self.node(path), path.to_vec())
+    }
+
+    /// Looks through the parts of the node at `path` that don't change its value. `env` holds the
+    /// `let`-bindings in scope, so that variables can be followed to their definition.
+    fn look_through(&self, mut path: NodePath, env: &mut Env) -> NodePath {
+        loop {
+            let (inner, node) = self.inner(&path);
+            let nodes: Vec<(usize, &SyntaxNode)> = node.nodes().collect();
+            path = match (node.kind(), nodes.as_slice()) {
+                (Rule::expression, [(_, first), .., (body, _)])
+                    if first.kind() == Rule::let_binding =>
+                {
+                    for (i, binding) in &nodes[..nodes.len() - 1] {
+                        let mut parts = binding.nodes();
+                        let l = label(parts.next().unwrap().1);
+                        let v = parts.last().unwrap().0;
+                        env.push((l, child_path(&child_path(&inner, *i), v)));
+                    }
+                    child_path(&inner, *body)
+                }
+                // A type annotation.
+                (Rule::expression, [(e, x), _])
+                    if x.kind() == Rule::operator_expression =>
+                {
+                    child_path(&inner, *e)
+                }
+                (Rule::first_application_expression, [(_, some), (e, _)])
+                    if some.kind() == Rule::Some_ =>
+                {
+                    child_path(&inner, *e)
+                }
+                // The record of a completion.
+                (Rule::completion_expression, [_, (e, _)]) => {
+                    child_path(&inner, *e)
+                }
+                (Rule::variable, _) => {
+                    let l = label(nodes[0].1);
+                    let n = match nodes.get(1) {
+                        Some((_, n)) => match n.content().parse() {
+                            Ok(n) => n,
+                            Err(_) => return path,
+                        },
+                        None => 0,
+                    };
+                    match self.lookup(env, &l, n) {
+                        Some(v) => v,
+                        None => return path,
+                    }
+                }
+                _ => return path,
+            }
+        }
+    }
+
+    /// The value of the `n`th innermost `let` that binds `l`. The bindings after it are dropped
+    /// from `env`, since its value doesn't see them.
+    fn lookup(&self, env: &mut Env, l: &str, n: usize) -> Option<NodePath> {
+        let found = env
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, (x, _))| x == l)
+            .nth(n)
+            .map(|(i, _)| i)?;
+        let (_, v) = env[found].clone();
+        env.truncate(found);
+        Some(v)
+    }
+
+    /// The field `l` of the record literal at `record`, if it has one. A punned field like
+    /// `{ x }` is not written out: it points to the `let` that it refers to.
+    fn field(&self, record: &[usize], l: &str, env: &mut Env) -> Option<Place> {
+        let mut found = self
+            .node(record)
+            .nodes()
+            .filter(|(_, n)| n.kind() == Rule::record_literal_entry)
+            .map(|(i, n)| (i, entry(n)))
+            .filter(|(_, (labels, _))| labels[0] == l);
+        let (i, (labels, value)) = found.next()?;
+        if found.next().is_some() {
+            return Some(Place::Scattered);
+        }
+        let entry_path = child_path(record, i);
+        Some(match value {
+            _ if labels.len() > 1 => Place::Dotted(entry_path, 1),
+            Some(v) => Place::Node(child_path(&entry_path, v)),
+            None => match self.lookup(env, l, 0) {
+                Some(v) => Place::Node(self.look_through(v, env)),
+                None => Place::Scattered,
+            },
+        })
+    }
+
+    /// The contents of the union alternative `l` applied in the `application_expression` at
+    /// `app`, like `< A : Natural >.A 1`.
+    fn alternative(&self, app: &[usize], l: &str) -> Option<NodePath> {
+        let nodes: Vec<_> = self.node(app).nodes().map(|(i, _)| i).collect();
+        if nodes.len() != 2 {
+            return None;
+        }
+        let (_, f) = self.inner(&child_path(app, nodes[0]));
+        match f.nodes().last() {
+            Some((_, s))
+                if f.kind() == Rule::selector_expression
+                    && s.kind() == Rule::selector
+                    && label(s) == l =>
+            {
+                Some(child_path(app, nodes[1]))
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether `place` is a record literal with a field `l`.
+    fn has_field(&self, place: &Place, l: &str) -> bool {
+        match place {
+            Place::Node(path) => {
+                let (inner, node) = self.inner(path);
+                node.kind() == Rule::non_empty_record_literal
+                    && self.field(&inner, l, &mut Env::new()).is_some()
+            }
+            _ => false,
+        }
+    }
+
+    /// The side of `place` that the field `l` comes from, if it is a `//` of records: the right
+    /// side if it has the field or if neither side has it, and the left side otherwise.
+    fn field_record(&self, place: Place, l: &str, env: &mut Env) -> Place {
+        let (path, lo, hi) = match place {
+            Place::Node(path) => {
+                let (inner, node) = self.inner(&path);
+                let nodes: Vec<_> = node.nodes().map(|(i, _)| i).collect();
+                if node.kind() != Rule::operator_expression || nodes.len() < 3 {
+                    return Place::Node(path);
+                }
+                (inner, nodes[0], *nodes.last().unwrap())
+            }
+            Place::Operands(path, lo, hi) => (path, lo, hi),
+            place => return place,
+        };
+        // The operator that applies last is the loosest one, and the last of them since they
+        // associate to the left.
+        let node = self.node(&path);
+        let split = node
+            .nodes()
+            .filter(|(i, _)| lo <= *i && *i <= hi)
+            .filter_map(|(i, n)| Some((precedence(n)?, i)))
+            .min_by_key(|(p, i)| (*p, std::cmp::Reverse(*i)));
+        let op = match split {
+            Some((p, op)) if OPERATORS[p] == Rule::prefer => op,
+            _ => return Place::Operands(path, lo, hi),
+        };
+        let side = |lo: usize, hi: usize| {
+            let mut operands = node.nodes().filter(|(i, n)| {
+                lo <= *i && *i <= hi && precedence(n).is_none()
+            });
+            match (operands.next(), operands.next()) {
+                (Some((i, _)), None) => Place::Node(child_path(&path, i)),
+                _ => Place::Operands(path.clone(), lo, hi),
+            }
+        };
+        let (a, b) = (side(lo, op - 1), side(op + 1, hi));
+        let mut a_env = env.clone();
+        let b = match b {
+            Place::Node(b) => Place::Node(self.look_through(b, env)),
+            b => b,
+        };
+        let b = self.field_record(b, l, env);
+        if self.has_field(&b, l) {
+            return b;
+        }
+        let a = match a {
+            Place::Node(a) => Place::Node(self.look_through(a, &mut a_env)),
+            a => a,
+        };
+        let a = self.field_record(a, l, &mut a_env);
+        if self.has_field(&a, l) {
+            *env = a_env;
+            return a;
+        }
+        b
+    }
+
+    /// Finds the value at the end of `steps`, looking through everything that doesn't change it.
+    /// `env` receives the `let`-bindings in scope there.
+    fn locate(
+        &self,
+        path: &str,
+        steps: &[Step],
+        env: &mut Env,
+    ) -> Result<Place> {
+        let not_found = || EditError::NotFound(path.to_owned());
+        let (root, _) = self.root.nodes().next().unwrap();
+        let mut place = Place::Node(self.look_through(vec![root], env));
+        for step in steps {
+            if let Step::Field(l) = step {
+                place = self.field_record(place, l, env);
+            }
+            let next = match (&place, step) {
+                (Place::Node(p), Step::Field(l)) => {
+                    let (inner, node) = self.inner(p);
+                    match node.kind() {
+                        Rule::non_empty_record_literal => {
+                            self.field(&inner, l, env)
+                        }
+                        Rule::application_expression => {
+                            self.alternative(&inner, l).map(Place::Node)
+                        }
+                        _ => None,
+                    }
+                }
+                (Place::Node(p), Step::Index(i)) => {
+                    let (inner, node) = self.inner(p);
+                    match node.kind() {
+                        Rule::non_empty_list_literal => node
+                            .nodes()
+                            .nth(*i)
+                            .map(|(x, _)| Place::Node(child_path(&inner, x))),
+                        _ => None,
+                    }
+                }
+                (Place::Dotted(entry_path, n), Step::Field(l)) => {
+                    let (labels, value) = entry(self.node(entry_path));
+                    if labels[*n] != *l {
+                        None
+                    } else if n + 1 < labels.len() {
+                        Some(Place::Dotted(entry_path.clone(), n + 1))
+                    } else {
+                        Some(Place::Node(child_path(
+                            entry_path,
+                            value.unwrap(),
+                        )))
+                    }
+                }
+                _ => None,
+            };
+            place = match next.ok_or_else(not_found)? {
+                Place::Node(p) => Place::Node(self.look_through(p, env)),
+                place => place,
+            };
+        }
+        Ok(place)
+    }
+
+    /// The byte range of the node at `path` in the text, without its trivia.
+    fn range(&self, path: &[usize]) -> (usize, usize) {
+        let mut node = &self.root;
+        let mut start = node.leading_len();
+        for &i in path {
+            start += node.children()[..i]
+                .iter()
+                .map(SyntaxElement::text_len)
+                .sum::<usize>();
+            node = node.child(i);
+            start += node.leading_len();
+        }
+        (start, start + node.content_len())
+    }
+
+    /// Replaces the node at `path` with `value`, keeping the comments around it.
+    fn replace(&mut self, path: &[usize], value: &Expr) -> Result<()> {
+        self.replace_with(path, &value.to_string())
+    }
+
+    fn replace_with(&mut self, path: &[usize], text: &str) -> Result<()> {
+        let old = self.node_mut(path);
+        let mut new = value_node(text, old.kind())?;
+        new.replace(old);
+        self.refresh();
+        Ok(())
+    }
+
+    /// The separator between the items of the list or record that starts at `start`, following
+    /// the style of the line of the item that ends at `last`. Returns whether the style puts
+    /// commas at the start of lines.
+    fn separator(&self, start: usize, last: usize) -> (String, bool) {
+        let line_start = self.text[..last].rfind('\n').map_or(0, |i| i + 1);
+        if line_start <= start {
+            return (", ".to_owned(), false);
+        }
+        let line = &self.text[line_start..];
+        let indent = &line[..line.len() - line.trim_start().len()];
+        if line[indent.len()..].starts_with(',') {
+            (format!("\n{}, ", indent), true)
+        } else {
+            (format!(",\n{}", indent), false)
+        }
+    }
+
+    /// Adds `item` after the child `after` of the node at `items`, which holds the items of the
+    /// list or record at `container`.
+    fn append_after(
+        &mut self,
+        container: &[usize],
+        items: &[usize],
+        after: usize,
+        item: SyntaxNode,
+    ) -> Result<()> {
+        let last = self.node(items).nodes().last().unwrap().0;
+        let (sep, leading_commas) = self.separator(
+            self.range(container).0,
+            self.range(&child_path(items, last)).1,
+        );
+        // A comment at the end of the line of the last item is attached to the outermost node
+        // that ends there, which may be outside of `items`.
+        let mut outer_comment = Vec::new();
+        if after == last && self.node(items).children().len() == after + 1 {
+            let mut path = items.to_vec();
+            while let Some(i) = path.pop() {
+                let parent = self.node_mut(&path);
+                let trailing = parent.child_mut(i).trailing_mut();
+                if !trailing.is_empty() {
+                    outer_comment = std::mem::take(trailing);
+                    break;
+                }
+                if parent.children().len() != i + 1 {
+                    break;
+                }
+            }
+        }
+        let node = self.node_mut(items);
+        let nth = node.nodes().position(|(i, _)| i == after).unwrap();
+        node.detach_trivia();
+        let after = node.nodes().nth(nth).unwrap().0;
+        let children = node.children_mut();
+        let mut end = after + 1;
+        while let Some(SyntaxElement::Trivia(t)) = children.get(end) {
+            if t.starts_line() {
+                break;
+            }
+            end += 1;
+        }
+        let mut comment: Vec<Trivia> = children
+            .drain(after + 1..end)
+            .filter_map(|c| match c {
+                SyntaxElement::Trivia(t) => Some(t),
+                _ => None,
+            })
+            .collect();
+        comment.extend(outer_comment);
+        let line_comment =
+            comment.iter().any(|t| matches!(t, Trivia::LineComment(_)));
+        let multiline = sep.contains('\n');
+        let mut new = Vec::new();
+        let trivia =
+            |ts: Vec<Trivia>| ts.into_iter().map(SyntaxElement::Trivia);
+        // Keep a comment at the end of the line with the item it is about. Nothing can follow a
+        // line comment on its line, so the new item goes on the next line, or before it.
+        match (line_comment, multiline, leading_commas) {
+            (true, true, true) => {
+                new.extend(trivia(comment));
+                new.extend(lex_trivia(&sep));
+                new.push(SyntaxElement::Node(item));
+            }
+            (true, true, false) => {
+                new.push(SyntaxElement::Token(",".to_owned()));
+                new.extend(trivia(comment));
+                new.extend(lex_trivia(&sep[1..]));
+                new.push(SyntaxElement::Node(item));
+            }
+            (true, false, _) => {
+                new.extend(lex_trivia(&sep));
+                new.push(SyntaxElement::Node(item));
+                new.extend(trivia(comment));
+            }
+            (false, _, _) => {
+                let split = comment
+                    .iter()
+                    .rposition(Trivia::is_comment)
+                    .map_or(0, |i| i + 1);
+                let rest = comment.split_off(split);
+                new.extend(trivia(comment));
+                new.extend(lex_trivia(&sep));
+                new.push(SyntaxElement::Node(item));
+                new.extend(trivia(rest));
+            }
+        }
+        children.splice(after + 1..after + 1, new);
+        node.attach_trivia();
+        self.refresh();
+        Ok(())
+    }
+
+    /// Prints the tree again after an edit.
+    fn refresh(&mut self) {
+        self.text = self.root.to_string();
+    }
+}
+
+impl fmt::Display for Document {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
@@ -0,0 +1,513 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use ::pretty::RcDoc;
+use itertools::Itertools;
+
+use crate::operations::{BinOp, OpKind};
+use crate::syntax::text::printer::{
+    escape_text, FmtLabel, PhasedExpr, PrintPhase,
+};
+use crate::syntax::{
+    Expr, ExprKind, InterpolatedText, InterpolatedTextContents, Label,
+};
+
+type Doc = RcDoc<'static, ()>;
+
+/// The order in which the fields of records and record types are printed by [`Expr::format`].
+///
+/// Expressions store their fields sorted, and by default they are printed that way. Orders
+/// registered with [`prefer`](FieldOrder::prefer) are used instead for records at a given path
+/// that have exactly the given set of fields. The path of a record is the list of field names
+/// that lead to it from the top of the expression; list elements, `Some` and union alternatives
+/// don't add to it.
+#[derive(Debug, Clone, Default)]
+pub struct FieldOrder(HashMap<(Vec<Label>, BTreeSet<Label>), Vec<Label>>);
+
+impl FieldOrder {
+    /// Prints all fields in sorted order.
+    pub fn sorted() -> Self {
+        FieldOrder::default()
+    }
+
+    /// Records at `path` with exactly these fields will be printed with the fields in this order.
+    /// If an order was already registered for that path and set of fields, it is kept.
+    pub fn prefer(
+        &mut self,
+        path: &[Label],
+        fields: impl IntoIterator<Item = Label>,
+    ) {
+        let fields: Vec<Label> = fields.into_iter().collect();
+        let set: BTreeSet<Label> = fields.iter().cloned().collect();
+        if set.len() == fields.len() {
+            self.0.entry((path.to_vec(), set)).or_insert(fields);
+        }
+    }
+
+    fn order<'a, T>(
+        &self,
+        path: &[Label],
+        map: &'a BTreeMap<Label, T>,
+    ) -> Vec<(&'a Label, &'a T)> {
+        let key = (path.to_vec(), map.keys().cloned().collect());
+        match self.0.get(&key) {
+            Some(order) => order
+                .iter()
+                .map(|l| map.get_key_value(l).unwrap())
+                .collect(),
+            None => map.iter().collect(),
+        }
+    }
+}
+
+/// Which characters [`Expr::format`] uses for `λ`, `→`, `∀` and the operators that have an ASCII
+/// spelling, like `dhall format --ascii`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Characters {
+    /// `λ`, `→`, `∀`, `∧`, `⩓`, `⫽` and `≡`.
+    Unicode,
+    /// `\`, `->`, `forall`, `/\`, `//\\`, `//` and `===`.
+    Ascii,
+}
+
+/// Options for [`Expr::format`].
+#[derive(Debug, Clone)]
+pub struct FormatOptions {
+    /// The number of columns the output should fit in where possible.
+    pub width: usize,
+    /// Unicode or ASCII symbols.
+    pub characters: Characters,
+    /// The order of the fields of records and record types.
+    pub field_order: FieldOrder,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        FormatOptions {
+            width: 80,
+            characters: Characters::Unicode,
+            field_order: FieldOrder::sorted(),
+        }
+    }
+}
+
+impl Expr {
+    /// Prints the expression in the style of `dhall format`, breaking it across lines so that the
+    /// output fits in `options.width` columns where possible. Long records and lists are laid out
+    /// with leading separators, texts with newlines become multi-line literals, and chains of
+    /// `let`s are printed one binding per line:
+    ///
+    /// ```text
+    /// let ports = [ 80, 443 ]
+    ///
+    /// in  { motd =
+    ///         ''
+    ///         Welcome!
+    ///         ''
+    ///     , name = "app"
+    ///     , ports = ports
+    ///     }
+    /// ```
+    pub fn format(&self, options: &FormatOptions) -> String {
+        let doc = Layout {
+            options,
+            path: RefCell::new(Vec::new()),
+        }
+        .phased(PhasedExpr(self, PrintPhase::Base));
+        let mut out = String::new();
+        doc.render_fmt(options.width, &mut out).unwrap();
+        // Blank lines get the indentation of the surrounding block.
+        out.lines().map(str::trim_end).join("\n")
+    }
+
+    /// Like [`Expr::format`] with Unicode symbols.
+    pub fn to_string_pretty(
+        &self,
+        width: usize,
+        field_order: &FieldOrder,
+    ) -> String {
+        self.format(&FormatOptions {
+            width,
+            field_order: field_order.clone(),
+            ..FormatOptions::default()
+        })
+    }
+}
+
+fn text(s: impl Into<String>) -> Doc {
+    RcDoc::text(s.into())
+}
+
+fn label(l: &Label) -> Doc {
+    text(FmtLabel(l).to_string())
+}
+
+// Sets the indentation of the following lines to the current column.
+fn align(doc: Doc) -> Doc {
+    RcDoc::column(move |col| {
+        let doc = doc.clone();
+        RcDoc::nesting(move |nest| {
+            doc.clone().nest(col as isize - nest as isize)
+        })
+    })
+}
+
+// Lays out `items` either on one line, as in `{ a, b }`, or one per line with leading
+// separators.
+fn enclosed(open: &str, sep: Doc, close: &str, items: Vec<Doc>) -> Doc {
+    let mut doc = text(open).append(RcDoc::space());
+    for (i, item) in items.into_iter().enumerate() {
+        if i > 0 {
+            doc = doc.append(sep.clone());
+        }
+        doc = doc.append(item);
+    }
+    align(doc.append(RcDoc::line()).append(text(close))).group()
+}
+
+// `key = value`, with the value on its own indented line if it does not fit.
+fn entry(key: &Label, sep: &str, value: Doc) -> Doc {
+    label(key)
+        .append(text(sep))
+        .append(RcDoc::line().append(value).nest(4))
+        .group()
+}
+
+/// Whether `text` can be printed as a multi-line literal without changing its value. Lines are
+/// indented with spaces, and trailing whitespace is removed from the output.
+fn fits_multiline(text: &InterpolatedText<PhasedExpr<'_>>) -> bool {
+    // Lines of text, where interpolations count as a non-space character.
+    let mut lines = vec![String::new()];
+    for chunk in text.iter() {
+        match chunk {
+            InterpolatedTextContents::Text(s) => {
+                for (i, line) in s.split('\n').enumerate() {
+                    if i > 0 {
+                        lines.push(String::new());
+                    }
+                    lines.last_mut().unwrap().push_str(line);
+                }
+            }
+            InterpolatedTextContents::Expr(_) => {
+                lines.last_mut().unwrap().push('x')
+            }
+        }
+    }
+    let last = lines.last().unwrap();
+    lines.len() > 1
+        && lines.iter().all(|l| {
+            l.trim_end() == l
+                && !l.chars().any(|c| c.is_control() && c != '\t')
+        })
+        // `''` is escaped as `'''`, so an odd quote right before the `''` of `''${` or of the end
+        // of the literal would be read as part of an escape.
+        && quotes_paired_before(last.len(), last)
+        && lines
+            .iter()
+            .all(|l| l.match_indices("${").all(|(i, _)| quotes_paired_before(i, l)))
+        // The indentation that Dhall strips must not eat into the text.
+        && (last.is_empty()
+            || lines.iter().any(|l| !l.is_empty() && !l.starts_with(&[' ', '\t'][..])))
+}
+
+// Whether the quotes that end at `end` in `line` come in pairs.
+fn quotes_paired_before(end: usize, line: &str) -> bool {
+    line[..end].chars().rev().take_while(|c| *c == '\'').count() % 2 == 0
+}
+
+struct Layout<'o> {
+    options: &'o FormatOptions,
+    /// The fields that lead to the expression being printed.
+    path: RefCell<Vec<Label>>,
+}
+
+impl<'o> Layout<'o> {
+    fn ascii(&self) -> bool {
+        self.options.characters == Characters::Ascii
+    }
+
+    fn lambda(&self) -> &'static str {
+        if self.ascii() {
+            "\\"
+        } else {
+            "λ"
+        }
+    }
+
+    fn arrow(&self) -> &'static str {
+        if self.ascii() {
+            "->"
+        } else {
+            "→"
+        }
+    }
+
+    fn forall(&self) -> &'static str {
+        if self.ascii() {
+            "forall "
+        } else {
+            "∀"
+        }
+    }
+
+    fn binop(&self, op: BinOp) -> String {
+        use BinOp::*;
+        match op {
+            RecursiveRecordMerge if self.ascii() => "/\\".to_owned(),
+            RecursiveRecordTypeMerge if self.ascii() => "//\\\\".to_owned(),
+            RightBiasedRecordMerge if self.ascii() => "//".to_owned(),
+            Equivalence if self.ascii() => "===".to_owned(),
+            op => op.to_string(),
+        }
+    }
+
+    // The fields of a record in the order they should be printed in, each with the given
+    // separator and printed value.
+    fn fields<T: Copy>(
+        &self,
+        map: &BTreeMap<Label, T>,
+        sep: &str,
+        value: impl Fn(T) -> Doc,
+    ) -> Vec<Doc> {
+        let order = self.options.field_order.order(&self.path.borrow(), map);
+        order
+            .into_iter()
+            .map(|(k, v)| {
+                self.path.borrow_mut().push(k.clone());
+                let doc = entry(k, sep, value(*v));
+                self.path.borrow_mut().pop();
+                doc
+            })
+            .collect()
+    }
+
+    fn phased(&self, PhasedExpr(e, phase): PhasedExpr<'_>) -> Doc {
+        let doc = self.kind(e.as_ref().annotate_with_phases());
+        if e.as_ref().needs_paren(phase) {
+            text("(").append(align(doc)).append(text(")"))
+        } else {
+            doc
+        }
+    }
+
+    fn kind(&self, kind: ExprKind<PhasedExpr<'_>>) -> Doc {
+        use ExprKind::*;
+        match kind {
+            Lam(l, t, b) => align(
+                text(format!("{}({} : ", self.lambda(), FmtLabel(&l)))
+                    .append(align(self.phased(t)))
+                    .append(text(format!(") {}", self.arrow())))
+                    .append(RcDoc::line().append(self.phased(b)).nest(2))
+                    .group(),
+            ),
+            Pi(l, t, b) if l.as_ref() == "_" => align(
+                self.phased(t)
+                    .append(RcDoc::line())
+                    .append(text(format!("{} ", self.arrow())))
+                    .append(self.phased(b))
+                    .group(),
+            ),
+            Pi(l, t, b) => align(
+                text(format!("{}({} : ", self.forall(), FmtLabel(&l)))
+                    .append(align(self.phased(t)))
+                    .append(text(format!(") {}", self.arrow())))
+                    .append(RcDoc::line().append(self.phased(b)).nest(2))
+                    .group(),
+            ),
+            Let(l, t, v, b) => self.let_block(l, t, v, b),
+            TextLit(t) => self.text_lit(t),
+            SomeLit(e) => text("Some ").append(self.phased(e)),
+            EmptyListLit(t) => text("[] : ").append(self.phased(t)),
+            NEListLit(es) => enclosed(
+                "[",
+                RcDoc::line_().append(text(", ")),
+                "]",
+                es.into_iter().map(|e| self.phased(e)).collect(),
+            ),
+            RecordLit(m) if m.is_empty() => text("{=}"),
+            RecordLit(m) => enclosed(
+                "{",
+                RcDoc::line_().append(text(", ")),
+                "}",
+                self.fields(&m, " =", |v| self.phased(v)),
+            ),
+            RecordType(m) if m.is_empty() => text("{}"),
+            RecordType(m) => enclosed(
+                "{",
+                RcDoc::line_().append(text(", ")),
+                "}",
+                self.fields(&m, " :", |t| self.phased(t)),
+            ),
+            UnionType(m) => enclosed(
+                "<",
+                RcDoc::line().append(text("| ")),
+                ">",
+                m.iter()
+                    .map(|(k, t)| match t {
+                        Some(t) => entry(k, " :", self.phased(*t)),
+                        None => label(k),
+                    })
+                    .collect(),
+            ),
+            Annot(a, b) => {
+                self.phased(a).append(text(" : ")).append(self.phased(b))
+            }
+            Assert(a) => text("assert : ").append(self.phased(a)),
+            Op(op) => self.op(op),
+            kind => text(kind.to_string()),
+        }
+    }
+
+    // A chain of `let`s, always one binding per line with blank lines in between:
+    //
+    //     let x = 1
+    //
+    //     let y =
+    //           f x
+    //
+    //     in  x + y
+    fn let_block(
+        &self,
+        l: Label,
+        t: Option<PhasedExpr<'_>>,
+        v: PhasedExpr<'_>,
+        b: PhasedExpr<'_>,
+    ) -> Doc {
+        let mut bindings = vec![(l, t, v)];
+        let mut body = b;
+        while let ExprKind::Let(l, t, v, b) =
+            body.0.as_ref().annotate_with_phases()
+        {
+            bindings.push((l, t, v));
+            body = b;
+        }
+        let blank_line = RcDoc::hardline().append(RcDoc::hardline());
+        let mut doc = RcDoc::nil();
+        for (l, t, v) in bindings {
+            let mut binding = text(format!("let {}", FmtLabel(&l)));
+            if let Some(t) = t {
+                binding = binding.append(text(" : ")).append(self.phased(t));
+            }
+            let binding = binding
+                .append(text(" ="))
+                .append(RcDoc::line().append(align(self.phased(v))).nest(6))
+                .group();
+            doc = doc.append(binding).append(blank_line.clone());
+        }
+        align(doc.append(text("in  ")).append(align(self.phased(body))))
+    }
+
+    // Texts with newlines are printed as multi-line literals.
+    fn text_lit(&self, t: InterpolatedText<PhasedExpr<'_>>) -> Doc {
+        let interpolation = |e: &PhasedExpr<'_>| {
+            text("${ ").append(self.phased(*e)).append(text(" }"))
+        };
+        if !fits_multiline(&t) {
+            let mut doc = text("\"");
+            for chunk in t.iter() {
+                doc = doc.append(match chunk {
+                    InterpolatedTextContents::Text(s) => text(escape_text(&s)),
+                    InterpolatedTextContents::Expr(e) => interpolation(e),
+                });
+            }
+            return doc.append(text("\""));
+        }
+        let mut doc = text("''").append(RcDoc::hardline());
+        for chunk in t.iter() {
+            doc = doc.append(match chunk {
+                InterpolatedTextContents::Text(s) => {
+                    let s = s.replace("''", "'''").replace("${", "''${");
+                    RcDoc::intersperse(
+                        s.split('\n').map(|l| text(l.to_owned())),
+                        RcDoc::hardline(),
+                    )
+                }
+                InterpolatedTextContents::Expr(e) => interpolation(e),
+            });
+        }
+        align(doc.append(text("''")))
+    }
+
+    fn op(&self, op: OpKind<PhasedExpr<'_>>) -> Doc {
+        use OpKind::*;
+        match op {
+            App(f, a) => {
+                // Collect the arguments of `f a b c` to lay them out together.
+                let mut args = vec![a];
+                let mut f = f;
+                while let ExprKind::Op(App(g, b)) =
+                    f.0.as_ref().annotate_with_phases()
+                {
+                    args.push(b);
+                    f = g;
+                }
+                let args = args
+                    .into_iter()
+                    .rev()
+                    .map(|a| RcDoc::line().append(self.phased(a)));
+                align(
+                    self.phased(f).append(RcDoc::concat(args).nest(2)).group(),
+                )
+            }
+            BinOp(op, a, b) => align(
+                self.phased(a)
+                    .append(RcDoc::line())
+                    .append(text(format!("{} ", self.binop(op))))
+                    .append(self.phased(b))
+                    .group(),
+            ),
+            BoolIf(a, b, c) => align(
+                text("if ")
+                    .append(self.phased(a))
+                    .append(RcDoc::line())
+                    .append(text("then "))
+                    .append(self.phased(b))
+                    .append(RcDoc::line())
+                    .append(text("else "))
+                    .append(self.phased(c))
+                    .group(),
+            ),
+            Merge(a, b, t) => {
+                let mut doc = align(
+                    text("merge")
+                        .append(
+                            RcDoc::line()
+                                .append(self.phased(a))
+                                .append(RcDoc::line())
+                                .append(self.phased(b))
+                                .nest(2),
+                        )
+                        .group(),
+                );
+                if let Some(t) = t {
+                    doc = doc.append(text(" : ")).append(self.phased(t));
+                }
+                doc
+            }
+            ToMap(a, t) => {
+                let mut doc = text("toMap ").append(self.phased(a));
+                if let Some(t) = t {
+                    doc = doc.append(text(" : ")).append(self.phased(t));
+                }
+                doc
+            }
+            Field(a, l) => self.phased(a).append(text(".")).append(label(&l)),
+            Projection(e, ls) => self.phased(e).append(text(format!(
+                ".{{ {} }}",
+                ls.iter().map(FmtLabel).join(", ")
+            ))),
+            ProjectionByExpr(a, b) => self
+                .phased(a)
+                .append(text(".("))
+                .append(self.phased(b))
+                .append(text(")")),
+            Completion(a, b) => {
+                self.phased(a).append(text("::")).append(self.phased(b))
+            }
+            With(a, ls, b) => self
+                .phased(a)
+                .append(text(format!(" with {} = ", ls.iter().join("."))))
+                .append(self.phased(b)),
+        }
+    }
+}
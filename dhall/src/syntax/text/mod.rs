@@ -1,2 +1,5 @@
+pub mod cst;
+pub mod edit;
+pub mod layout;
 pub mod parser;
 pub mod printer;
@@ -5,9 +5,11 @@ use std::collections::{BTreeMap, BTreeSet};
 use std::iter::once;
 use std::rc::Rc;
 
+use pest::iterators::Pair;
 use pest_consume::{match_nodes, Parser};
 
 use crate::operations::OpKind::*;
+use crate::syntax::text::cst::{lex_trivia, SyntaxElement, SyntaxNode};
 use crate::syntax::ExprKind::*;
 use crate::syntax::NumKind::*;
 use crate::syntax::{
@@ -105,29 +107,29 @@ fn insert_recordlit_entry(map: &mut BTreeMap<Label, Expr>, l: Label, e: Expr) {
     }
 }
 
+/// The binary operators, from the loosest to the tightest. They all associate to the left.
+pub(crate) const OPERATORS: [Rule; 13] = [
+    Rule::equivalent,
+    Rule::import_alt,
+    Rule::bool_or,
+    Rule::natural_plus,
+    Rule::text_append,
+    Rule::list_append,
+    Rule::bool_and,
+    Rule::combine,
+    Rule::prefer,
+    Rule::combine_types,
+    Rule::natural_times,
+    Rule::bool_eq,
+    Rule::bool_ne,
+];
+
 lazy_static::lazy_static! {
     static ref PRECCLIMBER: PrecClimber<Rule> = {
-        use Rule::*;
-        // In order of precedence
-        let operators = vec![
-            equivalent,
-            import_alt,
-            bool_or,
-            natural_plus,
-            text_append,
-            list_append,
-            bool_and,
-            combine,
-            prefer,
-            combine_types,
-            natural_times,
-            bool_eq,
-            bool_ne,
-        ];
         PrecClimber::new(
-            operators
-                .into_iter()
-                .map(|op| pcl::Operator::new(op, pcl::Assoc::Left))
+            OPERATORS
+                .iter()
+                .map(|op| pcl::Operator::new(*op, pcl::Assoc::Left))
                 .collect(),
         )
     };
@@ -1032,6 +1034,52 @@ pub fn parse_expr(input_str: &str) -> ParseResult<Expr> {
     ))
 }
 
+/// Parses Dhall source text into a [`SyntaxNode`] of kind `final_expression`, that keeps all of
+/// the text. Imports are kept as they are written.
+pub fn parse_cst(input_str: &str) -> ParseResult<SyntaxNode> {
+    let rc_input_str = input_str.to_string().into();
+    let inputs = DhallParser::parse_with_userdata(
+        Rule::final_expression,
+        input_str,
+        rc_input_str,
+    )?;
+    let pairs: Vec<_> = inputs.clone().map(|i| i.as_pair().clone()).collect();
+    // The grammar accepts a few things that are still errors, like out-of-range escapes.
+    match_nodes!(<DhallParser>; inputs;
+        [expression(_e)] => (),
+    );
+    let mut pairs = pairs.into_iter();
+    Ok(syntax_node(pairs.next().unwrap()))
+}
+
+fn syntax_node(pair: Pair<Rule>) -> SyntaxNode {
+    let kind = pair.as_rule();
+    let span = pair.as_span();
+    let text = span.as_str();
+    let mut pos = 0;
+    let mut children = Vec::new();
+    for child in pair.into_inner() {
+        if child.as_rule() == Rule::EOI {
+            continue;
+        }
+        let start = child.as_span().start() - span.start();
+        children.extend(lex_trivia(&text[pos..start]));
+        pos = child.as_span().end() - span.start();
+        children.push(SyntaxElement::Node(syntax_node(child)));
+    }
+    if children.is_empty() {
+        // Those two operators swallow the whitespace that follows them.
+        if kind == Rule::import_alt || kind == Rule::natural_plus {
+            children = lex_trivia(text);
+        } else if !text.is_empty() {
+            children.push(SyntaxElement::Token(text.to_owned()));
+        }
+    } else {
+        children.extend(lex_trivia(&text[pos..]));
+    }
+    SyntaxNode::new(kind, children)
+}
+
 #[test]
 #[cfg_attr(windows, ignore)]
 // Check that the local copy of the grammar file is in sync with the one from dhall-lang.
@@ -10,7 +10,7 @@ use std::fmt::{self, Display};
 // of automatically getting all the parentheses and precedences right (in a manner dual do Pratt
 // parsing).
 #[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq)]
-enum PrintPhase {
+pub(super) enum PrintPhase {
     // `expression`
     Base,
     // `operator-expression`
@@ -28,7 +28,7 @@ enum PrintPhase {
 // Wraps an Expr with a phase, so that phase selection can be done separate from the actual
 // printing.
 #[derive(Copy, Clone)]
-struct PhasedExpr<'a>(&'a Expr, PrintPhase);
+pub(super) struct PhasedExpr<'a>(pub(super) &'a Expr, pub(super) PrintPhase);
 
 impl<'a> PhasedExpr<'a> {
     fn phase(self, phase: PrintPhase) -> PhasedExpr<'a> {
@@ -38,7 +38,7 @@ impl<'a> PhasedExpr<'a> {
 
 impl UnspannedExpr {
     // Annotate subexpressions with the appropriate phase, defaulting to Base
-    fn annotate_with_phases(&self) -> ExprKind<PhasedExpr<'_>> {
+    pub(super) fn annotate_with_phases(&self) -> ExprKind<PhasedExpr<'_>> {
         use ExprKind::*;
         use OpKind::*;
         use PrintPhase::*;
@@ -86,15 +86,11 @@ impl UnspannedExpr {
         }
     }
 
-    fn fmt_phase(
-        &self,
-        f: &mut fmt::Formatter,
-        phase: PrintPhase,
-    ) -> Result<(), fmt::Error> {
+    // Whether this expression must be wrapped in parentheses when printed in the given phase.
+    pub(super) fn needs_paren(&self, phase: PrintPhase) -> bool {
         use ExprKind::*;
         use OpKind::*;
-
-        let needs_paren = match self {
+        match self {
             Lam(_, _, _)
             | Pi(_, _, _)
             | Let(_, _, _, _)
@@ -110,8 +106,15 @@ impl UnspannedExpr {
             Op(App(_, _)) => phase > PrintPhase::App,
             Op(Completion(_, _)) => phase > PrintPhase::Import,
             _ => false,
-        };
+        }
+    }
 
+    fn fmt_phase(
+        &self,
+        f: &mut fmt::Formatter,
+        phase: PrintPhase,
+    ) -> Result<(), fmt::Error> {
+        let needs_paren = self.needs_paren(phase);
         if needs_paren {
             f.write_str("(")?;
         }
@@ -146,6 +149,15 @@ where
     f.write_str(close)
 }
 
+/// Displays a label, quoting it if needed.
+pub(crate) struct FmtLabel<'a>(pub(crate) &'a Label);
+
+impl<'a> Display for FmtLabel<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        fmt_label(self.0, f)
+    }
+}
+
 fn fmt_label(label: &Label, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
     // TODO: distinguish between reserved and nonreserved locations for quoting builtins
     let s = String::from(label);
@@ -316,36 +328,41 @@ impl<'a> Display for PhasedExpr<'a> {
     }
 }
 
+/// Escapes text for a double-quoted string literal.
+pub(super) fn escape_text(s: &str) -> String {
+    let mut out = String::new();
+    for c in s.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '$' => out.push_str("\\u0024"),
+            '\u{0008}' => out.push_str("\\b"),
+            '\u{000C}' => out.push_str("\\f"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\u{0000}'..='\u{001F}' => {
+                // Escape to an explicit "\u{XXXX}" form
+                let escaped: String = c.escape_default().collect();
+                // Print as "\uXXXX"
+                out.push_str(&format!(
+                    "\\u{:0>4}",
+                    &escaped[3..escaped.len() - 1]
+                ))
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
 impl<SubExpr: Display> Display for InterpolatedText<SubExpr> {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
         f.write_str("\"")?;
         for x in self.iter() {
             match x {
                 InterpolatedTextContents::Text(a) => {
-                    for c in a.chars() {
-                        match c {
-                            '\\' => f.write_str("\\\\"),
-                            '"' => f.write_str("\\\""),
-                            '$' => f.write_str("\\u0024"),
-                            '\u{0008}' => f.write_str("\\b"),
-                            '\u{000C}' => f.write_str("\\f"),
-                            '\n' => f.write_str("\\n"),
-                            '\r' => f.write_str("\\r"),
-                            '\t' => f.write_str("\\t"),
-                            '\u{0000}'..='\u{001F}' => {
-                                // Escape to an explicit "\u{XXXX}" form
-                                let escaped: String =
-                                    c.escape_default().collect();
-                                // Print as "\uXXXX"
-                                write!(
-                                    f,
-                                    "\\u{:0>4}",
-                                    &escaped[3..escaped.len() - 1]
-                                )
-                            }
-                            c => write!(f, "{}", c),
-                        }?;
-                    }
+                    f.write_str(&escape_text(&a))?;
                 }
                 InterpolatedTextContents::Expr(e) => {
                     f.write_str("${ ")?;
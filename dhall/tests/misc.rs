@@ -48,3 +48,506 @@ fn manual_function_application() {
     // The crate uses essentially a global context, created here.
     Ctxt::with_new(run).unwrap();
 }
+
+#[test]
+fn eval_limits() {
+    use dhall::error::EvalLimitError;
+    use std::time::{Duration, Instant};
+
+    fn eval(limits: EvalLimits, s: &str) -> Result<String, EvalLimitError> {
+        Ctxt::with_limits(limits, |cx| {
+            let typed = Parsed::parse_str(s)
+                .unwrap()
+                .skip_resolve(cx)
+                .unwrap()
+                .typecheck(cx)
+                .unwrap();
+            let nf = typed.normalize_within_limits(cx)?;
+            Ok(nf.to_expr(cx).to_string())
+        })
+    }
+
+    let fold = "Natural/fold 100 Natural (\\(x: Natural) -> x + 1) 0";
+    assert_eq!(eval(EvalLimits::default(), fold), Ok("100".to_owned()));
+    assert_eq!(
+        eval(
+            EvalLimits {
+                max_fold_iterations: Some(10),
+                ..EvalLimits::default()
+            },
+            fold
+        ),
+        Err(EvalLimitError::TooManyFoldIterations(10))
+    );
+    assert_eq!(
+        eval(
+            EvalLimits {
+                max_steps: Some(50),
+                ..EvalLimits::default()
+            },
+            fold
+        ),
+        Err(EvalLimitError::TooManySteps(50))
+    );
+    // This would overflow the stack if it was evaluated.
+    assert_eq!(
+        eval(
+            EvalLimits {
+                max_steps: Some(100),
+                ..EvalLimits::default()
+            },
+            "Natural/fold 100000000 Natural (\\(x: Natural) -> x + 1) 0"
+        ),
+        Err(EvalLimitError::TooManySteps(100))
+    );
+
+    let build = "List/build Natural (\\(list: Type) -> \\(cons: Natural -> list -> list) -> \\(nil: list) -> Natural/fold 100 list (cons 0) nil)";
+    assert_eq!(
+        eval(
+            EvalLimits {
+                max_list_length: Some(20),
+                ..EvalLimits::default()
+            },
+            build
+        ),
+        Err(EvalLimitError::ListTooLong(20))
+    );
+
+    let text = "Natural/fold 10 Text (\\(t: Text) -> t ++ t) \"ab\"";
+    assert_eq!(
+        eval(
+            EvalLimits {
+                max_text_size: Some(1000),
+                ..EvalLimits::default()
+            },
+            text
+        ),
+        Err(EvalLimitError::TextTooLong(1000))
+    );
+
+    assert_eq!(
+        eval(
+            EvalLimits {
+                deadline: Some(Instant::now() - Duration::from_secs(1)),
+                ..EvalLimits::default()
+            },
+            fold
+        ),
+        Err(EvalLimitError::DeadlineExceeded)
+    );
+}
+
+#[test]
+fn host_function() {
+    use dhall::builtins::HostFunction;
+
+    fn eval(s: &str) -> String {
+        Ctxt::with_new(|cx| {
+            let ty = Parsed::parse_str("Natural -> Natural -> Natural")
+                .unwrap()
+                .to_expr();
+            let max = HostFunction::new("max".into(), ty, |_, args| {
+                match (args[0].kind(), args[1].kind()) {
+                    (
+                        NirKind::Num(NumKind::Natural(x)),
+                        NirKind::Num(NumKind::Natural(y)),
+                    ) => Some(
+                        NirKind::Num(NumKind::Natural(*x.max(y))).into_nir(),
+                    ),
+                    _ => None,
+                }
+            });
+            cx.push_host_function(max).unwrap();
+            let typed = Parsed::parse_str(s)
+                .unwrap()
+                .skip_resolve(cx)
+                .unwrap()
+                .typecheck(cx)
+                .unwrap();
+            typed.normalize(cx).to_expr(cx).to_string()
+        })
+    }
+
+    assert_eq!(eval("max 2 (1 + 2)"), "3");
+    assert_eq!(eval("max 2"), "max 2");
+    // The function only gets called once its arguments are fully evaluated.
+    assert_eq!(
+        eval("\\(x: Natural) -> max x 2"),
+        "λ(x : Natural) → max x 2"
+    );
+    assert_eq!(eval("(\\(x: Natural) -> max x 2) 5"), "5");
+    assert_eq!(
+        eval("\\(max: Natural) -> max@1 max"),
+        "λ(max : Natural) → max@1 max"
+    );
+}
+
+#[test]
+fn to_string_pretty() {
+    let exprs = [
+        r#"{ a = [ 1, 2, 3 ], b = { c = Some "x", d = None Natural }, e = [] : List Bool }"#,
+        r#"\(x : { a : Natural, b : < A | B : Text > }) -> if x.a == 0 then [ x.b ] else ([] : List < A | B : Text >) # [ x.b ]"#,
+        r#"let f = \(n : Natural) -> { n = n } in (f 1 // f 2).n + List/length Natural [ 1, 2 ]"#,
+        r#"merge { A = 0, B = \(t : Text) -> 1 } (< A | B : Text >.B "x") : Natural"#,
+    ];
+    for s in exprs.iter() {
+        let expr = Parsed::parse_str(s).unwrap().to_expr();
+        for width in [1, 20, 80].iter() {
+            let pretty = expr.to_string_pretty(*width, &FieldOrder::sorted());
+            let reparsed = Parsed::parse_str(&pretty).unwrap().to_expr();
+            assert_eq!(reparsed.to_string(), expr.to_string(), "{}", pretty);
+        }
+    }
+
+    let expr = Parsed::parse_str("{ b = 1, a = { y = 2, x = 3 } }")
+        .unwrap()
+        .to_expr();
+    assert_eq!(
+        expr.to_string_pretty(80, &FieldOrder::sorted()),
+        "{ a = { x = 3, y = 2 }, b = 1 }"
+    );
+    assert_eq!(
+        expr.to_string_pretty(24, &FieldOrder::sorted()),
+        "{ a = { x = 3, y = 2 }\n, b = 1\n}"
+    );
+    let mut order = FieldOrder::sorted();
+    order.prefer(&[], vec!["b".into(), "a".into()]);
+    order.prefer(&["a".into()], vec!["y".into(), "x".into()]);
+    // Only the record at the given path uses the order.
+    order.prefer(&["c".into()], vec!["b".into(), "a".into()]);
+    assert_eq!(
+        expr.to_string_pretty(80, &order),
+        "{ b = 1, a = { y = 2, x = 3 } }"
+    );
+    let expr =
+        Parsed::parse_str("{ c = { a = 1, b = 2 }, d = { a = 3, b = 4 } }")
+            .unwrap()
+            .to_expr();
+    assert_eq!(
+        expr.to_string_pretty(80, &order),
+        "{ c = { b = 2, a = 1 }, d = { a = 3, b = 4 } }"
+    );
+}
+
+#[test]
+fn diff() {
+    fn diff_str(old: &str, new: &str) -> String {
+        Ctxt::with_new(|cx| {
+            let normalize = |s: &str| {
+                Parsed::parse_str(s)
+                    .unwrap()
+                    .skip_resolve(cx)
+                    .unwrap()
+                    .typecheck(cx)
+                    .unwrap()
+                    .normalize(cx)
+            };
+            dhall::diff(cx, &normalize(old), &normalize(new)).to_string()
+        })
+    }
+
+    // Values are compared after normalization.
+    assert_eq!(diff_str("{ x = 1 + 1 }", "{ x = 2 }"), "");
+    assert_eq!(
+        diff_str(
+            r#"{ name = "app", port = 80, tls = None Bool }"#,
+            r#"{ name = "app", port = 8080, tls = Some True, debug = False }"#,
+        ),
+        "+ .debug = False\n\
+         - .port = 80\n\
+         + .port = 8080\n\
+         - .tls = None Bool\n\
+         + .tls = Some True"
+    );
+    // Insertions in lists don't shift the other elements.
+    assert_eq!(
+        diff_str(
+            r#"[ { host = "a", port = 1 }, { host = "b", port = 2 } ]"#,
+            r#"[ { host = "z", port = 0 }, { host = "a", port = 1 }, { host = "b", port = 3 } ]"#,
+        ),
+        "+ [0] = { host = \"z\", port = 0 }\n\
+         - [2].port = 2\n\
+         + [2].port = 3"
+    );
+    assert_eq!(
+        diff_str("[ 1, 2, 3 ]", "[] : List Natural"),
+        "- [0] = 1\n- [1] = 2\n- [2] = 3"
+    );
+    assert_eq!(
+        diff_str(
+            "< A : { x : Natural } | B >.A { x = 1 }",
+            "< A : { x : Natural } | B >.A { x = 2 }"
+        ),
+        "- .A.x = 1\n+ .A.x = 2"
+    );
+    assert_eq!(
+        diff_str(
+            "< A : { x : Natural } | B >.A { x = 1 }",
+            "< A : { x : Natural } | B >.B"
+        ),
+        "- < A: { x : Natural } | B >.A { x = 1 }\n+ < A: { x : Natural } | B >.B"
+    );
+    assert_eq!(
+        diff_str(
+            "{ x : Natural, `y z` : Bool }",
+            "{ x : Text, `y z` : Bool }"
+        ),
+        "- .x : Natural\n+ .x : Text"
+    );
+    assert_eq!(diff_str("1", "2"), "- 1\n+ 2");
+
+    let expr = |s: &str| Parsed::parse_str(s).unwrap().to_expr();
+    let diff = expr("{ a = [ 1, 2 ], b = True }")
+        .diff(&expr("{ a = [ 2 ], b = True }"));
+    assert!(!diff.is_same());
+    assert_eq!(
+        diff,
+        Diff::Record(
+            vec![(
+                "a".into(),
+                Diff::List(vec![Diff::Removed(expr("1")), Diff::Same])
+            )]
+            .into_iter()
+            .collect()
+        )
+    );
+    assert_eq!(
+        diff.render(ColorChoice::Always),
+        "\u{1b}[31m- .a[0] = 1\u{1b}[0m"
+    );
+    assert_eq!(diff.render(ColorChoice::Never), "- .a[0] = 1");
+}
+
+#[test]
+fn edit_document() {
+    let expr = |s: &str| Parsed::parse_str(s).unwrap().to_expr();
+    let edit = |text: &str, f: &dyn Fn(&mut Document) -> Result<(), Error>| {
+        let mut doc = Document::parse(text).unwrap();
+        f(&mut doc).map_err(|e| e.to_string())?;
+        Ok::<_, String>(doc.to_string())
+    };
+
+    let services = r#"-- Services of the app
+let defaultPort = 80
+
+let web =
+      { host = "example.com"
+      , port = defaultPort -- the public port
+      }
+
+in  { web, db = { port = 5432, replicas = [ "a", "b" ] } } : Services
+"#;
+    // Editing nothing gives back the same text.
+    assert_eq!(edit(services, &|_| Ok(())).unwrap(), services);
+    assert_eq!(
+        edit(services, &|doc| doc.set(".web.port", &expr("8080"))).unwrap(),
+        services.replace("port = defaultPort", "port = 8080")
+    );
+    assert_eq!(
+        edit(services, &|doc| doc.set_let("defaultPort", &expr("443")))
+            .unwrap(),
+        services.replace("= 80\n", "= 443\n")
+    );
+    assert_eq!(
+        edit(services, &|doc| doc.set(".web.tls", &expr("True"))).unwrap(),
+        services.replace(
+            "the public port\n",
+            "the public port\n      , tls = True\n"
+        )
+    );
+    assert_eq!(
+        edit(services, &|doc| doc.set(".db.`max conns`", &expr("10"))).unwrap(),
+        services.replace(
+            "[ \"a\", \"b\" ] }",
+            "[ \"a\", \"b\" ], `max conns` = 10 }"
+        )
+    );
+    assert_eq!(
+        edit(services, &|doc| {
+            doc.insert(".db.replicas", 0, &expr("\"z\""))?;
+            doc.push(".db.replicas", &expr("\"c\""))
+        })
+        .unwrap(),
+        services.replace("[ \"a\", \"b\" ]", "[ \"z\", \"a\", \"b\", \"c\" ]")
+    );
+    assert_eq!(
+        edit("{ xs = [] : List Natural }", &|doc| doc
+            .push(".xs", &expr("1")))
+        .unwrap(),
+        "{ xs = [ 1 ] }"
+    );
+    assert_eq!(
+        edit("[ 1\n, 2\n]", &|doc| doc.insert("", 1, &expr("3"))).unwrap(),
+        "[ 1\n, 3\n, 2\n]"
+    );
+    assert_eq!(
+        edit("< A : Natural | B >.A 1", &|doc| doc
+            .set(".A", &expr("1 + 1")))
+        .unwrap(),
+        "< A : Natural | B >.A (1 + 1)"
+    );
+    let completion = "let T = { Type = { a : Natural, b : Natural }, default.b = 0 }\n\nin  T::{ a = 1 }";
+    assert_eq!(
+        edit(completion, &|doc| {
+            doc.set(".a", &expr("2"))?;
+            doc.set(".b", &expr("3"))
+        })
+        .unwrap(),
+        completion.replace("{ a = 1 }", "{ a = 2, b = 3 }")
+    );
+    let merge = "let base = { a = 1, b = 2 }\n\nin  base // { b = 3 }";
+    assert_eq!(
+        edit(merge, &|doc| {
+            doc.set(".a", &expr("4"))?;
+            doc.set(".b", &expr("5"))?;
+            doc.set(".c", &expr("6"))
+        })
+        .unwrap(),
+        merge
+            .replace("{ a = 1,", "{ a = 4,")
+            .replace("{ b = 3 }", "{ b = 5, c = 6 }")
+    );
+    // Comments around a replaced value stay, the ones inside it go with it.
+    let commented = "-- Ports\n{ a = {- old -} 1 -- one\n, b =\n    -- two\n    [ 2 {- inner -} ]\n}\n";
+    assert_eq!(
+        edit(commented, &|doc| {
+            doc.set(".a", &expr("3"))?;
+            doc.set(".b", &expr("[ 4 ]"))
+        })
+        .unwrap(),
+        "-- Ports\n{ a = {- old -} 3 -- one\n, b =\n    -- two\n    [4]\n}\n"
+    );
+    assert_eq!(
+        edit(commented, &|doc| doc.push(".b", &expr("3"))).unwrap(),
+        commented.replace("2 {- inner -} ]", "2 {- inner -}, 3 ]")
+    );
+    assert_eq!(
+        edit(commented, &|doc| doc.set("", &expr("{=}"))).unwrap(),
+        "-- Ports\n{=}\n"
+    );
+    assert_eq!(
+        edit("[ 1 -- one\n]", &|doc| doc.push("", &expr("2"))).unwrap(),
+        "[ 1, 2 -- one\n]"
+    );
+    let doc = Document::parse(commented).unwrap();
+    assert_eq!(doc.syntax().to_string(), commented);
+    let record = match &doc.syntax().children()[0] {
+        SyntaxElement::Node(n) => n,
+        _ => unreachable!(),
+    };
+    assert_eq!(
+        record.leading_trivia(),
+        &[
+            Trivia::LineComment("-- Ports".to_owned()),
+            Trivia::Whitespace("\n".to_owned())
+        ][..]
+    );
+
+    assert_eq!(
+        edit(services, &|doc| doc.set(".web.name.first", &expr("1")))
+            .unwrap_err(),
+        "nothing is written at `.web.name.first`"
+    );
+    assert_eq!(
+        edit("{ a.b = 1 }", &|doc| doc.set(".a.c", &expr("2"))).unwrap_err(),
+        "the value at `.a.c` is not written out in the source"
+    );
+    assert_eq!(
+        edit(services, &|doc| doc.push(".web", &expr("1"))).unwrap_err(),
+        "the value at `.web` is not a list"
+    );
+    assert_eq!(
+        edit(services, &|doc| doc.set_let("x", &expr("1"))).unwrap_err(),
+        "no `let` binds `x`"
+    );
+}
+
+#[test]
+fn format() {
+    let format = |s: &str, options: &FormatOptions| {
+        let expr = Parsed::parse_str(s).unwrap().to_expr();
+        let formatted = expr.format(options);
+        let reparsed = Parsed::parse_str(&formatted).unwrap().to_expr();
+        assert_eq!(reparsed.to_string(), expr.to_string(), "{}", formatted);
+        formatted
+    };
+    let narrow = FormatOptions {
+        width: 30,
+        ..FormatOptions::default()
+    };
+
+    assert_eq!(
+        format("let x = 1 let y = 2 in x + y", &FormatOptions::default()),
+        "let x = 1\n\nlet y = 2\n\nin  x + y"
+    );
+    assert_eq!(
+        format(
+            "let ports = [ 80, 443 ] in { name = \"app\", ports, motd = \"Welcome!\\n\" }",
+            &narrow,
+        ),
+        "let ports = [ 80, 443 ]\n\
+         \n\
+         in  { motd =\n\
+         \x20       ''\n\
+         \x20       Welcome!\n\
+         \x20       ''\n\
+         \x20   , name = \"app\"\n\
+         \x20   , ports = ports\n\
+         \x20   }"
+    );
+    assert_eq!(
+        format("\"a\\n  b ${\"c\"} ''\"", &FormatOptions::default()),
+        "''\na\n  b ${ \"c\" } '''''"
+    );
+    // Text that would lose its indentation or trailing spaces stays on one line.
+    assert_eq!(
+        format("\"  a\\n  b\"", &FormatOptions::default()),
+        "\"  a\\n  b\""
+    );
+    assert_eq!(
+        format("\"a \\nb\"", &FormatOptions::default()),
+        "\"a \\nb\""
+    );
+    assert_eq!(
+        format("\"a\\nb'\"", &FormatOptions::default()),
+        "\"a\\nb'\""
+    );
+    assert_eq!(
+        format("\"a\\n'\\${\"", &FormatOptions::default()),
+        "\"a\\n'\\u0024{\""
+    );
+
+    let ascii = FormatOptions {
+        characters: Characters::Ascii,
+        ..FormatOptions::default()
+    };
+    assert_eq!(
+        format(
+            "\\(x : { a : Bool }) -> forall (y : Type) -> x /\\ { b = True } // x === x",
+            &ascii,
+        ),
+        "\\(x : { a : Bool }) -> forall (y : Type) -> x /\\ { b = True } // x === x"
+    );
+
+    let exprs = [
+        r#"let f = \(n : Natural) -> { n = n, text = "line\nline${Natural/show n}\n" } let g : Natural -> Natural = \(n : Natural) -> n + 1 in (f 1 // f 2).n + g 3"#,
+        r#"{ a = ''
+            x''${y}
+              z
+            '' }"#,
+    ];
+    for s in exprs.iter() {
+        for width in [1, 20, 80].iter() {
+            for characters in [Characters::Unicode, Characters::Ascii].iter() {
+                format(
+                    s,
+                    &FormatOptions {
+                        width: *width,
+                        characters: *characters,
+                        ..FormatOptions::default()
+                    },
+                );
+            }
+        }
+    }
+}
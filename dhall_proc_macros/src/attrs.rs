@@ -0,0 +1,276 @@
+use syn::spanned::Spanned;
+use syn::{Attribute, Error, Lit, Meta, NestedMeta};
+
+/// The attributes of a type, field or variant that affect its Dhall type.
+///
+/// These are read from `#[serde(...)]` attributes, so that the derived type matches what serde
+/// does, and from `#[dhall(...)]` attributes, which take precedence.
+#[derive(Default)]
+pub struct Attrs {
+    pub rename: Option<String>,
+    pub rename_all: Option<RenameRule>,
+    pub skip: bool,
+    pub flatten: bool,
+    pub transparent: bool,
+}
+
+impl Attrs {
+    pub fn parse(attrs: &[Attribute]) -> Result<Attrs, Error> {
+        let mut res = Attrs::default();
+        // `dhall` attributes come last so they override `serde` ones.
+        for (tool, strict) in &[("serde", false), ("dhall", true)] {
+            for attr in attrs.iter().filter(|attr| attr.path.is_ident(tool)) {
+                let list =
+                    match attr.parse_meta()? {
+                        Meta::List(list) => list,
+                        meta => return Err(Error::new(
+                            meta.span(),
+                            "Derive StaticType: expected a list of attributes",
+                        )),
+                    };
+                for nested in list.nested.iter() {
+                    res.parse_one(nested, *strict)?;
+                }
+            }
+        }
+        Ok(res)
+    }
+
+    fn parse_one(
+        &mut self,
+        nested: &NestedMeta,
+        strict: bool,
+    ) -> Result<(), Error> {
+        let meta = match nested {
+            NestedMeta::Meta(meta) => meta,
+            NestedMeta::Lit(lit) => {
+                return Err(Error::new(
+                    lit.span(),
+                    "Derive StaticType: unexpected literal",
+                ))
+            }
+        };
+        let name = meta.path().get_ident().map(|i| i.to_string());
+        match (name.as_deref(), meta) {
+            (Some("rename"), Meta::NameValue(nv)) => {
+                self.rename = Some(string_lit(&nv.lit)?);
+            }
+            // `rename(serialize = "...", deserialize = "...")`. A type can only have one name,
+            // so we use the one serde reads.
+            (Some("rename"), Meta::List(list)) => {
+                if let Some(name) = directional(list, "deserialize")?
+                    .or(directional(list, "serialize")?)
+                {
+                    self.rename = Some(name);
+                }
+            }
+            (Some("rename_all"), Meta::NameValue(nv)) => {
+                let rule = string_lit(&nv.lit)?;
+                self.rename_all =
+                    Some(RenameRule::from_str(&rule).ok_or_else(|| {
+                        Error::new(
+                            nv.lit.span(),
+                            format!(
+                                "Derive StaticType: unknown rename rule `{}`",
+                                rule
+                            ),
+                        )
+                    })?);
+            }
+            (Some("rename_all"), Meta::List(list)) => {
+                if let Some(rule) = directional(list, "deserialize")?
+                    .or(directional(list, "serialize")?)
+                {
+                    self.rename_all = RenameRule::from_str(&rule);
+                }
+            }
+            (Some("skip"), Meta::Path(_)) => self.skip = true,
+            (Some("flatten"), Meta::Path(_)) => self.flatten = true,
+            (Some("transparent"), Meta::Path(_)) => self.transparent = true,
+            // Dhall records have no optional fields, so `#[serde(default)]` can't change the
+            // type: the field must still be present in the Dhall value.
+            (Some("default"), _) if strict => {
+                return Err(Error::new(
+                    meta.span(),
+                    "Derive StaticType: Dhall records have no optional fields; fill in missing fields with `Deserializer::with_defaults` instead",
+                ))
+            }
+            // Other serde attributes don't change the shape of the data, or are checked by serde.
+            _ if !strict => {}
+            _ => {
+                return Err(Error::new(
+                    meta.span(),
+                    "Derive StaticType: unknown attribute",
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    /// The name of a field, taking renaming into account.
+    pub fn field_name(
+        &self,
+        ident: &syn::Ident,
+        rename_all: Option<RenameRule>,
+    ) -> String {
+        match &self.rename {
+            Some(name) => name.clone(),
+            None => {
+                let name = unraw(ident);
+                match rename_all {
+                    Some(rule) => rule.apply_to_field(&name),
+                    None => name,
+                }
+            }
+        }
+    }
+
+    /// The name of a variant, taking renaming into account.
+    pub fn variant_name(
+        &self,
+        ident: &syn::Ident,
+        rename_all: Option<RenameRule>,
+    ) -> String {
+        match &self.rename {
+            Some(name) => name.clone(),
+            None => {
+                let name = unraw(ident);
+                match rename_all {
+                    Some(rule) => rule.apply_to_variant(&name),
+                    None => name,
+                }
+            }
+        }
+    }
+}
+
+fn unraw(ident: &syn::Ident) -> String {
+    ident.to_string().trim_start_matches("r#").to_owned()
+}
+
+fn string_lit(lit: &Lit) -> Result<String, Error> {
+    match lit {
+        Lit::Str(s) => Ok(s.value()),
+        _ => Err(Error::new(
+            lit.span(),
+            "Derive StaticType: expected a string",
+        )),
+    }
+}
+
+// Finds `key = "..."` in `rename(serialize = "...", deserialize = "...")`.
+fn directional(
+    list: &syn::MetaList,
+    key: &str,
+) -> Result<Option<String>, Error> {
+    for nested in list.nested.iter() {
+        if let NestedMeta::Meta(Meta::NameValue(nv)) = nested {
+            if nv.path.is_ident(key) {
+                return Ok(Some(string_lit(&nv.lit)?));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// The case conventions accepted by `rename_all`, with the same meaning as in serde.
+#[derive(Clone, Copy)]
+pub enum RenameRule {
+    Lower,
+    Upper,
+    Pascal,
+    Camel,
+    Snake,
+    ScreamingSnake,
+    Kebab,
+    ScreamingKebab,
+}
+
+impl RenameRule {
+    fn from_str(s: &str) -> Option<RenameRule> {
+        use RenameRule::*;
+        Some(match s {
+            "lowercase" => Lower,
+            "UPPERCASE" => Upper,
+            "PascalCase" => Pascal,
+            "camelCase" => Camel,
+            "snake_case" => Snake,
+            "SCREAMING_SNAKE_CASE" => ScreamingSnake,
+            "kebab-case" => Kebab,
+            "SCREAMING-KEBAB-CASE" => ScreamingKebab,
+            _ => return None,
+        })
+    }
+
+    /// Renames a variant, which is assumed to be in Pascal.
+    pub fn apply_to_variant(self, variant: &str) -> String {
+        use RenameRule::*;
+        match self {
+            Pascal => variant.to_owned(),
+            Lower => variant.to_ascii_lowercase(),
+            Upper => variant.to_ascii_uppercase(),
+            Camel => {
+                let mut chars = variant.chars();
+                match chars.next() {
+                    Some(c) => {
+                        c.to_ascii_lowercase().to_string() + chars.as_str()
+                    }
+                    None => String::new(),
+                }
+            }
+            Snake => {
+                let mut snake = String::new();
+                for (i, c) in variant.char_indices() {
+                    if i > 0 && c.is_uppercase() {
+                        snake.push('_');
+                    }
+                    snake.push(c.to_ascii_lowercase());
+                }
+                snake
+            }
+            ScreamingSnake => {
+                Snake.apply_to_variant(variant).to_ascii_uppercase()
+            }
+            Kebab => Snake.apply_to_variant(variant).replace('_', "-"),
+            ScreamingKebab => {
+                ScreamingSnake.apply_to_variant(variant).replace('_', "-")
+            }
+        }
+    }
+
+    /// Renames a field, which is assumed to be in snake_case.
+    pub fn apply_to_field(self, field: &str) -> String {
+        use RenameRule::*;
+        match self {
+            Lower | Snake => field.to_owned(),
+            Upper | ScreamingSnake => field.to_ascii_uppercase(),
+            Pascal => {
+                let mut pascal = String::new();
+                let mut capitalize = true;
+                for c in field.chars() {
+                    if c == '_' {
+                        capitalize = true;
+                    } else if capitalize {
+                        pascal.push(c.to_ascii_uppercase());
+                        capitalize = false;
+                    } else {
+                        pascal.push(c);
+                    }
+                }
+                pascal
+            }
+            Camel => {
+                let pascal = Pascal.apply_to_field(field);
+                let mut chars = pascal.chars();
+                match chars.next() {
+                    Some(c) => {
+                        c.to_ascii_lowercase().to_string() + chars.as_str()
+                    }
+                    None => String::new(),
+                }
+            }
+            Kebab => field.replace('_', "-"),
+            ScreamingKebab => field.to_ascii_uppercase().replace('_', "-"),
+        }
+    }
+}
@@ -5,6 +5,8 @@ use syn::spanned::Spanned;
 use syn::Error;
 use syn::{parse_quote, DeriveInput};
 
+use crate::attrs::{Attrs, RenameRule};
+
 pub fn derive_static_type(input: TokenStream) -> TokenStream {
     TokenStream::from(match derive_static_type_inner(input) {
         Ok(tokens) => tokens,
@@ -21,85 +23,196 @@ where
     )
 }
 
+fn is_phantom_data(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Path(p) if p.qself.is_none() => {
+            match p.path.segments.last() {
+                Some(seg) => seg.ident == "PhantomData",
+                None => false,
+            }
+        }
+        _ => false,
+    }
+}
+
+// Whether a type is known not to have a record type, so that it can't be flattened.
+fn is_not_record(ty: &syn::Type) -> bool {
+    const NOT_RECORDS: &[&str] = &[
+        "bool", "char", "str", "String", "u8", "u16", "u32", "u64", "u128",
+        "usize", "i8", "i16", "i32", "i64", "i128", "isize", "f32", "f64",
+        "Vec", "VecDeque", "Option", "HashMap", "BTreeMap", "HashSet",
+        "BTreeSet",
+    ];
+    match ty {
+        syn::Type::Path(p) if p.qself.is_none() => match p.path.segments.last()
+        {
+            Some(seg) => NOT_RECORDS.iter().any(|name| seg.ident == name),
+            None => false,
+        },
+        syn::Type::Array(_) | syn::Type::Slice(_) => true,
+        _ => false,
+    }
+}
+
+// The Dhall type of a field. Its Rust type must implement `StaticType`.
+fn field_type(
+    ty: &syn::Type,
+    constraints: &mut Vec<(syn::Type, syn::Path)>,
+) -> Result<proc_macro2::TokenStream, Error> {
+    if is_phantom_data(ty) {
+        return Err(Error::new_spanned(
+            ty,
+            "Derive StaticType: `PhantomData` has no Dhall type; skip the field with `#[serde(skip)]`",
+        ));
+    }
+    constraints.push((ty.clone(), parse_quote!(::serde_dhall::StaticType)));
+    Ok(static_type(ty))
+}
+
+// Builds the record type for a list of fields, taking their attributes into account.
+fn derive_for_fields(
+    fields: &syn::Fields,
+    rename_all: Option<RenameRule>,
+    constraints: &mut Vec<(syn::Type, syn::Path)>,
+) -> Result<proc_macro2::TokenStream, Error> {
+    let fields = derive_record_fields(fields, rename_all, constraints)?;
+    Ok(quote!( ::serde_dhall::SimpleType::Record(#fields) ))
+}
+
+// Builds the map of the fields of the record type for a list of fields. Flattened fields must
+// implement `RecordStaticType`, so that the fields they add are known to be record fields.
+fn derive_record_fields(
+    fields: &syn::Fields,
+    rename_all: Option<RenameRule>,
+    constraints: &mut Vec<(syn::Type, syn::Path)>,
+) -> Result<proc_macro2::TokenStream, Error> {
+    let mut entries = vec![];
+    let mut flattened = vec![];
+    let mut index = 0;
+    for field in fields.iter() {
+        let attrs = Attrs::parse(&field.attrs)?;
+        if attrs.skip {
+            continue;
+        }
+        let ty = &field.ty;
+        if attrs.flatten && is_not_record(ty) {
+            return Err(Error::new_spanned(
+                ty,
+                "Derive StaticType: a flattened field must have a record type",
+            ));
+        }
+        if attrs.flatten {
+            constraints.push((
+                ty.clone(),
+                parse_quote!(::serde_dhall::RecordStaticType),
+            ));
+            flattened.push(quote! {
+                fields.extend(
+                    <#ty as ::serde_dhall::RecordStaticType>::record_fields()
+                );
+            });
+            continue;
+        }
+        let static_ty = field_type(ty, constraints)?;
+        let name = match &field.ident {
+            Some(ident) => attrs.field_name(ident, rename_all),
+            None => {
+                index += 1;
+                format!("_{}", index)
+            }
+        };
+        entries.push(quote!( (#name.to_owned(), #static_ty) ));
+    }
+    if flattened.is_empty() {
+        Ok(quote! {
+            vec![ #(#entries),* ]
+                .into_iter()
+                .collect::<::std::collections::HashMap<_, _>>()
+        })
+    } else {
+        Ok(quote! {{
+            let mut fields: ::std::collections::HashMap<
+                ::std::string::String,
+                ::serde_dhall::SimpleType,
+            > = vec![ #(#entries),* ].into_iter().collect();
+            #(#flattened)*
+            fields
+        }})
+    }
+}
+
+// The type of a struct, as either a plain type or the fields of a record type.
+enum StructType {
+    Type(proc_macro2::TokenStream),
+    Record(proc_macro2::TokenStream),
+}
+
 fn derive_for_struct(
     data: &syn::DataStruct,
-    constraints: &mut Vec<syn::Type>,
-) -> Result<proc_macro2::TokenStream, Error> {
-    let fields = match &data.fields {
-        syn::Fields::Named(fields) => fields
-            .named
-            .iter()
-            .map(|f| {
-                let name = f.ident.as_ref().unwrap().to_string();
-                let ty = &f.ty;
-                (name, ty)
-            })
-            .collect(),
-        syn::Fields::Unnamed(fields) => fields
-            .unnamed
-            .iter()
-            .enumerate()
-            .map(|(i, f)| {
-                let name = format!("_{}", i + 1);
-                let ty = &f.ty;
-                (name, ty)
-            })
-            .collect(),
-        syn::Fields::Unit => vec![],
-    };
-    let entries = fields.into_iter().map(|(name, ty)| {
-        constraints.push(ty.clone());
-        let ty = static_type(ty);
-        quote!( (#name.to_owned(), #ty) )
-    });
-    Ok(quote! {
-        ::serde_dhall::SimpleType::Record(
-            vec![ #(#entries),* ].into_iter().collect()
-        )
-    })
+    attrs: &Attrs,
+    constraints: &mut Vec<(syn::Type, syn::Path)>,
+) -> Result<StructType, Error> {
+    if attrs.transparent {
+        let mut fields = vec![];
+        for field in data.fields.iter() {
+            if !Attrs::parse(&field.attrs)?.skip {
+                fields.push(field);
+            }
+        }
+        return match fields.as_slice() {
+            [field] => field_type(&field.ty, constraints).map(StructType::Type),
+            _ => Err(Error::new(
+                data.struct_token.span(),
+                "Derive StaticType: a transparent struct must have exactly one field that is not skipped",
+            )),
+        };
+    }
+    match &data.fields {
+        // Newtype structs are transparent.
+        syn::Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+            field_type(&fields.unnamed.iter().next().unwrap().ty, constraints)
+                .map(StructType::Type)
+        }
+        fields => derive_record_fields(fields, attrs.rename_all, constraints)
+            .map(StructType::Record),
+    }
 }
 
 fn derive_for_enum(
     data: &syn::DataEnum,
-    constraints: &mut Vec<syn::Type>,
+    attrs: &Attrs,
+    constraints: &mut Vec<(syn::Type, syn::Path)>,
 ) -> Result<proc_macro2::TokenStream, Error> {
-    let entries: Vec<_> = data
-        .variants
-        .iter()
-        .map(|v| {
-            let name = v.ident.to_string();
-            match &v.fields {
-                syn::Fields::Unit => Ok(quote!( (#name.to_owned(), None) )),
-                syn::Fields::Unnamed(fields) if fields.unnamed.is_empty() => {
-                    Ok(quote!( (#name.to_owned(), None) ))
-                }
-                syn::Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
-                    let ty = &fields.unnamed.iter().next().unwrap().ty;
-                    constraints.push(ty.clone());
-                    let ty = static_type(ty);
-                    Ok(quote!( (#name.to_owned(), Some(#ty)) ))
-                },
-                syn::Fields::Unnamed(_) => Err(Error::new(
-                    v.span(),
-                    "Derive StaticType: Variants with more than one field are not supported",
-                )),
-                syn::Fields::Named(fields) => {
-                    let entries = fields
-                        .named
-                        .iter()
-                        .map(|field| {
-                            constraints.push(field.ty.clone());
-                            let ty = static_type(&field.ty);
-                            let name = field.ident.as_ref().unwrap().to_string();
-                            quote!( (#name.to_owned(), #ty) ) });
-                    let record = quote! {::serde_dhall::SimpleType::Record(
-                            vec![ #(#entries),* ].into_iter().collect()
-                    )};
-                    Ok(quote!( (#name.to_owned(), Some(#record)) ))
-                }
+    let mut entries = vec![];
+    for v in data.variants.iter() {
+        let variant_attrs = Attrs::parse(&v.attrs)?;
+        if variant_attrs.skip {
+            continue;
+        }
+        let name = variant_attrs.variant_name(&v.ident, attrs.rename_all);
+        entries.push(match &v.fields {
+            syn::Fields::Unit => quote!( (#name.to_owned(), None) ),
+            syn::Fields::Unnamed(fields) if fields.unnamed.is_empty() => {
+                quote!( (#name.to_owned(), None) )
             }
-        })
-        .collect::<Result<_, Error>>()?;
+            syn::Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                let ty = field_type(
+                    &fields.unnamed.iter().next().unwrap().ty,
+                    constraints,
+                )?;
+                quote!( (#name.to_owned(), Some(#ty)) )
+            }
+            // Tuple variants get a `{ _1 : A, _2 : B }` payload, like tuples.
+            fields => {
+                let record = derive_for_fields(
+                    fields,
+                    variant_attrs.rename_all,
+                    constraints,
+                )?;
+                quote!( (#name.to_owned(), Some(#record)) )
+            }
+        });
+    }
 
     Ok(quote! {
         ::serde_dhall::SimpleType::Union(
@@ -112,19 +225,33 @@ pub fn derive_static_type_inner(
     input: TokenStream,
 ) -> Result<proc_macro2::TokenStream, Error> {
     let input: DeriveInput = syn::parse_macro_input::parse(input)?;
+    let attrs = Attrs::parse(&input.attrs)?;
 
-    // List of types that must impl StaticType
+    // List of types that must impl StaticType, or RecordStaticType for flattened fields
     let mut constraints = vec![];
 
+    let mut record_fields = None;
     let get_type = match &input.data {
-        syn::Data::Struct(data) => derive_for_struct(data, &mut constraints)?,
+        syn::Data::Struct(data) => {
+            match derive_for_struct(data, &attrs, &mut constraints)? {
+                StructType::Type(ty) => ty,
+                StructType::Record(fields) => {
+                    record_fields = Some(fields);
+                    quote!(::serde_dhall::SimpleType::Record(
+                        <Self as ::serde_dhall::RecordStaticType>::record_fields()
+                    ))
+                }
+            }
+        }
         syn::Data::Enum(data) if data.variants.is_empty() => {
             return Err(Error::new(
                 input.span(),
                 "Derive StaticType: Empty enums are not supported",
             ))
         }
-        syn::Data::Enum(data) => derive_for_enum(data, &mut constraints)?,
+        syn::Data::Enum(data) => {
+            derive_for_enum(data, &attrs, &mut constraints)?
+        }
         syn::Data::Union(x) => {
             return Err(Error::new(
                 x.union_token.span(),
@@ -140,22 +267,23 @@ pub fn derive_static_type_inner(
     let orig_where_clause = orig_where_clause.unwrap();
 
     // Hygienic errors
-    let assertions = constraints.iter().enumerate().map(|(i, ty)| {
+    let assertions = constraints.iter().enumerate().map(|(i, (ty, bound))| {
         // Ensure that ty: StaticType, with an appropriate span
         let assert_name =
             syn::Ident::new(&format!("_AssertType{}", i), ty.span());
         let mut local_where_clause = orig_where_clause.clone();
         local_where_clause
             .predicates
-            .push(parse_quote!(#ty: ::serde_dhall::StaticType));
-        let phantoms = generics.params.iter().map(|param| match param {
+            .push(parse_quote!(#ty: #bound));
+        // Const parameters don't need to be used.
+        let phantoms = generics.params.iter().filter_map(|param| match param {
             syn::GenericParam::Type(syn::TypeParam { ident, .. }) => {
-                quote!(#ident)
+                Some(quote!(#ident))
             }
             syn::GenericParam::Lifetime(syn::LifetimeDef {
                 lifetime, ..
-            }) => quote!(&#lifetime ()),
-            _ => unimplemented!(),
+            }) => Some(quote!(&#lifetime ())),
+            syn::GenericParam::Const(_) => None,
         });
         quote_spanned! {ty.span()=>
             struct #assert_name #impl_generics #local_where_clause {
@@ -166,13 +294,25 @@ pub fn derive_static_type_inner(
 
     // Ensure that all the fields have a StaticType impl
     let mut where_clause = orig_where_clause.clone();
-    for ty in constraints.iter() {
-        where_clause
-            .predicates
-            .push(parse_quote!(#ty: ::serde_dhall::StaticType));
+    for (ty, bound) in constraints.iter() {
+        where_clause.predicates.push(parse_quote!(#ty: #bound));
     }
 
     let ident = &input.ident;
+    let record_impl = record_fields.map(|fields| {
+        quote! {
+            impl #impl_generics ::serde_dhall::RecordStaticType
+                    for #ident #ty_generics
+                    #where_clause {
+                fn record_fields() -> ::std::collections::HashMap<
+                    ::std::string::String,
+                    ::serde_dhall::SimpleType,
+                > {
+                    #fields
+                }
+            }
+        }
+    });
     let tokens = quote! {
         #[allow(unused_parens)]
         impl #impl_generics ::serde_dhall::StaticType
@@ -183,6 +323,7 @@ pub fn derive_static_type_inner(
                 #get_type
             }
         }
+        #record_impl
     };
     Ok(tokens)
 }
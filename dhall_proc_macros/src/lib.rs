@@ -5,11 +5,12 @@
 //!
 //! [dhall-rust]: https://github.com/Nadrieril/dhall-rust
 
+mod attrs;
 mod derive;
 
 use proc_macro::TokenStream;
 
-#[proc_macro_derive(StaticType)]
+#[proc_macro_derive(StaticType, attributes(dhall))]
 pub fn derive_static_type(input: TokenStream) -> TokenStream {
     derive::derive_static_type(input)
 }
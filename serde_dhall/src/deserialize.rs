@@ -1,18 +1,40 @@
 use std::borrow::Cow;
 use std::collections::BTreeMap;
 use std::fmt;
+use std::rc::Rc;
 
 use serde::de::value::{
     MapAccessDeserializer, MapDeserializer, SeqDeserializer,
 };
-use serde::de::VariantAccess as _;
+use serde::de::{IntoDeserializer as _, VariantAccess as _};
 
-use dhall::syntax::NumKind;
+use dhall::builtins::Builtin;
+use dhall::operations::OpKind;
+use dhall::semantics::{Nir, NirKind};
+use dhall::syntax::{Expr, ExprKind, Label, NumKind, Span};
+use dhall::{Ctxt, SharedCtxt};
 
 use crate::value::SimpleValue;
-use crate::{Error, ErrorKind, Value};
+use crate::{EnumRepresentation, Error, ErrorKind, Value};
 
-pub trait Sealed {}
+pub trait Sealed {
+    /// Like `FromDhall::from_dhall`, but reads the normalized expression `x` of type `ty`
+    /// directly, with unions in the shape chosen by `enums`. This is here rather than in
+    /// `FromDhall` so that it stays out of the public API.
+    fn from_nir<'cx>(
+        cx: Ctxt<'cx>,
+        x: &Nir<'cx>,
+        ty: &Nir<'cx>,
+        shared: &SharedCtxt,
+        enums: &EnumRepresentation,
+    ) -> crate::Result<Self>
+    where
+        Self: FromDhall,
+    {
+        let _ = enums;
+        Self::from_dhall(&Value::from_nir_and_ty(cx, x, ty, shared)?)
+    }
+}
 
 /// A data structure that can be deserialized from a Dhall expression.
 ///
@@ -45,7 +67,27 @@ pub trait FromDhall: Sealed + Sized {
     fn from_dhall(v: &Value) -> crate::Result<Self>;
 }
 
-impl<T> Sealed for T where T: serde::de::DeserializeOwned {}
+impl<T> Sealed for T
+where
+    T: serde::de::DeserializeOwned,
+{
+    // This avoids building a `SimpleValue`: subexpressions are only evaluated when serde asks for
+    // them.
+    fn from_nir<'cx>(
+        cx: Ctxt<'cx>,
+        x: &Nir<'cx>,
+        _ty: &Nir<'cx>,
+        _shared: &SharedCtxt,
+        enums: &EnumRepresentation,
+    ) -> crate::Result<Self> {
+        T::deserialize(NirDeserializer {
+            cx,
+            nir: x.clone(),
+            path: Path::default(),
+            enums: Rc::new(enums.clone()),
+        })
+    }
+}
 
 /// Deserialize a Rust value from a Dhall [`SimpleValue`].
 ///
@@ -88,7 +130,7 @@ pub fn from_simple_value<T>(v: SimpleValue) -> crate::Result<T>
 where
     T: serde::de::DeserializeOwned,
 {
-    T::deserialize(Deserializer(Cow::Owned(v)))
+    T::deserialize(Deserializer::new(Cow::Owned(v)))
 }
 
 impl<T> FromDhall for T
@@ -106,7 +148,108 @@ where
     }
 }
 
-struct Deserializer<'a>(Cow<'a, SimpleValue>);
+/// A step taken while descending into a value.
+#[derive(Debug, Clone)]
+pub(crate) enum PathSegment {
+    /// A record field, printed `.x`.
+    Field(String),
+    /// A list element, printed `[2]`.
+    Index(usize),
+    /// The contents of a union alternative, printed `.Foo`.
+    Variant(String),
+}
+
+/// The location of a sub-value inside a Dhall value, e.g. `.db.replicas[2].host`.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Path(Vec<PathSegment>);
+
+impl Path {
+    pub(crate) fn child(&self, segment: PathSegment) -> Path {
+        let mut path = self.clone();
+        path.0.push(segment);
+        path
+    }
+
+    pub(crate) fn is_root(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Finds the span of the sub-expression this path points to. This only works when the
+    /// expression was written out literally in the source, e.g. not for values computed by a
+    /// function.
+    pub(crate) fn span_in(&self, expr: &Expr) -> Option<Span> {
+        let mut expr = expr;
+        let mut segments = self.0.iter().peekable();
+        loop {
+            expr = match (expr.kind(), segments.peek()) {
+                // These don't change the value; look through them.
+                (ExprKind::Annot(e, _), _)
+                | (ExprKind::Let(_, _, _, e), _)
+                | (ExprKind::SomeLit(e), _) => e,
+                (_, None) => return Some(expr.span()),
+                (ExprKind::RecordLit(kvs), Some(PathSegment::Field(k))) => {
+                    segments.next();
+                    kvs.get(k.as_str())?
+                }
+                (ExprKind::NEListLit(xs), Some(PathSegment::Index(i))) => {
+                    segments.next();
+                    xs.get(*i)?
+                }
+                (
+                    ExprKind::Op(OpKind::App(f, x)),
+                    Some(PathSegment::Variant(v)),
+                ) => match f.kind() {
+                    ExprKind::Op(OpKind::Field(_, l)) if l.as_ref() == v => {
+                        segments.next();
+                        x
+                    }
+                    _ => return None,
+                },
+                _ => return None,
+            }
+        }
+    }
+}
+
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for segment in &self.0 {
+            match segment {
+                PathSegment::Field(x) | PathSegment::Variant(x) => {
+                    write!(f, ".{}", x)?
+                }
+                PathSegment::Index(i) => write!(f, "[{}]", i)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Deserializer<'a> {
+    value: Cow<'a, SimpleValue>,
+    /// Where `value` is located inside the value being deserialized.
+    path: Path,
+}
+
+impl<'a> Deserializer<'a> {
+    fn new(value: Cow<'a, SimpleValue>) -> Self {
+        Deserializer {
+            value,
+            path: Path::default(),
+        }
+    }
+
+    fn child<'b>(
+        &self,
+        segment: PathSegment,
+        value: &'b SimpleValue,
+    ) -> Deserializer<'b> {
+        Deserializer {
+            value: Cow::Borrowed(value),
+            path: self.path.child(segment),
+        }
+    }
+}
 
 impl<'de: 'a, 'a> serde::de::IntoDeserializer<'de, Error> for Deserializer<'a> {
     type Deserializer = Deserializer<'a>;
@@ -123,26 +266,36 @@ impl<'de: 'a, 'a> serde::Deserializer<'de> for Deserializer<'a> {
         V: serde::de::Visitor<'de>,
     {
         use NumKind::*;
+        use PathSegment::*;
         use SimpleValue::*;
 
-        let val = |x| Deserializer(Cow::Borrowed(x));
-        match self.0.as_ref() {
+        let value: &SimpleValue = self.value.as_ref();
+        let res = match value {
             Num(Bool(x)) => visitor.visit_bool(*x),
             Num(Natural(x)) => visitor.visit_u64(*x),
             Num(Integer(x)) => visitor.visit_i64(*x),
             Num(Double(x)) => visitor.visit_f64((*x).into()),
             Text(x) => visitor.visit_str(x),
-            List(xs) => {
-                visitor.visit_seq(SeqDeserializer::new(xs.iter().map(val)))
-            }
-            Optional(None) => visitor.visit_none(),
-            Optional(Some(x)) => visitor.visit_some(val(x)),
-            Record(m) => visitor.visit_map(MapDeserializer::new(
-                m.iter().map(|(k, v)| (k.as_str(), val(v))),
+            List(xs) => visitor.visit_seq(SeqDeserializer::new(
+                xs.iter().enumerate().map(|(i, x)| self.child(Index(i), x)),
             )),
+            Optional(None) => visitor.visit_none(),
+            Optional(Some(x)) => visitor.visit_some(Deserializer {
+                value: Cow::Borrowed(x),
+                path: self.path.clone(),
+            }),
+            Record(m) => {
+                visitor.visit_map(MapDeserializer::new(m.iter().map(
+                    |(k, v)| (k.as_str(), self.child(Field(k.clone()), v)),
+                )))
+            }
             Union(field_name, Some(x)) => visitor.visit_enum(
                 MapAccessDeserializer::new(MapDeserializer::new(
-                    Some((field_name.as_str(), val(x))).into_iter(),
+                    Some((
+                        field_name.as_str(),
+                        self.child(Variant(field_name.clone()), x),
+                    ))
+                    .into_iter(),
                 )),
             ),
             Union(field_name, None) => visitor.visit_enum(
@@ -150,6 +303,61 @@ impl<'de: 'a, 'a> serde::Deserializer<'de> for Deserializer<'a> {
                     Some((field_name.as_str(), ())).into_iter(),
                 )),
             ),
+        };
+        res.map_err(|e| e.at_path(&self.path))
+    }
+
+    // Maps with non-`Text` keys stay lists of `{ mapKey, mapValue }` records.
+    fn deserialize_map<V>(self, visitor: V) -> crate::Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        let is_entry = |x: &SimpleValue| match x {
+            SimpleValue::Record(m) => {
+                m.len() == 2
+                    && m.contains_key("mapKey")
+                    && m.contains_key("mapValue")
+            }
+            _ => false,
+        };
+        match self.value.as_ref() {
+            SimpleValue::List(xs) if xs.iter().all(is_entry) => visitor
+                .visit_map(MapDeserializer::new(xs.iter().enumerate().map(
+                    |(i, x)| {
+                        let entry = self.child(PathSegment::Index(i), x);
+                        let field = |name: &str| {
+                            let m = match x {
+                                SimpleValue::Record(m) => m,
+                                _ => unreachable!(),
+                            };
+                            entry.child(
+                                PathSegment::Field(name.to_owned()),
+                                m.get(name).unwrap(),
+                            )
+                        };
+                        (field("mapKey"), field("mapValue"))
+                    },
+                )))
+                .map_err(|e| e.at_path(&self.path)),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    // Enums can also be read from text, e.g. the keys of a map produced by `toMap`.
+    fn deserialize_enum<V>(
+        self,
+        _: &'static str,
+        _: &'static [&'static str],
+        visitor: V,
+    ) -> crate::Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        match self.value.as_ref() {
+            SimpleValue::Text(x) => visitor
+                .visit_enum(x.as_str().into_deserializer())
+                .map_err(|e: Error| e.at_path(&self.path)),
+            _ => self.deserialize_any(visitor),
         }
     }
 
@@ -161,29 +369,483 @@ impl<'de: 'a, 'a> serde::Deserializer<'de> for Deserializer<'a> {
     where
         V: serde::de::Visitor<'de>,
     {
-        let val = |x| Deserializer(Cow::Borrowed(x));
-        match self.0.as_ref() {
+        let value: &SimpleValue = self.value.as_ref();
+        match value {
             // Blindly takes keys in sorted order.
             SimpleValue::Record(m) => visitor
-                .visit_seq(SeqDeserializer::new(m.iter().map(|(_, v)| val(v)))),
+                .visit_seq(SeqDeserializer::new(m.iter().map(|(k, v)| {
+                    self.child(PathSegment::Field(k.clone()), v)
+                })))
+                .map_err(|e| e.at_path(&self.path)),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_unit<V>(self, visitor: V) -> crate::Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        match self.value.as_ref() {
+            SimpleValue::Record(m) if m.is_empty() => visitor
+                .visit_unit()
+                .map_err(|e: Error| e.at_path(&self.path)),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_unit_struct<V>(
+        self,
+        _: &'static str,
+        visitor: V,
+    ) -> crate::Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        self.deserialize_unit(visitor)
+    }
+
+    // Newtype structs are transparent.
+    fn deserialize_newtype_struct<V>(
+        self,
+        _: &'static str,
+        visitor: V,
+    ) -> crate::Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> crate::Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option seq struct identifier ignored_any
+    }
+}
+
+/// Deserializes from a normalized expression, forcing its evaluation as it goes.
+struct NirDeserializer<'cx> {
+    cx: Ctxt<'cx>,
+    nir: Nir<'cx>,
+    /// Where `nir` is located inside the value being deserialized.
+    path: Path,
+    /// How `deserialize_any` presents unions.
+    enums: Rc<EnumRepresentation>,
+}
+
+impl<'cx> NirDeserializer<'cx> {
+    fn child(&self, segment: PathSegment, nir: &Nir<'cx>) -> Self {
+        NirDeserializer {
+            cx: self.cx,
+            nir: nir.clone(),
+            path: self.path.child(segment),
+            enums: self.enums.clone(),
+        }
+    }
+
+    fn not_simple(&self) -> Error {
+        Error(ErrorKind::Deserialize(format!(
+            "this cannot be deserialized into the serde data model: {}",
+            self.nir.to_expr(self.cx, Default::default())
+        )))
+    }
+
+    /// If this is a list of `{ mapKey, mapValue }` records as produced by `toMap`, returns its
+    /// entries. If `text_keys` is set, only maps with `Text` keys are considered.
+    fn map_entries(
+        &self,
+        text_keys: bool,
+    ) -> Option<crate::Result<Vec<(Self, Self)>>> {
+        use PathSegment::*;
+        let is_entry =
+            |kvs: &std::collections::HashMap<_, Nir<'cx>>,
+             is_text: &dyn Fn(&Nir<'cx>) -> bool| {
+                kvs.len() == 2
+                    && kvs.contains_key("mapValue")
+                    && matches!(
+                        kvs.get("mapKey"),
+                        Some(k) if !text_keys || is_text(k)
+                    )
+            };
+        match self.nir.kind() {
+            NirKind::EmptyListLit(t) => match t.kind() {
+                NirKind::RecordType(kts)
+                    if is_entry(kts, &|k| {
+                        matches!(k.kind(), NirKind::BuiltinType(Builtin::Text))
+                    }) =>
+                {
+                    Some(Ok(vec![]))
+                }
+                _ => None,
+            },
+            NirKind::NEListLit(xs) => match xs[0].kind() {
+                NirKind::RecordLit(kvs)
+                    if is_entry(kvs, &|k| {
+                        matches!(k.kind(), NirKind::TextLit(_))
+                    }) =>
+                {
+                    Some(
+                        xs.iter()
+                            .enumerate()
+                            .map(|(i, x)| {
+                                let kvs = match x.kind() {
+                                    NirKind::RecordLit(kvs) => kvs,
+                                    _ => unreachable!("Internal type error"),
+                                };
+                                let k = kvs.get("mapKey").unwrap();
+                                let v = kvs.get("mapValue").unwrap();
+                                // Entries with text keys are located like record fields.
+                                Ok(match k.kind() {
+                                    NirKind::TextLit(t) => {
+                                        let t = t
+                                            .as_text()
+                                            .ok_or_else(|| self.not_simple())?;
+                                        (
+                                            self.child(Field(t.clone()), k),
+                                            self.child(Field(t), v),
+                                        )
+                                    }
+                                    _ => {
+                                        let entry = self.child(Index(i), x);
+                                        (
+                                            entry.child(
+                                                Field("mapKey".to_owned()),
+                                                k,
+                                            ),
+                                            entry.child(
+                                                Field("mapValue".to_owned()),
+                                                v,
+                                            ),
+                                        )
+                                    }
+                                })
+                            })
+                            .collect(),
+                    )
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// The fields of a record, in sorted order.
+    fn fields(
+        &self,
+        kvs: &std::collections::HashMap<dhall::syntax::Label, Nir<'cx>>,
+    ) -> Vec<(String, Self)> {
+        let mut fields: Vec<_> = kvs
+            .iter()
+            .map(|(k, v)| {
+                let k = k.to_string();
+                let field = PathSegment::Field(k.clone());
+                (k, self.child(field, v))
+            })
+            .collect();
+        fields.sort_by(|(a, _), (b, _)| a.cmp(b));
+        fields
+    }
+
+    fn deserialize_any_<'de, V>(self, visitor: V) -> crate::Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        use PathSegment::*;
+
+        if let Some(entries) = self.map_entries(true) {
+            return visitor
+                .visit_map(MapDeserializer::new(entries?.into_iter()));
+        }
+        match self.nir.kind() {
+            NirKind::Num(NumKind::Bool(x)) => visitor.visit_bool(*x),
+            NirKind::Num(NumKind::Natural(x)) => visitor.visit_u64(*x),
+            NirKind::Num(NumKind::Integer(x)) => visitor.visit_i64(*x),
+            NirKind::Num(NumKind::Double(x)) => visitor.visit_f64((*x).into()),
+            NirKind::TextLit(t) => match t.as_text() {
+                Some(x) => visitor.visit_string(x),
+                None => Err(self.not_simple()),
+            },
+            NirKind::EmptyOptionalLit(_) => visitor.visit_none(),
+            NirKind::NEOptionalLit(x) => visitor.visit_some(NirDeserializer {
+                cx: self.cx,
+                nir: x.clone(),
+                path: self.path.clone(),
+                enums: self.enums.clone(),
+            }),
+            NirKind::EmptyListLit(_) => visitor
+                .visit_seq(SeqDeserializer::new(std::iter::empty::<Self>())),
+            NirKind::NEListLit(xs) => visitor.visit_seq(SeqDeserializer::new(
+                xs.iter().enumerate().map(|(i, x)| self.child(Index(i), x)),
+            )),
+            NirKind::RecordLit(kvs) => visitor
+                .visit_map(MapDeserializer::new(self.fields(kvs).into_iter())),
+            _ => match self.union_variant() {
+                Some((variant, x)) => self.visit_union(visitor, variant, x),
+                None => Err(self.not_simple()),
+            },
+        }
+    }
+
+    /// If this is a union value, returns its alternative and contents.
+    fn union_variant(&self) -> Option<(&Label, Option<&Nir<'cx>>)> {
+        match self.nir.kind() {
+            NirKind::UnionLit(variant, x, _) => Some((variant, Some(x))),
+            NirKind::UnionConstructor(variant, kts)
+                if matches!(kts.get(variant), Some(None)) =>
+            {
+                Some((variant, None))
+            }
+            _ => None,
+        }
+    }
+
+    /// Reads a union value as an externally tagged enum.
+    fn visit_variant<'de, V>(
+        &self,
+        visitor: V,
+        variant: &Label,
+        x: Option<&Nir<'cx>>,
+    ) -> crate::Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        let variant = variant.to_string();
+        match x {
+            Some(x) => {
+                let x = self.child(PathSegment::Variant(variant.clone()), x);
+                visitor.visit_enum(MapAccessDeserializer::new(
+                    MapDeserializer::new(Some((variant, x)).into_iter()),
+                ))
+            }
+            None => visitor.visit_enum(MapAccessDeserializer::new(
+                MapDeserializer::new(Some((variant, ())).into_iter()),
+            )),
+        }
+    }
+
+    /// Reads a union value in the shape chosen by `enums`.
+    fn visit_union<'de, V>(
+        &self,
+        visitor: V,
+        variant: &Label,
+        x: Option<&Nir<'cx>>,
+    ) -> crate::Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        let contents =
+            x.map(|x| self.child(PathSegment::Variant(variant.to_string()), x));
+        let tag_field = |tag: &str| {
+            let nir = Nir::from_text(variant);
+            (
+                tag.to_owned(),
+                self.child(PathSegment::Field(tag.to_owned()), &nir),
+            )
+        };
+        match &*self.enums {
+            EnumRepresentation::External => {
+                self.visit_variant(visitor, variant, x)
+            }
+            EnumRepresentation::Internal { tag } => {
+                let mut entries = vec![tag_field(tag)];
+                if let Some(contents) = contents {
+                    match contents.nir.kind() {
+                        NirKind::RecordLit(kvs) => {
+                            entries.extend(contents.fields(kvs))
+                        }
+                        _ => {
+                            return Err(Error(ErrorKind::Deserialize(format!(
+                                "the contents of `{}` must be a record to be \
+                                 read as an internally tagged enum",
+                                variant
+                            ))))
+                        }
+                    }
+                }
+                visitor.visit_map(MapDeserializer::new(entries.into_iter()))
+            }
+            EnumRepresentation::Adjacent { tag, content } => {
+                let mut entries = vec![tag_field(tag)];
+                if let Some(contents) = contents {
+                    entries.push((content.clone(), contents));
+                }
+                visitor.visit_map(MapDeserializer::new(entries.into_iter()))
+            }
+            EnumRepresentation::Untagged => match contents {
+                Some(contents) => contents.deserialize_any_(visitor),
+                None => visitor.visit_unit(),
+            },
+        }
+    }
+}
+
+impl<'de, 'cx> serde::de::IntoDeserializer<'de, Error>
+    for NirDeserializer<'cx>
+{
+    type Deserializer = Self;
+    fn into_deserializer(self) -> Self::Deserializer {
+        self
+    }
+}
+
+impl<'de, 'cx> serde::Deserializer<'de> for NirDeserializer<'cx> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> crate::Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        let path = self.path.clone();
+        self.deserialize_any_(visitor).map_err(|e| e.at_path(&path))
+    }
+
+    // Maps can have keys of any type, but only maps with `Text` keys look like maps to
+    // `deserialize_any`.
+    fn deserialize_map<V>(self, visitor: V) -> crate::Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        match self.map_entries(false) {
+            Some(entries) => entries
+                .and_then(|entries| {
+                    visitor.visit_map(MapDeserializer::new(entries.into_iter()))
+                })
+                .map_err(|e| e.at_path(&self.path)),
+            None => self.deserialize_any(visitor),
+        }
+    }
+
+    // Enums can also be read from text, e.g. the keys of a map produced by `toMap`. Enums that
+    // ask for their variant always read unions as externally tagged.
+    fn deserialize_enum<V>(
+        self,
+        _: &'static str,
+        _: &'static [&'static str],
+        visitor: V,
+    ) -> crate::Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        let res = match self.nir.kind() {
+            NirKind::TextLit(t) if t.as_text().is_some() => {
+                visitor.visit_enum(t.as_text().unwrap().into_deserializer())
+            }
+            _ => match self.union_variant() {
+                Some((variant, x)) => self.visit_variant(visitor, variant, x),
+                None => return self.deserialize_any(visitor),
+            },
+        };
+        res.map_err(|e: Error| e.at_path(&self.path))
+    }
+
+    // Lists of map entries can also be read as lists.
+    fn deserialize_seq<V>(self, visitor: V) -> crate::Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        match self.nir.kind() {
+            NirKind::NEListLit(xs) => visitor
+                .visit_seq(SeqDeserializer::new(
+                    xs.iter()
+                        .enumerate()
+                        .map(|(i, x)| self.child(PathSegment::Index(i), x)),
+                ))
+                .map_err(|e| e.at_path(&self.path)),
+            NirKind::EmptyListLit(_) => visitor
+                .visit_seq(SeqDeserializer::new(std::iter::empty::<Self>()))
+                .map_err(|e: Error| e.at_path(&self.path)),
             _ => self.deserialize_any(visitor),
         }
     }
 
+    fn deserialize_tuple<V>(
+        self,
+        _: usize,
+        visitor: V,
+    ) -> crate::Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        match self.nir.kind() {
+            // Blindly takes keys in sorted order.
+            NirKind::RecordLit(kvs) => visitor
+                .visit_seq(SeqDeserializer::new(
+                    self.fields(kvs).into_iter().map(|(_, v)| v),
+                ))
+                .map_err(|e| e.at_path(&self.path)),
+            _ => self.deserialize_seq(visitor),
+        }
+    }
+
     fn deserialize_unit<V>(self, visitor: V) -> crate::Result<V::Value>
     where
         V: serde::de::Visitor<'de>,
     {
-        match self.0.as_ref() {
-            SimpleValue::Record(m) if m.is_empty() => visitor.visit_unit(),
+        match self.nir.kind() {
+            NirKind::RecordLit(kvs) if kvs.is_empty() => visitor
+                .visit_unit()
+                .map_err(|e: Error| e.at_path(&self.path)),
             _ => self.deserialize_any(visitor),
         }
     }
 
+    fn deserialize_unit_struct<V>(
+        self,
+        _: &'static str,
+        visitor: V,
+    ) -> crate::Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        self.deserialize_unit(visitor)
+    }
+
+    // Newtype structs are transparent.
+    fn deserialize_newtype_struct<V>(
+        self,
+        _: &'static str,
+        visitor: V,
+    ) -> crate::Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> crate::Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    // Ignored values are never evaluated.
+    fn deserialize_ignored_any<V>(self, visitor: V) -> crate::Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
     serde::forward_to_deserialize_any! {
         bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
-        bytes byte_buf option unit_struct newtype_struct seq
-        tuple_struct map struct enum identifier ignored_any
+        bytes byte_buf option struct identifier
     }
 }
 
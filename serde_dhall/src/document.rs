@@ -0,0 +1,128 @@
+use std::fmt;
+use std::path::Path;
+
+use crate::{Error, ErrorKind, Result, ToDhall};
+
+/// A Dhall file that can be edited from Rust without losing its comments, `let`-bindings and
+/// formatting.
+///
+/// Each edit only prints the value it adds or replaces: the rest of the text is kept as it was.
+/// Values are found by their path, e.g. `.services.web.port` or `.hosts[2]`, written like the
+/// paths of [`Value::pointer()`]. Paths look through `let`s and type annotations, and through
+/// variables bound by a `let` of the file, so that in `let web = { port = 80 } in { web }` the
+/// path `.web.port` points to the `80`. They also look into the record of a completion
+/// `Service::{ port = 80 }`, and into the side of `a // b` that the field comes from.
+///
+/// The file is kept as a concrete syntax tree, with its comments attached to the values next to
+/// them: a replaced value keeps the comments before it and at the end of its line.
+///
+/// Values are converted to Dhall without a type annotation, so they can't contain empty lists,
+/// `None` or enums.
+///
+/// [`Value::pointer()`]: crate::Value::pointer()
+///
+/// # Example
+///
+/// ```rust
+/// # fn main() -> serde_dhall::Result<()> {
+/// use serde_dhall::Document;
+///
+/// let mut doc = Document::parse(
+///     "let defaultPort = 80\n\
+///      in  { web = { port = defaultPort } -- the public port\n\
+///          , hosts = [ \"a\" ]\n\
+///          }",
+/// )?;
+/// doc.set(".web.port", &8080u64)?;
+/// doc.push(".hosts", &"b")?;
+/// doc.set_let("defaultPort", &443u64)?;
+/// assert_eq!(
+///     doc.to_string(),
+///     "let defaultPort = 443\n\
+///      in  { web = { port = 8080 } -- the public port\n\
+///          , hosts = [ \"a\", \"b\" ]\n\
+///          }",
+/// );
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Document(dhall::syntax::Document);
+
+fn dhall_error(e: dhall::error::Error) -> Error {
+    Error(ErrorKind::Dhall(e))
+}
+
+impl Document {
+    /// Parses Dhall source text.
+    pub fn parse(text: &str) -> Result<Self> {
+        dhall::syntax::Document::parse(text)
+            .map(Document)
+            .map_err(dhall_error)
+    }
+
+    /// Reads and parses the Dhall file at `path`.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let text =
+            std::fs::read_to_string(path).map_err(|e| dhall_error(e.into()))?;
+        Self::parse(&text)
+    }
+
+    /// Writes the current text to the file at `path`, replacing its contents.
+    pub fn to_file(&self, path: impl AsRef<Path>) -> Result<()> {
+        std::fs::write(path, self.as_str()).map_err(|e| dhall_error(e.into()))
+    }
+
+    /// The current text.
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+
+    /// Sets the value at `path`. If the path points to a missing field of a record, the field is
+    /// added at the end of the record.
+    pub fn set<T>(&mut self, path: &str, value: &T) -> Result<()>
+    where
+        T: ToDhall,
+    {
+        let value = value.to_dhall(None)?.to_expr();
+        self.0.set(path, &value).map_err(dhall_error)
+    }
+
+    /// Inserts a value in the list at `path`, so that it ends up at position `index`.
+    pub fn insert<T>(
+        &mut self,
+        path: &str,
+        index: usize,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: ToDhall,
+    {
+        let value = value.to_dhall(None)?.to_expr();
+        self.0.insert(path, index, &value).map_err(dhall_error)
+    }
+
+    /// Adds a value at the end of the list at `path`.
+    pub fn push<T>(&mut self, path: &str, value: &T) -> Result<()>
+    where
+        T: ToDhall,
+    {
+        let value = value.to_dhall(None)?.to_expr();
+        self.0.push(path, &value).map_err(dhall_error)
+    }
+
+    /// Replaces the value bound by the first `let` named `name`, from the outside in.
+    pub fn set_let<T>(&mut self, name: &str, value: &T) -> Result<()>
+    where
+        T: ToDhall,
+    {
+        let value = value.to_dhall(None)?.to_expr();
+        self.0.set_let(name, &value).map_err(dhall_error)
+    }
+}
+
+impl fmt::Display for Document {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
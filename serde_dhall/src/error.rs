@@ -1,4 +1,7 @@
-use dhall::error::Error as DhallError;
+use dhall::error::{Error as DhallError, ErrorBuilder};
+use dhall::syntax::{Expr, Span};
+
+use crate::deserialize::Path;
 
 /// Alias for a `Result` with the error type `serde_dhall::Error`.
 pub type Result<T> = std::result::Result<T, Error>;
@@ -11,9 +14,58 @@ pub struct Error(pub(crate) ErrorKind);
 pub(crate) enum ErrorKind {
     Dhall(DhallError),
     Deserialize(String),
+    /// A deserialization error that occurred inside the value, e.g. in a record field.
+    DeserializeAt {
+        path: Path,
+        msg: String,
+        /// The error rendered along with the relevant bit of source, when we know where in the
+        /// source the value came from.
+        snippet: Option<String>,
+    },
     Serialize(String),
 }
 
+impl Error {
+    /// Records where in the value a deserialization error occurred. Errors raised deeper in the
+    /// value are reported first, so this keeps any path that is already known.
+    pub(crate) fn at_path(self, path: &Path) -> Self {
+        match self.0 {
+            ErrorKind::Deserialize(msg) if !path.is_root() => {
+                ErrorKind::DeserializeAt {
+                    path: path.clone(),
+                    msg,
+                    snippet: None,
+                }
+                .into()
+            }
+            _ => self,
+        }
+    }
+
+    /// Points the error to the location of the faulty value in the source expression, if it can
+    /// be found.
+    pub(crate) fn with_source(self, expr: &Expr) -> Self {
+        match self.0 {
+            ErrorKind::DeserializeAt {
+                path,
+                msg,
+                snippet: None,
+            } => {
+                let snippet = match path.span_in(expr) {
+                    Some(span @ Span::Parsed(_)) => Some(
+                        ErrorBuilder::new(format!("at `{}`: {}", path, msg))
+                            .span_err(span, "this value")
+                            .format(),
+                    ),
+                    _ => None,
+                };
+                ErrorKind::DeserializeAt { path, msg, snippet }.into()
+            }
+            kind => Error(kind),
+        }
+    }
+}
+
 impl From<ErrorKind> for Error {
     fn from(kind: ErrorKind) -> Error {
         Error(kind)
@@ -25,6 +77,13 @@ impl std::fmt::Display for Error {
         match &self.0 {
             ErrorKind::Dhall(err) => write!(f, "{}", err),
             ErrorKind::Deserialize(err) => write!(f, "{}", err),
+            ErrorKind::DeserializeAt {
+                snippet: Some(snippet),
+                ..
+            } => write!(f, "{}", snippet),
+            ErrorKind::DeserializeAt { path, msg, .. } => {
+                write!(f, "at `{}`: {}", path, msg)
+            }
             ErrorKind::Serialize(err) => write!(f, "{}", err),
         }
     }
@@ -39,6 +98,31 @@ impl serde::de::Error for Error {
     {
         ErrorKind::Deserialize(msg.to_string()).into()
     }
+
+    // Describe the value we found in Dhall terms rather than in terms of the serde data model.
+    fn invalid_type(
+        unexp: serde::de::Unexpected,
+        exp: &dyn serde::de::Expected,
+    ) -> Self {
+        use serde::de::Unexpected::*;
+        let found = match unexp {
+            Bool(x) => format!("Bool `{}`", if x { "True" } else { "False" }),
+            Unsigned(x) => format!("Natural `{}`", x),
+            Signed(x) => format!("Integer `{:+}`", x),
+            Float(x) => format!("Double `{}`", x),
+            Str(x) => format!("Text {:?}", x),
+            Option => "an Optional".to_owned(),
+            Seq => "a List".to_owned(),
+            Map => "a record".to_owned(),
+            Enum => "a union".to_owned(),
+            Unit => "an empty record".to_owned(),
+            other => other.to_string(),
+        };
+        <Self as serde::de::Error>::custom(format_args!(
+            "invalid type: expected {}, found {}",
+            exp, found
+        ))
+    }
 }
 
 impl serde::ser::Error for Error {
@@ -0,0 +1,177 @@
+use dhall::error::{TypeError, TypeMessage};
+use dhall::semantics::{apply_any, Nir, NirKind};
+use dhall::syntax::Expr;
+use dhall::{Ctxt, KeptNir, SharedCtxt};
+
+use crate::value::SimpleType;
+use crate::{Error, ErrorKind, FromDhall, Result, ToDhall, Value};
+
+/// A Dhall function, e.g. `λ(env : Text) → { name = env }`.
+///
+/// This can be obtained by parsing a Dhall expression that evaluates to a function, and then
+/// called from Rust with [`call()`]. The argument must have a type that corresponds to a
+/// [`SimpleType`]. This makes it possible to write configuration templates in Dhall and fill them
+/// in from Rust.
+///
+/// The function keeps the context it was evaluated in, with the builtins and evaluation limits of
+/// the [`Deserializer`] it was parsed with, so calls don't evaluate it again. Each call counts
+/// evaluation steps from zero, but if a call goes over the limits, the function may be left
+/// partially evaluated and later calls fail with the same error.
+///
+/// [`call()`]: Function::call()
+/// [`Deserializer`]: crate::Deserializer
+///
+/// # Example
+///
+/// ```
+/// # fn main() -> serde_dhall::Result<()> {
+/// use serde::Deserialize;
+/// use serde_dhall::{FromDhall, Function};
+///
+/// #[derive(Deserialize)]
+/// struct Config {
+///     name: String,
+///     replicas: u64,
+/// }
+///
+/// let template = r#"
+///     \(prod: Bool) -> { name = if prod then "app" else "app-dev", replicas = if prod then 3 else 1 }
+/// "#;
+/// let template = serde_dhall::from_str(template).parse::<Function>()?;
+///
+/// let config = Config::from_dhall(&template.call(&true)?)?;
+/// assert_eq!(config.name, "app");
+/// assert_eq!(config.replicas, 3);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Function {
+    /// The normalized function.
+    nir: KeptNir,
+    /// The type of the function. Always a function type. Kept in the same context as `nir`.
+    ty: KeptNir,
+}
+
+impl Function {
+    pub(crate) fn from_nir_and_ty<'cx>(
+        cx: Ctxt<'cx>,
+        x: &Nir<'cx>,
+        ty: &Nir<'cx>,
+        shared: &SharedCtxt,
+    ) -> Option<Self> {
+        match ty.kind() {
+            NirKind::PiClosure { .. } => Some(Function {
+                nir: shared.keep(cx, x.clone()),
+                ty: shared.keep(cx, ty.clone()),
+            }),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn to_expr(&self) -> Expr {
+        self.nir
+            .ctxt()
+            .with(|cx| self.nir.get(cx).to_expr(cx, Default::default()))
+    }
+
+    /// Applies the function to an argument. The argument is typechecked against the type the
+    /// function expects, and a mismatch is reported as a type error.
+    ///
+    /// If the function takes several arguments, this returns a function that can be called with
+    /// the remaining ones.
+    pub fn call(&self, arg: &impl ToDhall) -> Result<Value> {
+        self.nir.ctxt().with(|cx| {
+            cx.restart_budget();
+            self.call_in(cx, arg)
+                .map_err(ErrorKind::Dhall)
+                .map_err(Error)?
+        })
+    }
+
+    fn call_in<'cx>(
+        &self,
+        cx: Ctxt<'cx>,
+        arg: &impl ToDhall,
+    ) -> dhall::error::Result<Result<Value>> {
+        let f = self.nir.get(cx);
+        let (annot, closure) = match self.ty.get(cx).kind() {
+            NirKind::PiClosure { annot, closure, .. } => (annot, closure),
+            _ => unreachable!("a function must have a function type"),
+        };
+
+        let arg_ty = match SimpleType::from_nir(annot) {
+            Ok(arg_ty) => arg_ty,
+            Err(_) => {
+                return Err(type_error(format!(
+                    "cannot call a function that takes an argument of type {}",
+                    annot.to_expr(cx, Default::default())
+                )))
+            }
+        };
+        let arg = match arg.to_dhall(Some(&arg_ty)) {
+            Ok(arg) => arg,
+            Err(e) => return Err(type_error(e.to_string())),
+        };
+        let arg = match arg.to_simple_value() {
+            Some(arg) => arg,
+            None => {
+                return Err(type_error(format!(
+                    "expected a value of type {}, found {}",
+                    arg_ty, arg
+                )))
+            }
+        };
+        let arg = match arg.to_hir(Some(&arg_ty)) {
+            Ok(arg) => arg,
+            Err(e) => return Err(type_error(e.to_string())),
+        };
+        // The conversion above does not catch everything, e.g. missing record fields.
+        let arg_tir = arg.typecheck_noenv(cx)?;
+        if arg_tir.ty().as_nir() != annot {
+            return Err(type_error(format!(
+                "expected a value of type {}, found a value of type {}",
+                arg_ty,
+                arg_tir.ty().as_nir().to_expr(cx, Default::default())
+            )));
+        }
+
+        let arg = arg.eval_closed_expr(cx);
+        let val = Nir::from_kind(apply_any(f, arg.clone()));
+        let val_ty = closure.apply(arg);
+        let val = Value::from_nir_and_ty(cx, &val, &val_ty, self.nir.ctxt());
+        cx.check_limits()?;
+        Ok(val)
+    }
+}
+
+fn type_error(msg: String) -> dhall::error::Error {
+    TypeError::new(TypeMessage::Custom(msg)).into()
+}
+
+impl crate::deserialize::Sealed for Function {}
+
+impl FromDhall for Function {
+    fn from_dhall(v: &Value) -> Result<Self> {
+        v.to_function().ok_or_else(|| {
+            Error(ErrorKind::Deserialize(format!(
+                "this cannot be deserialized into a function: {}",
+                v
+            )))
+        })
+    }
+}
+
+/// Functions are only equal if they were evaluated together, e.g. as parts of the same parsed
+/// value, since their builtins and limits could differ otherwise.
+impl PartialEq for Function {
+    fn eq(&self, other: &Self) -> bool {
+        let cx = self.nir.ctxt();
+        cx.ptr_eq(other.nir.ctxt())
+            && cx.with(|cx| {
+                self.ty.get(cx) == other.ty.get(cx)
+                    && self.nir.get(cx) == other.nir.get(cx)
+            })
+    }
+}
+impl Eq for Function {}
@@ -0,0 +1,629 @@
+use std::collections::{BTreeSet, HashMap};
+
+use dhall::builtins::Builtin;
+use dhall::operations::OpKind;
+use dhall::syntax::{Const, Expr, ExprKind, Label, NumKind, V};
+use serde_json::{Map, Number, Value as Json};
+
+use crate::deserialize::{Path, PathSegment};
+use crate::{Error, ErrorKind, Result, SimpleType, SimpleValue, Value};
+
+/// The constructors of `Prelude.JSON.Type`, in any of its versions.
+const JSON_CONSTRUCTORS: &[&str] = &[
+    "array", "bool", "double", "integer", "null", "number", "object", "string",
+];
+
+/// Options for converting Dhall values to JSON, as with the flags of `dhall-to-json`.
+///
+/// This is used with [`Deserializer::to_json()`](crate::Deserializer::to_json).
+///
+/// # Example
+///
+/// ```rust
+/// # fn main() -> serde_dhall::Result<()> {
+/// use serde_dhall::JsonOptions;
+///
+/// let data = serde_dhall::from_str(
+///     "{ name = \"app\", replicas = None Natural, tags = [] : List Text }",
+/// );
+///
+/// assert_eq!(
+///     data.to_json(&JsonOptions::new())?.to_string(),
+///     r#"{"name":"app","tags":[]}"#
+/// );
+/// assert_eq!(
+///     data.to_json(&JsonOptions::new().omit_empty(true))?.to_string(),
+///     r#"{"name":"app"}"#
+/// );
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct JsonOptions {
+    omit_empty: bool,
+    preserve_null: bool,
+    maps: bool,
+}
+
+impl JsonOptions {
+    /// The default options, which match the defaults of `dhall-to-json`.
+    pub fn new() -> Self {
+        JsonOptions {
+            omit_empty: false,
+            preserve_null: false,
+            maps: true,
+        }
+    }
+
+    /// Omits record fields that are `null`, empty objects or empty arrays, after recursively
+    /// omitting such fields in their contents. This is `dhall-to-json --omit-empty`.
+    pub fn omit_empty(mut self, omit_empty: bool) -> Self {
+        self.omit_empty = omit_empty;
+        self
+    }
+
+    /// Keeps record fields whose value is `null`, e.g. `None Natural`. By default they are
+    /// omitted. This is `dhall-to-json --preserve-null`.
+    pub fn preserve_null(mut self, preserve_null: bool) -> Self {
+        self.preserve_null = preserve_null;
+        self
+    }
+
+    /// Whether lists of `{ mapKey : Text, mapValue : T }` records, like the ones produced by
+    /// `toMap`, become JSON objects. This is enabled by default; disabling it is
+    /// `dhall-to-json --no-maps`.
+    pub fn maps(mut self, maps: bool) -> Self {
+        self.maps = maps;
+        self
+    }
+}
+
+impl Default for JsonOptions {
+    fn default() -> Self {
+        JsonOptions::new()
+    }
+}
+
+/// Converts a normalized expression to JSON.
+pub(crate) fn expr_to_json(expr: &Expr, options: &JsonOptions) -> Result<Json> {
+    let json = Converter {
+        options,
+        json_var: None,
+    }
+    .convert(expr, &Path::default())?;
+    Ok(if options.omit_empty {
+        omit_empty(json)
+    } else if !options.preserve_null {
+        omit_null(json)
+    } else {
+        json
+    })
+}
+
+#[cfg(feature = "yaml")]
+pub(crate) fn json_to_yaml(json: &Json) -> Result<String> {
+    let yaml = serde_yaml::to_string(json)
+        .map_err(|e| ErrorKind::Serialize(e.to_string()))?;
+    // Like `dhall-to-yaml`, we don't start the document with `---`.
+    let yaml = yaml.strip_prefix("---\n").unwrap_or(&yaml);
+    Ok(format!("{}\n", yaml.trim_end_matches('\n')))
+}
+
+struct Converter<'a> {
+    options: &'a JsonOptions,
+    /// Inside a `Prelude.JSON` value, the variable bound to the record of JSON constructors.
+    json_var: Option<Label>,
+}
+
+impl<'a> Converter<'a> {
+    fn convert(&self, expr: &Expr, path: &Path) -> Result<Json> {
+        use ExprKind::*;
+        let err = || {
+            Error::from(ErrorKind::Deserialize(format!(
+                "this cannot be converted to JSON: {}",
+                expr
+            )))
+            .at_path(path)
+        };
+        Ok(match expr.kind() {
+            Num(NumKind::Bool(x)) => Json::Bool(*x),
+            Num(NumKind::Natural(x)) => Json::from(*x),
+            Num(NumKind::Integer(x)) => Json::from(*x),
+            Num(NumKind::Double(x)) => {
+                Json::Number(Number::from_f64((*x).into()).ok_or_else(err)?)
+            }
+            TextLit(t) if t.tail().is_empty() => {
+                Json::String(t.head().to_owned())
+            }
+            SomeLit(x) => self.convert(x, path)?,
+            EmptyListLit(t) if self.options.maps && is_map_type(t) => {
+                Json::Object(Map::new())
+            }
+            EmptyListLit(_) => Json::Array(Vec::new()),
+            NEListLit(xs) => {
+                if self.options.maps && is_map(xs) {
+                    return self.object(xs, path);
+                }
+                Json::Array(
+                    xs.iter()
+                        .enumerate()
+                        .map(|(i, x)| {
+                            self.convert(x, &path.child(PathSegment::Index(i)))
+                        })
+                        .collect::<Result<_>>()?,
+                )
+            }
+            RecordLit(kvs) => Json::Object(
+                kvs.iter()
+                    .map(|(k, v)| {
+                        let field = PathSegment::Field(k.to_string());
+                        Ok((
+                            k.to_string(),
+                            self.convert(v, &path.child(field))?,
+                        ))
+                    })
+                    .collect::<Result<_>>()?,
+            ),
+            Op(OpKind::Field(u, l)) => match u.kind() {
+                UnionType(_) => Json::String(l.to_string()),
+                _ if self.is_json_var(u) && l.as_ref() == "null" => Json::Null,
+                _ => return Err(err()),
+            },
+            Op(OpKind::App(f, x)) => match f.kind() {
+                Builtin(dhall::builtins::Builtin::OptionalNone) => Json::Null,
+                Op(OpKind::Field(u, l)) if matches!(u.kind(), UnionType(_)) => {
+                    let variant = PathSegment::Variant(l.to_string());
+                    self.convert(x, &path.child(variant))?
+                }
+                Op(OpKind::Field(v, l)) if self.is_json_var(v) => {
+                    match l.as_ref() {
+                        "object" => match x.kind() {
+                            EmptyListLit(_) => Json::Object(Map::new()),
+                            NEListLit(xs) if is_map(xs) => {
+                                self.object(xs, path)?
+                            }
+                            _ => return Err(err()),
+                        },
+                        "array" | "bool" | "double" | "integer" | "number"
+                        | "string" => self.convert(x, path)?,
+                        _ => return Err(err()),
+                    }
+                }
+                _ => return Err(err()),
+            },
+            Lam(..) if self.json_var.is_none() => match json_body(expr) {
+                Some((json_var, body)) => Converter {
+                    options: self.options,
+                    json_var: Some(json_var.clone()),
+                }
+                .convert(body, path)?,
+                None => return Err(err()),
+            },
+            _ => return Err(err()),
+        })
+    }
+
+    /// Converts a non-empty list of `{ mapKey, mapValue }` records with `Text` keys to an object.
+    fn object(&self, entries: &[Expr], path: &Path) -> Result<Json> {
+        let mut map = Map::new();
+        for entry in entries {
+            let (k, v) = match entry.kind() {
+                ExprKind::RecordLit(kvs) => {
+                    (kvs.get("mapKey").unwrap(), kvs.get("mapValue").unwrap())
+                }
+                _ => unreachable!("Internal type error"),
+            };
+            let k = match self.convert(k, path)? {
+                Json::String(k) => k,
+                _ => unreachable!("Internal type error"),
+            };
+            let field = PathSegment::Field(k.clone());
+            map.insert(k, self.convert(v, &path.child(field))?);
+        }
+        Ok(Json::Object(map))
+    }
+
+    fn is_json_var(&self, expr: &Expr) -> bool {
+        match (expr.kind(), &self.json_var) {
+            (ExprKind::Var(V(l, 0)), Some(json_var)) => l == json_var,
+            _ => false,
+        }
+    }
+}
+
+/// Whether this is the type `List { mapKey : Text, mapValue : T }`.
+fn is_map_type(list_ty: &Expr) -> bool {
+    let kts = match list_ty.kind() {
+        ExprKind::Op(OpKind::App(f, t))
+            if matches!(f.kind(), ExprKind::Builtin(Builtin::List)) =>
+        {
+            match t.kind() {
+                ExprKind::RecordType(kts) => kts,
+                _ => return false,
+            }
+        }
+        _ => return false,
+    };
+    kts.len() == 2
+        && kts.contains_key("mapValue")
+        && matches!(
+            kts.get("mapKey").map(Expr::kind),
+            Some(ExprKind::Builtin(Builtin::Text))
+        )
+}
+
+fn is_map(xs: &[Expr]) -> bool {
+    match xs[0].kind() {
+        ExprKind::RecordLit(kvs) => {
+            kvs.len() == 2
+                && kvs.contains_key("mapValue")
+                && matches!(
+                    kvs.get("mapKey").map(Expr::kind),
+                    Some(ExprKind::TextLit(_))
+                )
+        }
+        _ => false,
+    }
+}
+
+/// If this is a value of type `Prelude.JSON.Type`, i.e. of the form
+/// `λ(JSON : Type) → λ(json : { array : .., bool : .., .. }) → body`, returns the name of the
+/// `json` variable and the body.
+fn json_body(expr: &Expr) -> Option<(&Label, &Expr)> {
+    match expr.kind() {
+        ExprKind::Lam(_, ty, inner)
+            if matches!(ty.kind(), ExprKind::Const(Const::Type)) =>
+        {
+            match inner.kind() {
+                ExprKind::Lam(json_var, ty, body) => match ty.kind() {
+                    ExprKind::RecordType(kts)
+                        if kts.contains_key("object")
+                            && kts.keys().all(|k| {
+                                JSON_CONSTRUCTORS.contains(&k.as_ref())
+                            }) =>
+                    {
+                        Some((json_var, body))
+                    }
+                    _ => None,
+                },
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Removes the fields of objects that are `null`, recursively.
+fn omit_null(json: Json) -> Json {
+    match json {
+        Json::Object(map) => Json::Object(
+            map.into_iter()
+                .filter(|(_, v)| !v.is_null())
+                .map(|(k, v)| (k, omit_null(v)))
+                .collect(),
+        ),
+        Json::Array(xs) => Json::Array(xs.into_iter().map(omit_null).collect()),
+        json => json,
+    }
+}
+
+/// Turns empty objects and arrays into `null`, and removes the fields of objects that are
+/// `null`, recursively.
+fn omit_empty(json: Json) -> Json {
+    match json {
+        Json::Object(map) => {
+            let map: Map<_, _> = map
+                .into_iter()
+                .map(|(k, v)| (k, omit_empty(v)))
+                .filter(|(_, v)| !v.is_null())
+                .collect();
+            if map.is_empty() {
+                Json::Null
+            } else {
+                Json::Object(map)
+            }
+        }
+        Json::Array(xs) if xs.is_empty() => Json::Null,
+        Json::Array(xs) => {
+            Json::Array(xs.into_iter().map(omit_empty).collect())
+        }
+        json => json,
+    }
+}
+
+impl Value {
+    /// Converts JSON to a Dhall value of the given type, the way `json-to-dhall` does.
+    ///
+    /// - objects become records, in which case `Optional` fields may be missing, or maps, i.e.
+    ///   lists of `{ mapKey, mapValue }` records like the ones produced by `toMap`;
+    /// - `null` becomes `None`, and other values are wrapped in `Some` where needed;
+    /// - numbers can become `Natural`s, `Integer`s or `Double`s, as long as they fit;
+    /// - a union value is made with the first alternative, in alphabetical order, that the JSON
+    ///   can be converted to. Alternatives without contents are written as strings.
+    ///
+    /// YAML can be converted too, by first reading it into a `serde_json::Value`, e.g. with
+    /// `serde_yaml`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// use serde_dhall::{SimpleType, Value};
+    ///
+    /// let ty: SimpleType = "{ name : Text, port : Optional Natural, env : List { mapKey : Text, mapValue : Text } }"
+    ///     .parse()?;
+    /// let json = serde_json::json!({ "name": "app", "env": { "HOME": "/root" } });
+    ///
+    /// let value = Value::from_json(&json, &ty)?;
+    /// assert_eq!(
+    ///     value.to_string(),
+    ///     r#"{ env = [{ mapKey = "HOME", mapValue = "/root" }], name = "app", port = None Natural }"#
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_json(json: &Json, ty: &SimpleType) -> Result<Value> {
+        let val = json_to_simple_value(json, ty, &Path::default())?;
+        Ok(Value::from_val(val, Some(ty.clone())))
+    }
+
+    /// Converts JSON to a Dhall value like [`from_json()`](Value::from_json), with a type
+    /// inferred from the JSON. The inferred type is the [`ty()`](Value::ty) of the value.
+    ///
+    /// Objects become records. The elements of a list don't need to have the same type, as long
+    /// as their types can be merged: fields that are missing from some records become
+    /// `Optional`, `null`s make the type `Optional`, and numbers become `Integer`s or `Double`s
+    /// if they need to. The type of things we know nothing about, like the elements of an empty
+    /// list, is the empty union `<>`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// use serde_dhall::Value;
+    ///
+    /// let json = serde_json::json!([{ "x": 1 }, { "x": -2.5, "y": "a" }]);
+    ///
+    /// let value = Value::infer_from_json(&json)?;
+    /// assert_eq!(
+    ///     value.ty().unwrap().to_string(),
+    ///     "List { x : Double, y : Optional Text }"
+    /// );
+    /// assert_eq!(
+    ///     value.to_string(),
+    ///     r#"[{ x = 1.0, y = None Text }, { x = -2.5, y = Some "a" }]"#
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn infer_from_json(json: &Json) -> Result<Value> {
+        let ty = infer_type(json, &Path::default())?;
+        Value::from_json(json, &ty)
+    }
+}
+
+fn json_to_simple_value(
+    json: &Json,
+    ty: &SimpleType,
+    path: &Path,
+) -> Result<SimpleValue> {
+    use SimpleType as T;
+    use SimpleValue as V;
+    let err = || {
+        Error::from(ErrorKind::Deserialize(format!(
+            "expected a value of type `{}`, found {}",
+            ty, json
+        )))
+        .at_path(path)
+    };
+    Ok(match (ty, json) {
+        (T::Bool, Json::Bool(x)) => V::Num(NumKind::Bool(*x)),
+        (T::Natural, Json::Number(x)) => {
+            V::Num(NumKind::Natural(x.as_u64().ok_or_else(err)?))
+        }
+        (T::Integer, Json::Number(x)) => {
+            V::Num(NumKind::Integer(x.as_i64().ok_or_else(err)?))
+        }
+        (T::Double, Json::Number(x)) => {
+            V::Num(NumKind::Double(x.as_f64().ok_or_else(err)?.into()))
+        }
+        (T::Text, Json::String(x)) => V::Text(x.clone()),
+        (T::Optional(_), Json::Null) => V::Optional(None),
+        (T::Optional(t), _) => {
+            V::Optional(Some(Box::new(json_to_simple_value(json, t, path)?)))
+        }
+        (T::List(t), Json::Array(xs)) => V::List(
+            xs.iter()
+                .enumerate()
+                .map(|(i, x)| {
+                    json_to_simple_value(
+                        x,
+                        t,
+                        &path.child(PathSegment::Index(i)),
+                    )
+                })
+                .collect::<Result<_>>()?,
+        ),
+        (T::List(t), Json::Object(kvs)) => {
+            let (kt, vt) = match &**t {
+                T::Record(kts) if kts.len() == 2 => {
+                    match (kts.get("mapKey"), kts.get("mapValue")) {
+                        (Some(kt), Some(vt)) => (kt, vt),
+                        _ => return Err(err()),
+                    }
+                }
+                _ => return Err(err()),
+            };
+            let entries = kvs
+                .iter()
+                .map(|(k, v)| {
+                    let path = path.child(PathSegment::Field(k.clone()));
+                    let k = json_to_simple_value(
+                        &Json::String(k.clone()),
+                        kt,
+                        &path,
+                    )?;
+                    Ok((k, json_to_simple_value(v, vt, &path)?))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            // Maps with `Text` keys are represented as records.
+            if *kt == T::Text {
+                V::Record(
+                    entries
+                        .into_iter()
+                        .map(|(k, v)| match k {
+                            V::Text(k) => (k, v),
+                            _ => unreachable!(),
+                        })
+                        .collect(),
+                )
+            } else {
+                V::List(
+                    entries
+                        .into_iter()
+                        .map(|(k, v)| {
+                            V::Record(
+                                vec![
+                                    ("mapKey".to_owned(), k),
+                                    ("mapValue".to_owned(), v),
+                                ]
+                                .into_iter()
+                                .collect(),
+                            )
+                        })
+                        .collect(),
+                )
+            }
+        }
+        (T::Record(kts), Json::Object(kvs)) => {
+            if let Some(k) = kvs.keys().find(|k| !kts.contains_key(*k)) {
+                return Err(Error::from(ErrorKind::Deserialize(format!(
+                    "unknown field `{}`",
+                    k
+                )))
+                .at_path(path));
+            }
+            V::Record(
+                kts.iter()
+                    .map(|(k, t)| {
+                        let path = path.child(PathSegment::Field(k.clone()));
+                        let v = match (kvs.get(k), t) {
+                            (Some(x), _) => json_to_simple_value(x, t, &path)?,
+                            (None, T::Optional(_)) => V::Optional(None),
+                            (None, _) => {
+                                return Err(Error::from(
+                                    ErrorKind::Deserialize(format!(
+                                        "missing field `{}`",
+                                        k
+                                    )),
+                                )
+                                .at_path(&path))
+                            }
+                        };
+                        Ok((k.clone(), v))
+                    })
+                    .collect::<Result<_>>()?,
+            )
+        }
+        (T::Union(kts), _) => {
+            let mut alternatives: Vec<_> = kts.iter().collect();
+            alternatives.sort_by_key(|(k, _)| *k);
+            alternatives
+                .into_iter()
+                .find_map(|(k, t)| match (t, json) {
+                    (None, Json::String(x)) if x == k => {
+                        Some(V::Union(k.clone(), None))
+                    }
+                    (None, _) => None,
+                    (Some(t), _) => {
+                        let path = path.child(PathSegment::Variant(k.clone()));
+                        json_to_simple_value(json, t, &path)
+                            .ok()
+                            .map(|x| V::Union(k.clone(), Some(Box::new(x))))
+                    }
+                })
+                .ok_or_else(err)?
+        }
+        _ => return Err(err()),
+    })
+}
+
+fn infer_type(json: &Json, path: &Path) -> Result<SimpleType> {
+    use SimpleType as T;
+    Ok(match json {
+        Json::Null => T::Optional(Box::new(T::Union(HashMap::new()))),
+        Json::Bool(_) => T::Bool,
+        Json::Number(x) if x.is_u64() => T::Natural,
+        Json::Number(x) if x.is_i64() => T::Integer,
+        Json::Number(_) => T::Double,
+        Json::String(_) => T::Text,
+        Json::Array(xs) => {
+            let mut ty = T::Union(HashMap::new());
+            for (i, x) in xs.iter().enumerate() {
+                let x_ty = infer_type(x, &path.child(PathSegment::Index(i)))?;
+                ty =
+                    merge_types(ty.clone(), x_ty.clone()).ok_or_else(|| {
+                        Error::from(ErrorKind::Deserialize(format!(
+                        "the elements of this list have incompatible types \
+                         `{}` and `{}`",
+                        ty, x_ty
+                    )))
+                        .at_path(path)
+                    })?;
+            }
+            T::List(Box::new(ty))
+        }
+        Json::Object(kvs) => T::Record(
+            kvs.iter()
+                .map(|(k, v)| {
+                    let path = path.child(PathSegment::Field(k.clone()));
+                    Ok((k.clone(), infer_type(v, &path)?))
+                })
+                .collect::<Result<_>>()?,
+        ),
+    })
+}
+
+/// Finds a type that the values of both types can be converted to, if there is one. The empty
+/// union stands for values we know nothing about.
+fn merge_types(a: SimpleType, b: SimpleType) -> Option<SimpleType> {
+    use SimpleType::*;
+    let optional = |t: SimpleType| match t {
+        Optional(_) => t,
+        _ => Optional(Box::new(t)),
+    };
+    Some(match (a, b) {
+        (Union(kts), t) | (t, Union(kts)) if kts.is_empty() => t,
+        (Optional(a), Optional(b)) => Optional(Box::new(merge_types(*a, *b)?)),
+        (Optional(a), t) | (t, Optional(a)) => {
+            Optional(Box::new(merge_types(*a, t)?))
+        }
+        (Bool, Bool) => Bool,
+        (Text, Text) => Text,
+        (Natural, Natural) => Natural,
+        (Natural, Integer) | (Integer, Natural) | (Integer, Integer) => Integer,
+        (Double, Natural)
+        | (Double, Integer)
+        | (Double, Double)
+        | (Natural, Double)
+        | (Integer, Double) => Double,
+        (List(a), List(b)) => List(Box::new(merge_types(*a, *b)?)),
+        (Record(mut a), Record(mut b)) => {
+            let keys: BTreeSet<String> =
+                a.keys().chain(b.keys()).cloned().collect();
+            Record(
+                keys.into_iter()
+                    .map(|k| {
+                        let t = match (a.remove(&k), b.remove(&k)) {
+                            (Some(x), Some(y)) => merge_types(x, y)?,
+                            (Some(x), None) | (None, Some(x)) => optional(x),
+                            (None, None) => unreachable!(),
+                        };
+                        Some((k, t))
+                    })
+                    .collect::<Option<_>>()?,
+            )
+        }
+        _ => return None,
+    })
+}
@@ -259,8 +259,12 @@ mod test_readme {
 }
 
 mod deserialize;
+mod document;
 mod error;
+mod function;
+mod json;
 mod options;
+mod schema;
 mod serialize;
 mod static_type;
 /// Dhall values
@@ -270,10 +274,19 @@ mod value;
 pub use dhall_proc_macros::StaticType;
 
 pub use deserialize::{from_simple_value, FromDhall};
+pub use dhall::EvalLimits;
+pub use document::Document;
 pub(crate) use error::ErrorKind;
 pub use error::{Error, Result};
-pub use options::de::{from_binary_file, from_file, from_str, Deserializer};
+pub use function::Function;
+pub use json::JsonOptions;
+pub use options::de::{
+    from_binary, from_binary_file, from_file, from_reader, from_str, from_url,
+    Deserializer,
+};
 pub use options::ser::{serialize, Serializer};
+pub use options::EnumRepresentation;
+pub use schema::{schema, schema_with_default, Schema};
 pub use serialize::ToDhall;
-pub use static_type::StaticType;
-pub use value::{NumKind, SimpleType, SimpleValue, Value};
+pub use static_type::{RecordStaticType, StaticType};
+pub use value::{ColorChoice, Diff, NumKind, SimpleType, SimpleValue, Value};
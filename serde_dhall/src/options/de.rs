@@ -1,18 +1,30 @@
 use std::collections::HashMap;
+use std::io::Read;
 use std::path::{Path, PathBuf};
+use std::rc::Rc;
 
-use dhall::{Ctxt, Parsed};
+use dhall::builtins::HostFunction;
+use dhall::semantics::{ImportLocation, ImportOptions, Nir};
+use dhall::syntax::{Expr, ExprKind, Label, Span};
+use dhall::{Ctxt, EvalLimits, Parsed, SharedCtxt};
 
+use crate::json::{self, JsonOptions};
 use crate::options::{HasAnnot, ManualAnnot, NoAnnot, StaticAnnot, TypeAnnot};
-use crate::SimpleType;
-use crate::{Error, ErrorKind, FromDhall, Result, Value};
+use crate::{
+    EnumRepresentation, Error, ErrorKind, FromDhall, Result, StaticType,
+    ToDhall,
+};
+use crate::{SimpleType, SimpleValue};
 
 #[derive(Debug, Clone)]
 enum Source<'a> {
     Str(&'a str),
     File(PathBuf),
     BinaryFile(PathBuf),
-    // Url(&'a str),
+    Binary(&'a [u8]),
+    Url(&'a str),
+    /// The contents of a reader, read upfront.
+    Reader(Rc<std::io::Result<String>>),
 }
 
 /// Controls how a Dhall value is read.
@@ -59,9 +71,14 @@ pub struct Deserializer<'a, A> {
     source: Source<'a>,
     annot: A,
     allow_imports: bool,
+    import_options: ImportOptions,
     builtins: HashMap<dhall::syntax::Label, dhall::syntax::Expr>,
-    // allow_remote_imports: bool,
-    // use_cache: bool,
+    host_functions: Vec<HostFunction>,
+    limits: EvalLimits,
+    base_dir: Option<PathBuf>,
+    /// Records merged under the parsed value, in order.
+    defaults: Vec<Expr>,
+    enums: EnumRepresentation,
 }
 
 impl<'a> Deserializer<'a, NoAnnot> {
@@ -70,9 +87,13 @@ impl<'a> Deserializer<'a, NoAnnot> {
             source,
             annot: NoAnnot,
             allow_imports: true,
+            import_options: ImportOptions::default(),
             builtins: HashMap::new(),
-            // allow_remote_imports: true,
-            // use_cache: true,
+            host_functions: Vec::new(),
+            limits: EvalLimits::default(),
+            base_dir: None,
+            defaults: Vec::new(),
+            enums: EnumRepresentation::default(),
         }
     }
     fn from_str(s: &'a str) -> Self {
@@ -84,9 +105,17 @@ impl<'a> Deserializer<'a, NoAnnot> {
     fn from_binary_file<P: AsRef<Path>>(path: P) -> Self {
         Self::default_with_source(Source::BinaryFile(path.as_ref().to_owned()))
     }
-    // fn from_url(url: &'a str) -> Self {
-    //     Self::default_with_source(Source::Url(url))
-    // }
+    fn from_binary(data: &'a [u8]) -> Self {
+        Self::default_with_source(Source::Binary(data))
+    }
+    fn from_url(url: &'a str) -> Self {
+        Self::default_with_source(Source::Url(url))
+    }
+    fn from_reader<R: Read>(mut reader: R) -> Self {
+        let mut s = String::new();
+        let res = reader.read_to_string(&mut s).map(|_| s);
+        Self::default_with_source(Source::Reader(Rc::new(res)))
+    }
 
     /// Ensures that the parsed value matches the provided type.
     ///
@@ -134,7 +163,13 @@ impl<'a> Deserializer<'a, NoAnnot> {
             annot: ManualAnnot(ty),
             source: self.source,
             allow_imports: self.allow_imports,
+            import_options: self.import_options,
             builtins: self.builtins,
+            host_functions: self.host_functions,
+            limits: self.limits,
+            base_dir: self.base_dir,
+            defaults: self.defaults,
+            enums: self.enums,
         }
     }
 
@@ -185,7 +220,13 @@ impl<'a> Deserializer<'a, NoAnnot> {
             annot: StaticAnnot,
             source: self.source,
             allow_imports: self.allow_imports,
+            import_options: self.import_options,
             builtins: self.builtins,
+            host_functions: self.host_functions,
+            limits: self.limits,
+            base_dir: self.base_dir,
+            defaults: self.defaults,
+            enums: self.enums,
         }
     }
 }
@@ -219,14 +260,93 @@ impl<'a, A> Deserializer<'a, A> {
         }
     }
 
-    // /// TODO
-    // pub fn remote_imports(&mut self, imports: bool) -> &mut Self {
-    //     self.allow_remote_imports = imports;
-    //     if imports {
-    //         self.allow_imports = true;
-    //     }
-    //     self
-    // }
+    /// Sets whether to enable imports from URLs. Enabling them also enables imports in general.
+    ///
+    /// By default, remote imports are enabled. Imports protected by a hash can still be read from
+    /// the cache when they are disabled.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let data = "https://example.com/port.dhall";
+    /// assert!(
+    ///     serde_dhall::from_str(data)
+    ///         .remote_imports(false)
+    ///         .parse::<u64>()
+    ///         .is_err()
+    /// );
+    /// ```
+    pub fn remote_imports(mut self, imports: bool) -> Self {
+        self.import_options.remote = imports;
+        if imports {
+            self.allow_imports = true;
+        }
+        self
+    }
+
+    /// Sets whether to enable importing environment variables, e.g. `env:HOME`. Enabling them also
+    /// enables imports in general.
+    ///
+    /// By default, environment imports are enabled.
+    pub fn env_imports(mut self, imports: bool) -> Self {
+        self.import_options.env = imports;
+        if imports {
+            self.allow_imports = true;
+        }
+        self
+    }
+
+    /// Sets whether to use the cache of imports protected by a hash.
+    ///
+    /// By default, the cache is used. It lives in `$XDG_CACHE_HOME/dhall`, or
+    /// `$HOME/.cache/dhall` if that is not set.
+    pub fn cache(mut self, cache: bool) -> Self {
+        self.import_options.cache = cache;
+        self
+    }
+
+    /// Sets the directory of the cache of imports protected by a hash, and enables the cache.
+    pub fn cache_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.import_options.cache = true;
+        self.import_options.cache_dir = Some(dir.as_ref().to_owned());
+        self
+    }
+
+    /// Sets the value of an environment variable for `env:` imports.
+    ///
+    /// Once this is called, environment imports only see the variables set this way and no longer
+    /// read the environment of the process. This makes loading reproducible, e.g. in tests.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// let data = "env:REPLICAS + 1";
+    /// let replicas = serde_dhall::from_str(data)
+    ///     .env_var("REPLICAS", "2")
+    ///     .parse::<u64>()?;
+    /// assert_eq!(replicas, 3);
+    ///
+    /// assert!(
+    ///     serde_dhall::from_str("env:HOME as Text")
+    ///         .env_var("REPLICAS", "2")
+    ///         .parse::<String>()
+    ///         .is_err()
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn env_var(
+        mut self,
+        name: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Self {
+        self.import_options
+            .env_vars
+            .get_or_insert_with(HashMap::new)
+            .insert(name.into(), value.into());
+        self
+    }
 
     /// Makes a set of types available to the parsed dhall code. This is similar to how builtins
     /// like `Natural` work: they are provided by dhall and accessible in any file.
@@ -315,40 +435,435 @@ impl<'a, A> Deserializer<'a, A> {
         self
     }
 
-    fn _parse<T>(&self) -> dhall::error::Result<Result<Value>>
+    /// Makes a function implemented in Rust available to the parsed dhall code, under the given
+    /// name. Like the builtin functions provided by dhall, it is accessible in any file, including
+    /// imported ones.
+    ///
+    /// The function gets called once it has been applied to as many arguments as there are
+    /// `arg_types`, and those arguments are fully evaluated. It can return `None` to leave the
+    /// application unevaluated, in which case the result will likely fail to deserialize. A
+    /// returned value that doesn't have type `ret_type` also leaves the application unevaluated.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// use serde_dhall::{SimpleType, SimpleValue};
+    ///
+    /// let data = "greet \"world\"";
+    /// let greeting = serde_dhall::from_str(data)
+    ///     .with_builtin_function(
+    ///         "greet".to_string(),
+    ///         vec![SimpleType::Text],
+    ///         SimpleType::Text,
+    ///         |args| match &args[0] {
+    ///             SimpleValue::Text(name) => {
+    ///                 Some(SimpleValue::Text(format!("Hello, {}!", name)))
+    ///             }
+    ///             _ => None,
+    ///         },
+    ///     )
+    ///     .parse::<String>()?;
+    /// assert_eq!(greeting, "Hello, world!");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_builtin_function<F>(
+        mut self,
+        name: String,
+        arg_types: impl IntoIterator<Item = SimpleType>,
+        ret_type: SimpleType,
+        f: F,
+    ) -> Self
     where
-        A: TypeAnnot,
-        T: HasAnnot<A>,
+        F: Fn(&[SimpleValue]) -> Option<SimpleValue> + 'static,
     {
-        Ctxt::with_new(|cx| {
-            let parsed = match &self.source {
-                Source::Str(s) => Parsed::parse_str(s)?,
-                Source::File(p) => Parsed::parse_file(p.as_ref())?,
-                Source::BinaryFile(p) => Parsed::parse_binary_file(p.as_ref())?,
-            };
+        let ty = arg_types
+            .into_iter()
+            .collect::<Vec<_>>()
+            .into_iter()
+            .rev()
+            .fold(ret_type.to_expr(), |acc, arg| {
+                Expr::new(
+                    ExprKind::Pi("_".into(), arg.to_expr(), acc),
+                    Span::Artificial,
+                )
+            });
+        let label = Label::from_str(&name);
+        let func = HostFunction::new(label, ty, move |cx, args| {
+            let args = args
+                .iter()
+                .map(SimpleValue::from_nir)
+                .collect::<std::result::Result<Vec<_>, _>>()
+                .ok()?;
+            let hir = f(&args)?.to_hir(Some(&ret_type)).ok()?;
+            Some(hir.eval_closed_expr(cx))
+        });
+        self.host_functions.push(func);
+        self
+    }
 
-            let parsed_with_builtins =
-                self.builtins.iter().fold(parsed, |acc, (name, subst)| {
-                    acc.add_let_binding(name.clone(), subst.clone())
+    /// Makes a value available to the parsed dhall code, under the given name. Like the builtin
+    /// functions provided by dhall, it is accessible in any file, including imported ones.
+    ///
+    /// This is useful to pass runtime facts like the hostname or the environment name to the
+    /// configuration. The value is typechecked once here; parsing only checks its type.
+    ///
+    /// Fails if the value cannot be converted to Dhall without a type annotation, like an empty
+    /// list. Use [`with_builtin_expr()`] for those.
+    ///
+    /// [`with_builtin_expr()`]: Deserializer::with_builtin_expr()
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// let data = "if debug then \"${env}-debug\" else env";
+    /// let name = serde_dhall::from_str(data)
+    ///     .with_builtin_value("env".to_string(), "prod")?
+    ///     .with_builtin_value("debug".to_string(), true)?
+    ///     .parse::<String>()?;
+    /// assert_eq!(name, "prod-debug");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_builtin_value(
+        self,
+        name: String,
+        value: impl ToDhall,
+    ) -> Result<Self> {
+        let expr = value.to_dhall(None)?.to_expr();
+        self.with_builtin_binding(name, Parsed::from_expr_without_imports(expr))
+    }
+
+    /// Makes the value of a Dhall expression available to the parsed dhall code, under the given
+    /// name. Like the builtin functions provided by dhall, it is accessible in any file, including
+    /// imported ones.
+    ///
+    /// The expression is parsed and typechecked once here. It cannot contain imports.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// let data = "List/length Text replicas";
+    /// let n = serde_dhall::from_str(data)
+    ///     .with_builtin_expr("replicas".to_string(), "[] : List Text")?
+    ///     .parse::<u64>()?;
+    /// assert_eq!(n, 0);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_builtin_expr(self, name: String, expr: &str) -> Result<Self> {
+        let parsed = Parsed::parse_str(expr)
+            .map_err(ErrorKind::Dhall)
+            .map_err(Error)?;
+        self.with_builtin_binding(name, parsed)
+    }
+
+    fn with_builtin_binding(
+        mut self,
+        name: String,
+        parsed: Parsed,
+    ) -> Result<Self> {
+        let (value, ty) = Ctxt::with_new(|cx| -> dhall::error::Result<_> {
+            let typed = parsed.skip_resolve(cx)?.typecheck(cx)?;
+            let ty = typed.get_type()?.to_expr(cx);
+            Ok((typed.normalize(cx).to_expr(cx), ty))
+        })
+        .map_err(ErrorKind::Dhall)
+        .map_err(Error)?;
+        let label = Label::from_str(&name);
+        self.host_functions
+            .push(HostFunction::constant(label, ty, value));
+        Ok(self)
+    }
+
+    /// Fills in the fields that are missing from the parsed record with the given defaults, as
+    /// record completion (`Config::{ port = 8080 }`) does. This happens before typechecking, so
+    /// the parsed code only needs to set the fields that differ from the defaults.
+    ///
+    /// `defaults` is Dhall code for either a record of default values, or a schema like the ones
+    /// used with `::`, i.e. a record with `Type` and `default` fields. Like with `::`, the
+    /// defaults are merged with `//`, so nested records are not merged. If this is called several
+    /// times, later defaults take precedence.
+    ///
+    /// The expression is parsed and typechecked once here. It cannot contain imports.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// use serde::Deserialize;
+    /// use serde_dhall::StaticType;
+    ///
+    /// #[derive(Deserialize, StaticType)]
+    /// struct Config {
+    ///     host: String,
+    ///     port: u64,
+    /// }
+    ///
+    /// let config: Config = serde_dhall::from_str("{ port = 8080 }")
+    ///     .with_defaults("{ host = \"localhost\", port = 80 }")?
+    ///     .static_type_annotation()
+    ///     .parse()?;
+    /// assert_eq!(config.host, "localhost");
+    /// assert_eq!(config.port, 8080);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_defaults(mut self, defaults: &str) -> Result<Self> {
+        let parsed = Parsed::parse_str(defaults)
+            .map_err(ErrorKind::Dhall)
+            .map_err(Error)?;
+        let defaults = Ctxt::with_new(|cx| -> dhall::error::Result<_> {
+            let typed = parsed.skip_resolve(cx)?.typecheck(cx)?;
+            Ok(typed.normalize(cx).to_expr(cx))
+        })
+        .map_err(ErrorKind::Dhall)
+        .map_err(Error)?;
+        let defaults = match defaults.kind() {
+            ExprKind::RecordLit(kvs)
+                if kvs.len() == 2 && kvs.contains_key("Type") =>
+            {
+                match kvs.get("default") {
+                    Some(default) => default.clone(),
+                    None => defaults,
+                }
+            }
+            _ => defaults,
+        };
+        match defaults.kind() {
+            ExprKind::RecordLit(_) => {}
+            _ => {
+                return Err(Error(ErrorKind::Deserialize(format!(
+                    "the defaults must be a record, found: {}",
+                    defaults
+                ))))
+            }
+        }
+        self.defaults.push(defaults);
+        Ok(self)
+    }
+
+    /// Like [`with_defaults()`], with defaults given as a Rust value, typically the `Default`
+    /// value of the type being parsed.
+    ///
+    /// [`with_defaults()`]: Deserializer::with_defaults()
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// use serde::{Deserialize, Serialize};
+    /// use serde_dhall::StaticType;
+    ///
+    /// #[derive(Debug, PartialEq, Default, Deserialize, Serialize, StaticType)]
+    /// struct Config {
+    ///     name: String,
+    ///     replicas: Option<u64>,
+    /// }
+    ///
+    /// let config: Config = serde_dhall::from_str("{ name = \"app\" }")
+    ///     .with_default_value(&Config::default())?
+    ///     .parse()?;
+    /// assert_eq!(config, Config { name: "app".to_owned(), replicas: None });
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_default_value<T>(mut self, defaults: &T) -> Result<Self>
+    where
+        T: ToDhall + StaticType,
+    {
+        let defaults = defaults.to_dhall(Some(&T::static_type()))?.to_expr();
+        self.defaults.push(defaults);
+        Ok(self)
+    }
+
+    /// Chooses how unions look to enums that use `#[serde(tag = "...")]` or `#[serde(untagged)]`.
+    /// Other enums always read unions as externally tagged. See [`EnumRepresentation`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// use serde::Deserialize;
+    /// use serde_dhall::EnumRepresentation;
+    ///
+    /// #[derive(Debug, PartialEq, Deserialize)]
+    /// #[serde(untagged)]
+    /// enum Port {
+    ///     Number(u64),
+    ///     Name(String),
+    /// }
+    ///
+    /// let port: Vec<Port> = serde_dhall::from_str(
+    ///     "let P = < Number : Natural | Name : Text > in [ P.Number 80, P.Name \"http\" ]",
+    /// )
+    /// .enum_representation(EnumRepresentation::Untagged)
+    /// .parse()?;
+    /// assert_eq!(port, vec![Port::Number(80), Port::Name("http".to_owned())]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn enum_representation(self, enums: EnumRepresentation) -> Self {
+        Deserializer { enums, ..self }
+    }
+
+    /// Sets the directory relative to which imports are resolved. This is most useful with
+    /// [`from_str()`], [`from_reader()`] and [`from_binary()`], whose imports are otherwise
+    /// resolved relative to the current directory. For the other sources, this overrides the
+    /// location of the file.
+    ///
+    /// [`from_str()`]: crate::from_str()
+    /// [`from_reader()`]: crate::from_reader()
+    /// [`from_binary()`]: crate::from_binary()
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// let data = "./common.dhall // { name = \"app\" }";
+    /// let config = serde_dhall::from_str(data)
+    ///     .base_dir("/etc/app")
+    ///     .parse::<serde_dhall::SimpleValue>()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn base_dir<P: AsRef<Path>>(self, dir: P) -> Self {
+        Deserializer {
+            base_dir: Some(dir.as_ref().to_owned()),
+            ..self
+        }
+    }
+
+    /// Limits the resources that evaluating the Dhall code may use. This is useful when reading
+    /// untrusted input, since evaluation of a small Dhall expression can take arbitrarily long.
+    ///
+    /// By default, evaluation is unlimited.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use serde_dhall::EvalLimits;
+    ///
+    /// let data = "Natural/fold 1000000 Natural (\\(x: Natural) -> x + 1) 0";
+    /// let limits = EvalLimits {
+    ///     max_fold_iterations: Some(100),
+    ///     ..EvalLimits::default()
+    /// };
+    /// assert!(
+    ///     serde_dhall::from_str(data)
+    ///         .eval_limits(limits)
+    ///         .parse::<u64>()
+    ///         .is_err()
+    /// );
+    /// ```
+    pub fn eval_limits(self, limits: EvalLimits) -> Self {
+        Deserializer { limits, ..self }
+    }
+
+    fn parse_source(&self) -> dhall::error::Result<Parsed> {
+        let parsed = match &self.source {
+            Source::Str(s) => Parsed::parse_str(s)?,
+            Source::File(p) => Parsed::parse_file(p.as_ref())?,
+            Source::BinaryFile(p) => Parsed::parse_binary_file(p.as_ref())?,
+            Source::Binary(data) => Parsed::parse_binary(data)?,
+            Source::Url(url) => Parsed::parse_remote(url::Url::parse(url)?)?,
+            Source::Reader(res) => match &**res {
+                Ok(s) => Parsed::parse_str(s)?,
+                Err(e) => {
+                    return Err(
+                        std::io::Error::new(e.kind(), e.to_string()).into()
+                    )
+                }
+            },
+        };
+        Ok(match &self.base_dir {
+            Some(dir) => parsed
+                .with_location(ImportLocation::dhall_code_in_dir(dir.clone())),
+            None => parsed,
+        })
+    }
+
+    /// Evaluates the parsed value, typechecked against `annot` if given, and reads it with `read`.
+    fn eval<R>(
+        &self,
+        parsed: Parsed,
+        annot: Option<SimpleType>,
+        read: impl for<'cx> FnOnce(
+            Ctxt<'cx>,
+            &Nir<'cx>,
+            &Nir<'cx>,
+            &SharedCtxt,
+        ) -> Result<R>,
+    ) -> dhall::error::Result<Result<R>> {
+        // The context is shared so that functions can keep what they were evaluated with.
+        let shared = SharedCtxt::new(self.limits.clone());
+        shared.with(|cx| {
+            for func in &self.host_functions {
+                cx.push_host_function(func.clone())?;
+            }
+            let parsed_with_defaults =
+                self.defaults.iter().rev().fold(parsed, |acc, defaults| {
+                    acc.add_defaults(defaults.clone())
                 });
+            let parsed_with_builtins = self.builtins.iter().fold(
+                parsed_with_defaults,
+                |acc, (name, subst)| {
+                    acc.add_let_binding(name.clone(), subst.clone())
+                },
+            );
 
             let resolved = if self.allow_imports {
-                parsed_with_builtins.resolve(cx)?
+                parsed_with_builtins
+                    .resolve_with_options(cx, self.import_options.clone())?
             } else {
                 parsed_with_builtins.skip_resolve(cx)?
             };
-            let typed = match &T::get_annot(self.annot) {
-                None => resolved.typecheck(cx)?,
-                Some(ty) => resolved.typecheck_with(cx, &ty.to_hir())?,
+            let typed = match &annot {
+                None => resolved.typecheck(cx),
+                Some(ty) => resolved.typecheck_with(cx, &ty.to_hir()),
             };
-            Ok(Value::from_nir_and_ty(
+            // Typechecking evaluates types, so it may have gone over the limits too. In that case
+            // type errors are meaningless.
+            cx.check_limits()?;
+            let typed = typed?;
+            // Normalization is lazy: this only evaluates the parts of the value that are read.
+            let val = read(
                 cx,
                 typed.normalize(cx).as_nir(),
                 typed.ty().as_nir(),
-            ))
+                &shared,
+            );
+            cx.check_limits()?;
+            Ok(val)
         })
     }
 
+    /// Like `eval`, but also parses the source and reports errors.
+    fn parse_with<R>(
+        &self,
+        annot: Option<SimpleType>,
+        read: impl for<'cx> FnOnce(
+            Ctxt<'cx>,
+            &Nir<'cx>,
+            &Nir<'cx>,
+            &SharedCtxt,
+        ) -> Result<R>,
+    ) -> Result<R> {
+        let parsed = self
+            .parse_source()
+            .map_err(ErrorKind::Dhall)
+            .map_err(Error)?;
+        // Kept around to point deserialization errors to the source.
+        let expr = parsed.to_expr();
+        self.eval(parsed, annot, read)
+            .map_err(ErrorKind::Dhall)
+            .map_err(Error)?
+            .map_err(|e| e.with_source(&expr))
+    }
+
     /// Parses the chosen dhall value with the options provided.
     ///
     /// If you enabled static annotations, `T` is required to implement [`StaticType`].
@@ -370,11 +885,86 @@ impl<'a, A> Deserializer<'a, A> {
         A: TypeAnnot,
         T: FromDhall + HasAnnot<A>,
     {
-        let val = self
-            ._parse::<T>()
-            .map_err(ErrorKind::Dhall)
-            .map_err(Error)??;
-        T::from_dhall(&val)
+        self.parse_with(T::get_annot(self.annot), |cx, x, ty, shared| {
+            T::from_nir(cx, x, ty, shared, &self.enums)
+        })
+    }
+}
+
+impl<'a, A> Deserializer<'a, A>
+where
+    A: TypeAnnot,
+    serde_json::Value: HasAnnot<A>,
+{
+    /// Evaluates the chosen dhall value and converts it to JSON, the way `dhall-to-json` does.
+    ///
+    /// - records become objects, and lists of `{ mapKey, mapValue }` records with `Text` keys
+    ///   (e.g. produced by `toMap`) become objects too, with the keys in the order of the list;
+    /// - `Optional` values are unwrapped, and record fields that are `None` are omitted;
+    /// - union values are unwrapped: `< A : Natural | B >.A 1` is `1`, and alternatives without
+    ///   contents become their name as a string, e.g. `"B"`;
+    /// - values of type `Prelude.JSON.Type` become the JSON they describe.
+    ///
+    /// Functions, types and `Double`s that are not finite can't be converted. See
+    /// [`JsonOptions`] to change how some values are converted.
+    ///
+    /// This differs from `parse::<serde_json::Value>()`, which reads the value the way serde
+    /// would, e.g. with unions as externally tagged enums.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// use serde_dhall::JsonOptions;
+    ///
+    /// let data = r#"
+    ///     { env = toMap { PATH = "/bin", HOME = "/root" }
+    ///     , level = < Debug | Info >.Info
+    ///     , port = Some 8080
+    ///     , user = None Text
+    ///     }
+    /// "#;
+    /// let json = serde_dhall::from_str(data).to_json(&JsonOptions::new())?;
+    ///
+    /// assert_eq!(
+    ///     json.to_string(),
+    ///     r#"{"env":{"HOME":"/root","PATH":"/bin"},"level":"Info","port":8080}"#
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`JsonOptions`]: crate::JsonOptions
+    pub fn to_json(&self, options: &JsonOptions) -> Result<serde_json::Value> {
+        self.parse_with(
+            serde_json::Value::get_annot(self.annot),
+            |cx, x, _, _| {
+                json::expr_to_json(&x.to_expr(cx, Default::default()), options)
+            },
+        )
+    }
+
+    /// Evaluates the chosen dhall value and converts it to YAML, the way `dhall-to-yaml` does.
+    /// The value is converted as with [`to_json()`](Deserializer::to_json).
+    ///
+    /// This requires the `yaml` feature.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// use serde_dhall::JsonOptions;
+    ///
+    /// let yaml = serde_dhall::from_str("{ name = \"app\", ports = [80, 443] }")
+    ///     .to_yaml(&JsonOptions::new())?;
+    ///
+    /// assert_eq!(yaml, "name: app\nports:\n  - 80\n  - 443\n");
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "yaml")]
+    pub fn to_yaml(&self, options: &JsonOptions) -> Result<String> {
+        json::json_to_yaml(&self.to_json(options)?)
     }
 }
 
@@ -481,6 +1071,63 @@ pub fn from_binary_file<'a, P: AsRef<Path>>(
     Deserializer::from_binary_file(path)
 }
 
-// pub fn from_url(url: &str) -> Deserializer<'_, NoAnnot> {
-//     Deserializer::from_url(url)
-// }
+/// Deserialize a value from a CBOR-encoded Dhall binary buffer. See [`from_binary_file()`] for
+/// details on the format.
+///
+/// This returns a [`Deserializer`] object. Call the [`parse()`] method to get the deserialized
+/// value, or use other [`Deserializer`] methods to control the deserialization process.
+///
+/// Imports will be resolved relative to the current directory, unless another directory is set
+/// with [`base_dir()`].
+///
+/// [`parse()`]: Deserializer::parse()
+/// [`base_dir()`]: Deserializer::base_dir()
+pub fn from_binary(data: &[u8]) -> Deserializer<'_, NoAnnot> {
+    Deserializer::from_binary(data)
+}
+
+/// Deserialize a value from the Dhall file at the given URL.
+///
+/// This returns a [`Deserializer`] object. Call the [`parse()`] method to get the deserialized
+/// value, or use other [`Deserializer`] methods to control the deserialization process.
+///
+/// Imports will be resolved relative to the provided URL.
+///
+/// # Example
+///
+/// ```no_run
+/// # fn main() -> serde_dhall::Result<()> {
+/// let port: u64 = serde_dhall::from_url("https://example.com/port.dhall").parse()?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`parse()`]: Deserializer::parse()
+pub fn from_url(url: &str) -> Deserializer<'_, NoAnnot> {
+    Deserializer::from_url(url)
+}
+
+/// Deserialize a value from Dhall text read from `reader`. The reader is consumed immediately.
+///
+/// This returns a [`Deserializer`] object. Call the [`parse()`] method to get the deserialized
+/// value, or use other [`Deserializer`] methods to control the deserialization process.
+///
+/// Imports will be resolved relative to the current directory, unless another directory is set
+/// with [`base_dir()`].
+///
+/// # Example
+///
+/// ```
+/// # fn main() -> serde_dhall::Result<()> {
+/// let reader = std::io::Cursor::new("{ x = 1 }.x");
+/// let x: u64 = serde_dhall::from_reader(reader).parse()?;
+/// assert_eq!(x, 1);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`parse()`]: Deserializer::parse()
+/// [`base_dir()`]: Deserializer::base_dir()
+pub fn from_reader<'a, R: Read>(reader: R) -> Deserializer<'a, NoAnnot> {
+    Deserializer::from_reader(reader)
+}
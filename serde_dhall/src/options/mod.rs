@@ -3,6 +3,74 @@ use crate::{SimpleType, StaticType};
 pub(crate) mod de;
 pub(crate) mod ser;
 
+/// How Rust enums correspond to Dhall unions, following serde's [enum representations].
+///
+/// By default, enums are externally tagged: the variant `A { x: 1 }` corresponds to the union
+/// value `< A : { x : Natural } | B >.A { x = 1 }`. Types that use `#[serde(tag = "...")]` or
+/// `#[serde(untagged)]` don't go through this representation, and instead see unions in the
+/// shape chosen here. The union value above then looks like:
+///
+/// - `Internal { tag: "type" }`: `{ type = "A", x = 1 }`;
+/// - `Adjacent { tag: "t", content: "c" }`: `{ t = "A", c = { x = 1 } }`;
+/// - `Untagged`: `{ x = 1 }`.
+///
+/// The representation applies to the whole value. Enums that use serde's default representation
+/// keep working alongside it, except inside a tagged or untagged enum.
+///
+/// # Example
+///
+/// ```rust
+/// # fn main() -> serde_dhall::Result<()> {
+/// use serde::Deserialize;
+/// use serde_dhall::EnumRepresentation;
+///
+/// #[derive(Debug, PartialEq, Deserialize)]
+/// #[serde(tag = "type")]
+/// enum Shape {
+///     Circle { radius: u64 },
+///     Point,
+/// }
+///
+/// let data = "< Circle : { radius : Natural } | Point >.Circle { radius = 2 }";
+/// let shape: Shape = serde_dhall::from_str(data)
+///     .enum_representation(EnumRepresentation::Internal { tag: "type".to_string() })
+///     .parse()?;
+/// assert_eq!(shape, Shape::Circle { radius: 2 });
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [enum representations]: https://serde.rs/enum-representations.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EnumRepresentation {
+    /// The variant is the tag of the union. This is the default.
+    External,
+    /// The variant is stored in a field next to the fields of the union contents.
+    Internal {
+        /// The name of the field that holds the variant.
+        tag: String,
+    },
+    /// The variant and the union contents are stored in two fields.
+    Adjacent {
+        /// The name of the field that holds the variant.
+        tag: String,
+        /// The name of the field that holds the union contents.
+        content: String,
+    },
+    /// The variant is not stored: the union contents are used as is. When serializing, the value
+    /// must fit exactly one alternative of the union, since union types don't record the order
+    /// in which serde would try the variants.
+    Untagged,
+}
+
+// `#[default]` on enum variants needs a newer compiler than we support.
+#[allow(clippy::derivable_impls)]
+impl Default for EnumRepresentation {
+    fn default() -> Self {
+        EnumRepresentation::External
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct NoAnnot;
 #[derive(Debug, Clone, Copy)]
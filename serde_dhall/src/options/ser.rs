@@ -1,5 +1,15 @@
+use std::cell::RefCell;
+use std::io::Write;
+use std::path::Path;
+
+use dhall::error::Error as DhallError;
+use dhall::syntax::{binary, Expr, FieldOrder, Hash};
+use dhall::{Ctxt, Parsed};
+
 use crate::options::{HasAnnot, ManualAnnot, NoAnnot, StaticAnnot, TypeAnnot};
-use crate::{Result, SimpleType, ToDhall};
+use crate::{
+    EnumRepresentation, Error, ErrorKind, Result, SimpleType, ToDhall, Value,
+};
 
 /// Controls how a Dhall value is written.
 ///
@@ -7,13 +17,16 @@ use crate::{Result, SimpleType, ToDhall};
 /// annotations.
 ///
 /// When using [`Serializer`], you'll create it with [`serialize()`], then chain calls to methods
-/// to set each option, then call [`to_string()`]. This will give you a [`Result`] containing the
-/// input serialized to Dhall.
+/// to set each option, then call [`to_string()`] or [`to_string_pretty()`]. This will give you a
+/// [`Result`] containing the input serialized to Dhall. The value can also be written in the
+/// binary format with [`to_binary()`], or directly to a file or writer.
 ///
 /// Note that if you do not provide a type annotation, some values may not be convertible to Dhall,
 /// like empty lists or enums.
 ///
 /// [`to_string()`]: Serializer::to_string()
+/// [`to_string_pretty()`]: Serializer::to_string_pretty()
+/// [`to_binary()`]: Serializer::to_binary()
 ///
 /// # Examples
 ///
@@ -45,6 +58,8 @@ use crate::{Result, SimpleType, ToDhall};
 pub struct Serializer<'a, T, A> {
     data: &'a T,
     annot: A,
+    sort_fields: bool,
+    enums: EnumRepresentation,
 }
 
 impl<'a, T> Serializer<'a, T, NoAnnot> {
@@ -89,6 +104,8 @@ impl<'a, T> Serializer<'a, T, NoAnnot> {
         Serializer {
             annot: ManualAnnot(ty),
             data: self.data,
+            sort_fields: self.sort_fields,
+            enums: self.enums,
         }
     }
 
@@ -127,6 +144,8 @@ impl<'a, T> Serializer<'a, T, NoAnnot> {
         Serializer {
             annot: StaticAnnot,
             data: self.data,
+            sort_fields: self.sort_fields,
+            enums: self.enums,
         }
     }
 }
@@ -135,6 +154,88 @@ impl<'a, T, A> Serializer<'a, T, A>
 where
     A: TypeAnnot,
 {
+    /// Whether [`to_string_pretty()`] prints record fields in sorted order, which is the default.
+    /// If `false`, the fields of structs and maps are printed in the order in which they were
+    /// serialized, e.g. the order in which the fields of a struct are declared.
+    ///
+    /// [`to_string()`] always sorts fields.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// use serde::Serialize;
+    /// use serde_dhall::serialize;
+    ///
+    /// #[derive(Serialize)]
+    /// struct Point {
+    ///     y: u64,
+    ///     x: u64,
+    /// }
+    ///
+    /// let data = Point { y: 1, x: 0 };
+    /// let string = serialize(&data).sort_fields(false).to_string_pretty(80)?;
+    /// assert_eq!(string, "{ y = 1, x = 0 }");
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`to_string()`]: Serializer::to_string()
+    /// [`to_string_pretty()`]: Serializer::to_string_pretty()
+    pub fn sort_fields(self, sort: bool) -> Self {
+        Serializer {
+            sort_fields: sort,
+            ..self
+        }
+    }
+
+    /// Chooses how enums that use `#[serde(tag = "...")]` or `#[serde(untagged)]` are written.
+    /// With a type annotation, the records they produce are turned back into union values. See
+    /// [`EnumRepresentation`].
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// use serde::Serialize;
+    /// use serde_dhall::{serialize, EnumRepresentation, StaticType};
+    ///
+    /// #[derive(Serialize, StaticType)]
+    /// #[serde(tag = "type")]
+    /// enum Shape {
+    ///     Circle { radius: u64 },
+    ///     Point,
+    /// }
+    ///
+    /// let string = serialize(&Shape::Circle { radius: 2 })
+    ///     .static_type_annotation()
+    ///     .enum_representation(EnumRepresentation::Internal { tag: "type".to_string() })
+    ///     .to_string()?;
+    /// assert_eq!(
+    ///     string,
+    ///     "< Circle: { radius : Natural } | Point >.Circle { radius = 2 }"
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn enum_representation(self, enums: EnumRepresentation) -> Self {
+        Serializer { enums, ..self }
+    }
+
+    /// Converts the chosen value, recording the order of record fields in `field_order` if
+    /// given.
+    fn to_value(
+        &self,
+        field_order: Option<&RefCell<FieldOrder>>,
+    ) -> Result<Value>
+    where
+        T: ToDhall + HasAnnot<A>,
+    {
+        let annot = T::get_annot(self.annot);
+        self.data
+            .to_dhall_with(annot.as_ref(), field_order, &self.enums)
+    }
+
     /// Prints the chosen value with the options provided.
     ///
     /// If you enabled static annotations, `T` is required to implement [`StaticType`].
@@ -160,9 +261,168 @@ where
     where
         T: ToDhall + HasAnnot<A>,
     {
-        let val = self.data.to_dhall(T::get_annot(self.annot).as_ref())?;
+        let val = self.to_value(None)?;
         Ok(val.to_string())
     }
+
+    fn to_expr(&self) -> Result<Expr>
+    where
+        T: ToDhall + HasAnnot<A>,
+    {
+        let val = self.to_value(None)?;
+        Ok(val.to_expr())
+    }
+
+    /// Encodes the chosen value in the [binary format] of Dhall, with the options provided.
+    ///
+    /// The result can be read back with [`from_binary()`].
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// use serde_dhall::{from_binary, serialize};
+    ///
+    /// let data = vec![1u64, 2, 3];
+    /// let bytes = serialize(&data).to_binary()?;
+    /// let read: Vec<u64> = from_binary(&bytes).parse()?;
+    /// assert_eq!(read, data);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [binary format]: https://github.com/dhall-lang/dhall-lang/blob/master/standard/binary.md
+    /// [`from_binary()`]: crate::from_binary()
+    pub fn to_binary(&self) -> Result<Vec<u8>>
+    where
+        T: ToDhall + HasAnnot<A>,
+    {
+        binary::encode(&self.to_expr()?)
+            .map_err(|e| Error(ErrorKind::Dhall(e.into())))
+    }
+
+    /// Writes the chosen value as Dhall text to `writer`.
+    pub fn to_writer(&self, mut writer: impl Write) -> Result<()>
+    where
+        T: ToDhall + HasAnnot<A>,
+    {
+        let s = self.to_string()?;
+        writer.write_all(s.as_bytes()).map_err(io_error)
+    }
+
+    /// Writes the chosen value in the binary format of Dhall to `writer`.
+    pub fn to_binary_writer(&self, mut writer: impl Write) -> Result<()>
+    where
+        T: ToDhall + HasAnnot<A>,
+    {
+        let bytes = self.to_binary()?;
+        writer.write_all(&bytes).map_err(io_error)
+    }
+
+    /// Writes the chosen value as Dhall text to the file at `path`, replacing its contents.
+    pub fn to_file(&self, path: impl AsRef<Path>) -> Result<()>
+    where
+        T: ToDhall + HasAnnot<A>,
+    {
+        let s = self.to_string()?;
+        std::fs::write(path, s).map_err(io_error)
+    }
+
+    /// Writes the chosen value in the binary format of Dhall to the file at `path`, replacing its
+    /// contents. The file can be read back with [`from_binary_file()`].
+    ///
+    /// [`from_binary_file()`]: crate::from_binary_file()
+    pub fn to_binary_file(&self, path: impl AsRef<Path>) -> Result<()>
+    where
+        T: ToDhall + HasAnnot<A>,
+    {
+        let bytes = self.to_binary()?;
+        std::fs::write(path, bytes).map_err(io_error)
+    }
+
+    /// Computes the semantic hash of the chosen value, in the form `sha256:<hex digest>`.
+    ///
+    /// This is the hash that Dhall checks when an import is protected by an integrity check, so
+    /// the serialized value can be imported with e.g. `./config.dhall sha256:<hex digest>`. It
+    /// is also the name under which the value is stored in the import cache.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// use serde_dhall::serialize;
+    ///
+    /// let hash = serialize(&true).sha256()?;
+    /// assert_eq!(
+    ///     hash,
+    ///     "sha256:27abdeddfe8503496adeb623466caa47da5f63abd2bc6fa19f6cfcb73ecfed70"
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn sha256(&self) -> Result<String>
+    where
+        T: ToDhall + HasAnnot<A>,
+    {
+        let expr = self.to_expr()?;
+        // The hash is computed on the alpha-normal form, which needs the value to typecheck.
+        let hash = Ctxt::with_new(|cx| -> std::result::Result<_, DhallError> {
+            Parsed::from_expr_without_imports(expr)
+                .skip_resolve(cx)?
+                .typecheck(cx)?
+                .normalize(cx)
+                .to_expr_alpha(cx)
+                .sha256_hash()
+        })
+        .map_err(|e| Error(ErrorKind::Dhall(e)))?;
+        Ok(Hash::SHA256(hash).to_string())
+    }
+
+    /// Prints the chosen value with the options provided, breaking it across lines so that it
+    /// fits in `width` columns where possible.
+    ///
+    /// Records, lists and unions that do not fit on one line are laid out one element per line,
+    /// with leading separators in the style of `dhall format`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// use serde::Serialize;
+    /// use serde_dhall::serialize;
+    ///
+    /// #[derive(Serialize)]
+    /// struct Service {
+    ///     name: String,
+    ///     ports: Vec<u64>,
+    /// }
+    ///
+    /// let data = Service {
+    ///     name: "web".to_string(),
+    ///     ports: vec![80, 443],
+    /// };
+    /// let string = serialize(&data).to_string_pretty(30)?;
+    /// assert_eq!(string, "{ name = \"web\"\n, ports = [ 80, 443 ]\n}");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn to_string_pretty(&self, width: usize) -> Result<String>
+    where
+        T: ToDhall + HasAnnot<A>,
+    {
+        let field_order = RefCell::new(FieldOrder::sorted());
+        let val = if self.sort_fields {
+            self.to_value(None)?
+        } else {
+            self.to_value(Some(&field_order))?
+        };
+        let field_order = field_order.into_inner();
+        Ok(val.to_expr().to_string_pretty(width, &field_order))
+    }
+}
+
+fn io_error(e: std::io::Error) -> Error {
+    Error(ErrorKind::Dhall(e.into()))
 }
 
 /// Serialize a value to a string of Dhall text.
@@ -226,5 +486,7 @@ where
     Serializer {
         data,
         annot: NoAnnot,
+        sort_fields: true,
+        enums: EnumRepresentation::default(),
     }
 }
@@ -0,0 +1,164 @@
+use std::path::Path;
+
+use dhall::syntax::{Expr, ExprKind, FieldOrder, Span};
+
+use crate::{Error, ErrorKind, Result, SimpleType, StaticType, ToDhall, Value};
+
+/// The width used when rendering schemas to files.
+const FILE_WIDTH: usize = 80;
+
+/// A Dhall schema generated from a Rust type, to be shared with the people who write the Dhall
+/// side of a configuration.
+///
+/// A schema is either just a type, or, if it has a default value, a record of the form
+/// `{ Type = .., default = .. }` that can be used with Dhall's record completion syntax:
+/// `(./Config.dhall)::{ name = "app" }`.
+///
+/// This is created with [`schema()`], or with [`schema_with_default()`] for types that implement
+/// `Default`.
+///
+/// # Example
+///
+/// ```rust
+/// # fn main() -> serde_dhall::Result<()> {
+/// use serde::Serialize;
+/// use serde_dhall::StaticType;
+///
+/// #[derive(Default, Serialize, StaticType)]
+/// struct Config {
+///     name: String,
+///     replicas: Option<u64>,
+/// }
+///
+/// let ty = serde_dhall::schema::<Config>();
+/// assert_eq!(ty.to_string(), "{ name : Text, replicas : Optional Natural }");
+///
+/// let schema = serde_dhall::schema_with_default::<Config>()?;
+/// assert_eq!(
+///     schema.to_string(),
+///     "{ `Type` = { name : Text, replicas : Optional Natural }, \
+///        default = { name = \"\", replicas = None Natural } }"
+/// );
+///
+/// // The schema can be used to write partial configs.
+/// let config = format!("let Config = {} in Config::{{ name = \"app\" }}", schema);
+/// let config = serde_dhall::from_str(&config).parse::<serde_dhall::Value>()?;
+/// assert_eq!(config.to_string(), "{ name = \"app\", replicas = None Natural }");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Schema {
+    ty: SimpleType,
+    default: Option<Value>,
+}
+
+impl Schema {
+    /// Adds a default value, to be used with record completion. The type of the schema must be a
+    /// record type, and the value must have that type.
+    pub fn with_default<T>(self, default: &T) -> Result<Self>
+    where
+        T: ToDhall,
+    {
+        if !matches!(self.ty, SimpleType::Record(_)) {
+            return Err(Error(ErrorKind::Serialize(format!(
+                "only record types can have a default value, found {}",
+                self.ty
+            ))));
+        }
+        let default = default.to_dhall(Some(&self.ty))?;
+        Ok(Schema {
+            default: Some(default),
+            ..self
+        })
+    }
+
+    /// The type described by this schema.
+    pub fn ty(&self) -> &SimpleType {
+        &self.ty
+    }
+
+    fn to_expr(&self) -> Expr {
+        let ty = self.ty.to_expr();
+        match &self.default {
+            None => ty,
+            Some(default) => Expr::new(
+                ExprKind::RecordLit(
+                    vec![
+                        ("Type".into(), ty),
+                        ("default".into(), default.to_expr()),
+                    ]
+                    .into_iter()
+                    .collect(),
+                ),
+                Span::Artificial,
+            ),
+        }
+    }
+
+    /// Prints the schema, breaking it across lines so that it fits in `width` columns where
+    /// possible.
+    pub fn to_string_pretty(&self, width: usize) -> String {
+        self.to_expr()
+            .to_string_pretty(width, &FieldOrder::sorted())
+    }
+
+    /// Writes the schema to the file at `path`, replacing its contents. This is intended to be
+    /// called from a build script, to keep a `.dhall` file in sync with Rust types.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// use serde_dhall::StaticType;
+    ///
+    /// #[derive(StaticType)]
+    /// struct Config {
+    ///     name: String,
+    /// }
+    ///
+    /// serde_dhall::schema::<Config>().render_to_file("types/Config.dhall")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn render_to_file(&self, path: impl AsRef<Path>) -> Result<()> {
+        let mut s = self.to_string_pretty(FILE_WIDTH);
+        s.push('\n');
+        std::fs::write(path, s).map_err(|e| Error(ErrorKind::Dhall(e.into())))
+    }
+}
+
+impl std::fmt::Display for Schema {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.to_expr().fmt(f)
+    }
+}
+
+/// Generates the Dhall schema of a Rust type.
+///
+/// The schema is just the Dhall type of `T`. Use [`schema_with_default()`] or
+/// [`Schema::with_default()`] to get a `{ Type, default }` record for record completion instead.
+///
+/// See [`Schema`] for an example.
+pub fn schema<T>() -> Schema
+where
+    T: StaticType,
+{
+    Schema {
+        ty: T::static_type(),
+        default: None,
+    }
+}
+
+/// Generates the `{ Type, default }` schema of a Rust type, with `T::default()` as the default
+/// value.
+///
+/// This fails if `T` is not a record type, or if its default value doesn't match its static type.
+///
+/// See [`Schema`] for an example.
+pub fn schema_with_default<T>() -> Result<Schema>
+where
+    T: StaticType + ToDhall + Default,
+{
+    schema::<T>().with_default(&T::default())
+}
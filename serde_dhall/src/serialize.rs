@@ -1,10 +1,11 @@
 use serde::ser;
-use std::collections::BTreeMap;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
 
-use dhall::syntax::NumKind;
+use dhall::syntax::{FieldOrder, NumKind};
 
 use crate::value::SimpleValue;
-use crate::{Error, ErrorKind, Result, SimpleType, Value};
+use crate::{EnumRepresentation, Error, ErrorKind, Result, SimpleType, Value};
 use SimpleValue::*;
 
 pub trait Sealed {}
@@ -40,6 +41,18 @@ pub trait Sealed {}
 pub trait ToDhall: Sealed {
     #[doc(hidden)]
     fn to_dhall(&self, ty: Option<&SimpleType>) -> Result<Value>;
+    /// Like `to_dhall`, but also records the order in which record fields were produced if
+    /// `field_order` is given, and reads enums in the shape chosen by `enums`.
+    #[doc(hidden)]
+    fn to_dhall_with(
+        &self,
+        ty: Option<&SimpleType>,
+        field_order: Option<&RefCell<FieldOrder>>,
+        enums: &EnumRepresentation,
+    ) -> Result<Value> {
+        let _ = (field_order, enums);
+        self.to_dhall(ty)
+    }
 }
 
 impl<T> Sealed for T where T: ser::Serialize {}
@@ -49,25 +62,230 @@ where
     T: ser::Serialize,
 {
     fn to_dhall(&self, ty: Option<&SimpleType>) -> Result<Value> {
-        let sval: SimpleValue = self.serialize(Serializer)?;
+        let sval: SimpleValue = self.serialize(Serializer::default())?;
+        sval.into_value(ty)
+    }
+    fn to_dhall_with(
+        &self,
+        ty: Option<&SimpleType>,
+        field_order: Option<&RefCell<FieldOrder>>,
+        enums: &EnumRepresentation,
+    ) -> Result<Value> {
+        let sval: SimpleValue = self.serialize(Serializer {
+            field_order,
+            path: None,
+        })?;
+        let sval = match ty {
+            Some(ty) if *enums != EnumRepresentation::External => {
+                retag(sval, ty, enums)?
+            }
+            _ => sval,
+        };
         sval.into_value(ty)
     }
 }
 
+/// Turns the records produced by tagged or untagged enums back into union values, where `ty`
+/// expects a union. Values that don't have the expected shape are left alone, and fail to
+/// typecheck later. Untagged values that fit several alternatives are an error.
+fn retag(
+    val: SimpleValue,
+    ty: &SimpleType,
+    enums: &EnumRepresentation,
+) -> Result<SimpleValue> {
+    Ok(match (val, ty) {
+        (Union(variant, Some(x)), SimpleType::Union(kts)) => {
+            let x = match kts.get(&variant) {
+                Some(Some(t)) => retag(*x, t, enums)?,
+                _ => *x,
+            };
+            Union(variant, Some(Box::new(x)))
+        }
+        (val, SimpleType::Union(kts)) => retag_union(val, kts, enums)?,
+        (Record(m), SimpleType::Record(kts)) => Record(
+            m.into_iter()
+                .map(|(k, v)| {
+                    let v = match kts.get(&k) {
+                        Some(t) => retag(v, t, enums)?,
+                        None => v,
+                    };
+                    Ok((k, v))
+                })
+                .collect::<Result<_>>()?,
+        ),
+        (List(xs), SimpleType::List(t)) => List(
+            xs.into_iter()
+                .map(|x| retag(x, t, enums))
+                .collect::<Result<_>>()?,
+        ),
+        // Maps with `Text` keys are serialized as records.
+        (Record(m), SimpleType::List(t)) => match &**t {
+            SimpleType::Record(kts) if kts.contains_key("mapValue") => {
+                let t = kts.get("mapValue").unwrap();
+                Record(
+                    m.into_iter()
+                        .map(|(k, v)| Ok((k, retag(v, t, enums)?)))
+                        .collect::<Result<_>>()?,
+                )
+            }
+            _ => Record(m),
+        },
+        (Optional(Some(x)), SimpleType::Optional(t)) => {
+            Optional(Some(Box::new(retag(*x, t, enums)?)))
+        }
+        (val, _) => val,
+    })
+}
+
+fn retag_union(
+    val: SimpleValue,
+    kts: &std::collections::HashMap<String, Option<SimpleType>>,
+    enums: &EnumRepresentation,
+) -> Result<SimpleValue> {
+    // The variant named by the `tag` field of a record, with the remaining fields. Adjacently
+    // tagged enums serialize the tag as a unit variant.
+    let split_tag = |m: &BTreeMap<String, SimpleValue>, tag: &str| {
+        let mut m = m.clone();
+        match m.remove(tag) {
+            Some(Text(variant)) | Some(Union(variant, None))
+                if kts.contains_key(&variant) =>
+            {
+                Some((variant, m))
+            }
+            _ => None,
+        }
+    };
+    let retagged = match (&val, enums) {
+        (Record(m), EnumRepresentation::Internal { tag }) => {
+            match split_tag(m, tag) {
+                Some((variant, m)) => match kts.get(&variant).unwrap() {
+                    None if m.is_empty() => Some(Union(variant, None)),
+                    None => None,
+                    Some(t) => {
+                        let x = retag(Record(m), t, enums)?;
+                        Some(Union(variant, Some(Box::new(x))))
+                    }
+                },
+                None => None,
+            }
+        }
+        (Record(m), EnumRepresentation::Adjacent { tag, content }) => {
+            match split_tag(m, tag) {
+                Some((variant, mut m)) => {
+                    let x = m.remove(content);
+                    match (kts.get(&variant).unwrap(), x) {
+                        _ if !m.is_empty() => None,
+                        (None, None) => Some(Union(variant, None)),
+                        (Some(t), Some(x)) => {
+                            let x = retag(x, t, enums)?;
+                            Some(Union(variant, Some(Box::new(x))))
+                        }
+                        _ => None,
+                    }
+                }
+                None => None,
+            }
+        }
+        // Without a tag, the value must fit exactly one alternative. Serde would pick the first
+        // variant in declaration order, but union types don't record it.
+        (_, EnumRepresentation::Untagged) => {
+            let mut fits = Vec::new();
+            for (variant, t) in kts {
+                match t {
+                    None if val == Record(Default::default()) => {
+                        fits.push(Union(variant.clone(), None))
+                    }
+                    None => {}
+                    Some(t) => {
+                        let x = retag(val.clone(), t, enums)?;
+                        if x.to_hir(Some(t)).is_ok() {
+                            fits.push(Union(variant.clone(), Some(Box::new(x))))
+                        }
+                    }
+                }
+            }
+            if fits.len() > 1 {
+                let mut variants: Vec<_> = fits
+                    .iter()
+                    .map(|x| match x {
+                        Union(variant, _) => format!("`{}`", variant),
+                        _ => unreachable!(),
+                    })
+                    .collect();
+                variants.sort();
+                return Err(Error(ErrorKind::Serialize(format!(
+                    "this value of an untagged enum fits several alternatives: {}",
+                    variants.join(", ")
+                ))));
+            }
+            fits.pop()
+        }
+        _ => None,
+    };
+    Ok(retagged.unwrap_or(val))
+}
+
 #[derive(Default, Clone, Copy)]
-struct Serializer;
+struct Serializer<'o> {
+    /// If set, the order of the fields of each serialized struct or map is recorded there.
+    field_order: Option<&'o RefCell<FieldOrder>>,
+    /// The record field the value being serialized is found in, if any.
+    path: Option<&'o FieldPath<'o>>,
+}
 
-impl ser::Serializer for Serializer {
+/// The fields that lead to a value, from the innermost one out.
+struct FieldPath<'o> {
+    field: &'o str,
+    parent: Option<&'o FieldPath<'o>>,
+}
+
+impl<'o> Serializer<'o> {
+    fn record_field_order(self, fields: &[String]) {
+        if let Some(field_order) = self.field_order {
+            let mut path = Vec::new();
+            let mut node = self.path;
+            while let Some(n) = node {
+                path.push(n.field.into());
+                node = n.parent;
+            }
+            path.reverse();
+            field_order
+                .borrow_mut()
+                .prefer(&path, fields.iter().map(|f| f.as_str().into()));
+        }
+    }
+
+    /// Serializes a value that will end up in the given record field.
+    fn serialize_in_field<T>(
+        self,
+        field: &str,
+        value: &T,
+    ) -> Result<SimpleValue>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        let path = FieldPath {
+            field,
+            parent: self.path,
+        };
+        value.serialize(Serializer {
+            field_order: self.field_order,
+            path: Some(&path),
+        })
+    }
+}
+
+impl<'o> ser::Serializer for Serializer<'o> {
     type Ok = SimpleValue;
     type Error = Error;
 
-    type SerializeSeq = SeqSerializer;
-    type SerializeTuple = TupleSerializer;
-    type SerializeTupleStruct = ser::Impossible<Self::Ok, Self::Error>;
-    type SerializeTupleVariant = ser::Impossible<Self::Ok, Self::Error>;
-    type SerializeMap = MapSerializer;
-    type SerializeStruct = StructSerializer;
-    type SerializeStructVariant = ser::Impossible<Self::Ok, Self::Error>;
+    type SerializeSeq = SeqSerializer<'o>;
+    type SerializeTuple = TupleSerializer<'o>;
+    type SerializeTupleStruct = TupleSerializer<'o>;
+    type SerializeTupleVariant = VariantSerializer<TupleSerializer<'o>>;
+    type SerializeMap = MapSerializer<'o>;
+    type SerializeStruct = StructSerializer<'o>;
+    type SerializeStructVariant = VariantSerializer<StructSerializer<'o>>;
 
     fn serialize_bool(self, v: bool) -> Result<Self::Ok> {
         Ok(Num(NumKind::Bool(v)))
@@ -113,11 +331,12 @@ impl ser::Serializer for Serializer {
         Ok(Text(v.to_owned()))
     }
 
-    fn serialize_bytes(self, _v: &[u8]) -> Result<Self::Ok> {
-        Err(ErrorKind::Serialize(
-            "Unsupported data for serialization: byte array".to_owned(),
-        )
-        .into())
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok> {
+        Ok(List(
+            v.iter()
+                .map(|b| Num(NumKind::Natural(u64::from(*b))))
+                .collect(),
+        ))
     }
 
     fn serialize_none(self) -> Result<Self::Ok> {
@@ -140,22 +359,23 @@ impl ser::Serializer for Serializer {
     fn serialize_newtype_struct<T>(
         self,
         _name: &'static str,
-        _value: &T,
+        value: &T,
     ) -> Result<Self::Ok>
     where
         T: ?Sized + ser::Serialize,
     {
-        Err(ErrorKind::Serialize(
-            "Unsupported data for serialization: newtype struct".to_owned(),
-        )
-        .into())
+        value.serialize(self)
     }
     fn serialize_struct(
         self,
         _name: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeStruct> {
-        Ok(StructSerializer::default())
+        Ok(StructSerializer {
+            ser: self,
+            map: Default::default(),
+            order: Vec::new(),
+        })
     }
 
     fn serialize_unit_variant(
@@ -183,54 +403,53 @@ impl ser::Serializer for Serializer {
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
-        _len: usize,
+        variant: &'static str,
+        len: usize,
     ) -> Result<Self::SerializeTupleVariant> {
-        Err(ErrorKind::Serialize(
-            "Unsupported data for serialization: tuple variant".to_owned(),
-        )
-        .into())
+        Ok(VariantSerializer(variant, self.serialize_tuple(len)?))
     }
     fn serialize_struct_variant(
         self,
-        _name: &'static str,
+        name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
-        _len: usize,
+        variant: &'static str,
+        len: usize,
     ) -> Result<Self::SerializeStructVariant> {
-        Err(ErrorKind::Serialize(
-            "Unsupported data for serialization: struct variant".to_owned(),
-        )
-        .into())
+        Ok(VariantSerializer(
+            variant,
+            self.serialize_struct(name, len)?,
+        ))
     }
 
     fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
-        Ok(TupleSerializer::default())
+        Ok(TupleSerializer(self, Vec::new()))
     }
     fn serialize_tuple_struct(
         self,
         _name: &'static str,
-        _len: usize,
+        len: usize,
     ) -> Result<Self::SerializeTupleStruct> {
-        Err(ErrorKind::Serialize(
-            "Unsupported data for serialization: tuple struct".to_owned(),
-        )
-        .into())
+        self.serialize_tuple(len)
     }
 
     fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
-        Ok(SeqSerializer::default())
+        Ok(SeqSerializer(self, Vec::new()))
     }
 
     fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
-        Ok(MapSerializer::default())
+        Ok(MapSerializer {
+            ser: self,
+            entries: Vec::new(),
+            index: HashMap::new(),
+            key: None,
+            val: None,
+        })
     }
 }
 
-#[derive(Default)]
-struct SeqSerializer(Vec<SimpleValue>);
+struct SeqSerializer<'o>(Serializer<'o>, Vec<SimpleValue>);
 
-impl ser::SerializeSeq for SeqSerializer {
+impl<'o> ser::SerializeSeq for SeqSerializer<'o> {
     type Ok = SimpleValue;
     type Error = Error;
 
@@ -238,19 +457,18 @@ impl ser::SerializeSeq for SeqSerializer {
     where
         T: ?Sized + ser::Serialize,
     {
-        self.0.push(value.serialize(Serializer)?);
+        self.1.push(value.serialize(self.0)?);
         Ok(())
     }
 
     fn end(self) -> Result<Self::Ok> {
-        Ok(List(self.0))
+        Ok(List(self.1))
     }
 }
 
-#[derive(Default)]
-struct TupleSerializer(Vec<SimpleValue>);
+struct TupleSerializer<'o>(Serializer<'o>, Vec<SimpleValue>);
 
-impl ser::SerializeTuple for TupleSerializer {
+impl<'o> ser::SerializeTuple for TupleSerializer<'o> {
     type Ok = SimpleValue;
     type Error = Error;
 
@@ -258,29 +476,73 @@ impl ser::SerializeTuple for TupleSerializer {
     where
         T: ?Sized + ser::Serialize,
     {
-        self.0.push(value.serialize(Serializer)?);
+        let field = format!("_{}", self.1.len() + 1);
+        self.1.push(self.0.serialize_in_field(&field, value)?);
         Ok(())
     }
 
     fn end(self) -> Result<Self::Ok> {
-        Ok(Record(
-            self.0
-                .into_iter()
-                .enumerate()
-                .map(|(i, x)| (format!("_{}", i + 1), x))
-                .collect(),
-        ))
+        let fields: Vec<_> =
+            (1..=self.1.len()).map(|i| format!("_{}", i)).collect();
+        self.0.record_field_order(&fields);
+        Ok(Record(fields.into_iter().zip(self.1).collect()))
     }
 }
 
-#[derive(Default)]
-struct MapSerializer {
-    map: BTreeMap<String, SimpleValue>,
-    key: Option<String>,
+impl<'o> ser::SerializeTupleStruct for TupleSerializer<'o> {
+    type Ok = SimpleValue;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        ser::SerializeTuple::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        ser::SerializeTuple::end(self)
+    }
+}
+
+struct MapSerializer<'o> {
+    ser: Serializer<'o>,
+    entries: Vec<(SimpleValue, SimpleValue)>,
+    /// The position of each key in `entries`.
+    index: HashMap<SimpleValue, usize>,
+    key: Option<SimpleValue>,
     val: Option<SimpleValue>,
 }
 
-impl ser::SerializeMap for MapSerializer {
+impl<'o> MapSerializer<'o> {
+    fn insert(&mut self, key: SimpleValue, val: SimpleValue) {
+        match self.index.get(&key) {
+            Some(&i) => self.entries[i].1 = val,
+            None => {
+                self.index.insert(key.clone(), self.entries.len());
+                self.entries.push((key, val));
+            }
+        }
+    }
+}
+
+// Orders the keys of a `toMap`-style list, so that the output doesn't depend on the iteration
+// order of the map. Keys that can't be compared keep their relative order.
+fn cmp_map_keys(x: &SimpleValue, y: &SimpleValue) -> std::cmp::Ordering {
+    use std::cmp::Ordering;
+    match (x, y) {
+        (Num(NumKind::Bool(x)), Num(NumKind::Bool(y))) => x.cmp(y),
+        (Num(NumKind::Natural(x)), Num(NumKind::Natural(y))) => x.cmp(y),
+        (Num(NumKind::Integer(x)), Num(NumKind::Integer(y))) => x.cmp(y),
+        (Num(NumKind::Double(x)), Num(NumKind::Double(y))) => f64::from(*x)
+            .partial_cmp(&f64::from(*y))
+            .unwrap_or(Ordering::Equal),
+        (Union(x, None), Union(y, None)) => x.cmp(y),
+        _ => Ordering::Equal,
+    }
+}
+
+impl<'o> ser::SerializeMap for MapSerializer<'o> {
     type Ok = SimpleValue;
     type Error = Error;
 
@@ -288,12 +550,9 @@ impl ser::SerializeMap for MapSerializer {
     where
         T: ?Sized + ser::Serialize,
     {
-        let key = match key.serialize(Serializer)? {
-            Text(key) => key,
-            _ => return Err(<Error as ser::Error>::custom("not a string")),
-        };
+        let key: SimpleValue = key.serialize(self.ser)?;
         if let Some(val) = self.val.take() {
-            self.map.insert(key, val);
+            self.insert(key, val);
         } else {
             self.key = Some(key);
         }
@@ -304,24 +563,99 @@ impl ser::SerializeMap for MapSerializer {
     where
         T: ?Sized + ser::Serialize,
     {
-        let val: SimpleValue = val.serialize(Serializer)?;
+        // Values of `Text` keys become record fields, and the others become `mapValue` fields.
+        let val: SimpleValue = match &self.key {
+            Some(Text(key)) => self.ser.serialize_in_field(key, val)?,
+            Some(_) => self.ser.serialize_in_field("mapValue", val)?,
+            None => val.serialize(self.ser)?,
+        };
         if let Some(key) = self.key.take() {
-            self.map.insert(key, val);
+            self.insert(key, val);
         } else {
             self.val = Some(val);
         }
         Ok(())
     }
 
+    fn end(mut self) -> Result<Self::Ok> {
+        // Maps with `Text` keys become records, and other maps become lists of
+        // `{ mapKey, mapValue }` entries like the ones produced by `toMap`.
+        if self.entries.iter().all(|(k, _)| matches!(k, Text(_))) {
+            let mut order = Vec::new();
+            let mut map = BTreeMap::new();
+            for (k, v) in self.entries {
+                if let Text(k) = k {
+                    order.push(k.clone());
+                    map.insert(k, v);
+                }
+            }
+            self.ser.record_field_order(&order);
+            return Ok(Record(map));
+        }
+        self.entries.sort_by(|(x, _), (y, _)| cmp_map_keys(x, y));
+        let entries = self
+            .entries
+            .into_iter()
+            .map(|(k, v)| {
+                let mut entry = BTreeMap::new();
+                entry.insert("mapKey".to_owned(), k);
+                entry.insert("mapValue".to_owned(), v);
+                Record(entry)
+            })
+            .collect();
+        Ok(List(entries))
+    }
+}
+
+struct StructSerializer<'o> {
+    ser: Serializer<'o>,
+    map: BTreeMap<String, SimpleValue>,
+    order: Vec<String>,
+}
+
+impl<'o> ser::SerializeStruct for StructSerializer<'o> {
+    type Ok = SimpleValue;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, val: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        let val: SimpleValue = self.ser.serialize_in_field(key, val)?;
+        self.map.insert(key.into(), val);
+        self.order.push(key.into());
+        Ok(())
+    }
+
     fn end(self) -> Result<Self::Ok> {
+        self.ser.record_field_order(&self.order);
         Ok(Record(self.map))
     }
 }
 
-#[derive(Default)]
-struct StructSerializer(BTreeMap<String, SimpleValue>);
+/// Serializes the payload of a tuple or struct variant as a record, then wraps it in the variant.
+struct VariantSerializer<S>(&'static str, S);
 
-impl ser::SerializeStruct for StructSerializer {
+impl<'o> ser::SerializeTupleVariant for VariantSerializer<TupleSerializer<'o>> {
+    type Ok = SimpleValue;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        ser::SerializeTuple::serialize_element(&mut self.1, value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        let val = ser::SerializeTuple::end(self.1)?;
+        Ok(Union(self.0.to_owned(), Some(Box::new(val))))
+    }
+}
+
+impl<'o> ser::SerializeStructVariant
+    for VariantSerializer<StructSerializer<'o>>
+{
     type Ok = SimpleValue;
     type Error = Error;
 
@@ -329,13 +663,12 @@ impl ser::SerializeStruct for StructSerializer {
     where
         T: ?Sized + ser::Serialize,
     {
-        let val: SimpleValue = val.serialize(Serializer)?;
-        self.0.insert(key.into(), val);
-        Ok(())
+        ser::SerializeStruct::serialize_field(&mut self.1, key, val)
     }
 
     fn end(self) -> Result<Self::Ok> {
-        Ok(Record(self.0))
+        let val = ser::SerializeStruct::end(self.1)?;
+        Ok(Union(self.0.to_owned(), Some(Box::new(val))))
     }
 }
 
@@ -1,3 +1,17 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::net::{
+    IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6,
+};
+use std::num::{
+    NonZeroI16, NonZeroI32, NonZeroI64, NonZeroI8, NonZeroIsize, NonZeroU16,
+    NonZeroU32, NonZeroU64, NonZeroU8, NonZeroUsize,
+};
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
+
 use crate::SimpleType;
 
 /// A Rust type that can be represented as a Dhall type.
@@ -5,10 +19,25 @@ use crate::SimpleType;
 /// A typical example is `Option<bool>`, represented by the Dhall expression `Optional Bool`.
 ///
 /// This trait can be automatically derived, and this is the recommended way of implementing it.
+/// The derive follows serde's `rename`, `rename_all`, `skip`, `flatten` and `transparent`
+/// attributes, so that the type matches how serde reads and writes the value. The same attributes
+/// can be given as `#[dhall(...)]`, which takes precedence over `#[serde(...)]`.
+///
+/// Dhall records have no optional fields, so `#[serde(default)]` does not change the type: the
+/// field must still be present in the Dhall value for it to typecheck. Use
+/// [`Deserializer::with_defaults()`] to fill in missing fields instead; `#[dhall(default)]` is
+/// rejected.
+///
+/// [`Deserializer::with_defaults()`]: crate::Deserializer::with_defaults()
 ///
-/// Some Rust types cannot implement this trait, because there isn't a single Dhall type that
-/// corresponds to them. For example, `HashMap<String, u64>` could correspond to multiple different
-/// Dhall types, e.g. `{ foo: Natural, bar: Natural }` and `{ baz: Natural }`.
+/// Maps are represented as `List { mapKey : K, mapValue : V }`, the type produced by `toMap` when
+/// `K` is `Text`, since a record type would have to list the keys in advance. Keys can also be
+/// e.g. numbers or enums; enum keys can be read from either unions or `Text`.
+///
+/// Types from some other crates implement this trait when the corresponding feature is enabled:
+/// `chrono`, `semver`, `time` and `uuid`. `url::Url` always implements it. Those types are all
+/// represented as `Text`, which is how they are serialized by serde; for `time`, this enables its
+/// `serde-human-readable` feature.
 ///
 /// See also [the table of type correspondances].
 ///
@@ -43,28 +72,47 @@ pub trait StaticType {
     /// use serde::Deserialize;
     /// use serde_dhall::{SimpleType, StaticType};
     ///
-    /// // Using `derive(StaticType)` here would give it the type `{ _1: List Natural }`.
+    /// // Using `derive(StaticType)` here would give it the type `{ degrees: Double }`, but serde
+    /// // reads it from a plain number.
     /// #[derive(Deserialize)]
-    /// #[serde(transparent)]
-    /// struct Foo(Vec<u64>);
+    /// #[serde(from = "f64")]
+    /// struct Celsius {
+    ///     degrees: f64,
+    /// }
     ///
-    /// impl StaticType for Foo {
+    /// impl From<f64> for Celsius {
+    ///     fn from(degrees: f64) -> Self {
+    ///         Celsius { degrees }
+    ///     }
+    /// }
+    ///
+    /// impl StaticType for Celsius {
     ///     fn static_type() -> SimpleType {
-    ///         SimpleType::List(Box::new(SimpleType::Natural))
+    ///         SimpleType::Double
     ///     }
     /// }
     ///
-    /// let foo = serde_dhall::from_str("[ 1, 2 ]")
+    /// let temp = serde_dhall::from_str("21.5")
     ///     .static_type_annotation()
-    ///     .parse::<Foo>()?;
+    ///     .parse::<Celsius>()?;
     ///
-    /// assert_eq!(foo.0, vec![1, 2]);
+    /// assert_eq!(temp.degrees, 21.5);
     /// # Ok(())
     /// # }
     /// ```
     fn static_type() -> SimpleType;
 }
 
+/// A Rust type that is represented as a Dhall record type, and can therefore be flattened into
+/// another record with `#[serde(flatten)]`.
+///
+/// `#[derive(StaticType)]` implements this trait for structs that are not newtypes or
+/// transparent.
+pub trait RecordStaticType: StaticType {
+    /// Return the fields of the record type that represents this type.
+    fn record_fields() -> HashMap<String, SimpleType>;
+}
+
 macro_rules! derive_builtin {
     ($rust_ty:ty, $dhall_ty:ident) => {
         impl StaticType for $rust_ty {
@@ -79,13 +127,82 @@ derive_builtin!(bool, Bool);
 derive_builtin!(usize, Natural);
 derive_builtin!(u64, Natural);
 derive_builtin!(u32, Natural);
+derive_builtin!(u16, Natural);
+derive_builtin!(u8, Natural);
+derive_builtin!(NonZeroUsize, Natural);
+derive_builtin!(NonZeroU64, Natural);
+derive_builtin!(NonZeroU32, Natural);
+derive_builtin!(NonZeroU16, Natural);
+derive_builtin!(NonZeroU8, Natural);
 derive_builtin!(isize, Integer);
 derive_builtin!(i64, Integer);
 derive_builtin!(i32, Integer);
+derive_builtin!(i16, Integer);
+derive_builtin!(i8, Integer);
+derive_builtin!(NonZeroIsize, Integer);
+derive_builtin!(NonZeroI64, Integer);
+derive_builtin!(NonZeroI32, Integer);
+derive_builtin!(NonZeroI16, Integer);
+derive_builtin!(NonZeroI8, Integer);
 derive_builtin!(f64, Double);
 derive_builtin!(f32, Double);
 derive_builtin!(String, Text);
-derive_builtin!(&str, Text);
+derive_builtin!(char, Text);
+derive_builtin!(PathBuf, Text);
+derive_builtin!(IpAddr, Text);
+derive_builtin!(Ipv4Addr, Text);
+derive_builtin!(Ipv6Addr, Text);
+derive_builtin!(SocketAddr, Text);
+derive_builtin!(SocketAddrV4, Text);
+derive_builtin!(SocketAddrV6, Text);
+derive_builtin!(url::Url, Text);
+
+#[cfg(feature = "chrono")]
+mod chrono_impls {
+    use super::*;
+    use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
+
+    derive_builtin!(NaiveDate, Text);
+    derive_builtin!(NaiveTime, Text);
+    derive_builtin!(NaiveDateTime, Text);
+
+    impl<Tz: TimeZone> StaticType for DateTime<Tz> {
+        fn static_type() -> SimpleType {
+            SimpleType::Text
+        }
+    }
+}
+
+#[cfg(feature = "time")]
+mod time_impls {
+    use super::*;
+
+    derive_builtin!(time::Date, Text);
+    derive_builtin!(time::Time, Text);
+    derive_builtin!(time::PrimitiveDateTime, Text);
+    derive_builtin!(time::OffsetDateTime, Text);
+}
+
+#[cfg(feature = "semver")]
+derive_builtin!(semver::Version, Text);
+#[cfg(feature = "semver")]
+derive_builtin!(semver::VersionReq, Text);
+#[cfg(feature = "uuid")]
+derive_builtin!(uuid::Uuid, Text);
+
+// serde represents a `Duration` as a struct.
+impl StaticType for Duration {
+    fn static_type() -> SimpleType {
+        SimpleType::Record(
+            vec![
+                ("secs".to_owned(), SimpleType::Natural),
+                ("nanos".to_owned(), SimpleType::Natural),
+            ]
+            .into_iter()
+            .collect(),
+        )
+    }
+}
 
 impl StaticType for () {
     fn static_type() -> SimpleType {
@@ -199,10 +316,230 @@ where
 }
 
 impl<'a, T> StaticType for &'a T
+where
+    T: StaticType + ?Sized,
+{
+    fn static_type() -> SimpleType {
+        T::static_type()
+    }
+}
+
+impl<T, S> StaticType for HashSet<T, S>
+where
+    T: StaticType,
+{
+    fn static_type() -> SimpleType {
+        SimpleType::List(Box::new(T::static_type()))
+    }
+}
+
+impl<T> StaticType for BTreeSet<T>
 where
     T: StaticType,
+{
+    fn static_type() -> SimpleType {
+        SimpleType::List(Box::new(T::static_type()))
+    }
+}
+
+/// The type of a list of map entries with keys of type `k` and values of type `v`, like the ones
+/// produced by `toMap`.
+fn map_type(k: SimpleType, v: SimpleType) -> SimpleType {
+    SimpleType::List(Box::new(SimpleType::Record(
+        vec![("mapKey".to_owned(), k), ("mapValue".to_owned(), v)]
+            .into_iter()
+            .collect(),
+    )))
+}
+
+impl<K, V, S> StaticType for HashMap<K, V, S>
+where
+    K: StaticType,
+    V: StaticType,
+{
+    fn static_type() -> SimpleType {
+        map_type(K::static_type(), V::static_type())
+    }
+}
+
+impl<K, V> StaticType for BTreeMap<K, V>
+where
+    K: StaticType,
+    V: StaticType,
+{
+    fn static_type() -> SimpleType {
+        map_type(K::static_type(), V::static_type())
+    }
+}
+
+macro_rules! derive_transparent {
+    ($($rust_ty:ident),*) => {
+        $(
+            impl<T> StaticType for $rust_ty<T>
+            where
+                T: StaticType + ?Sized,
+            {
+                fn static_type() -> SimpleType {
+                    T::static_type()
+                }
+            }
+        )*
+    };
+}
+
+derive_transparent!(Box, Rc, Arc);
+
+impl<'a, T> StaticType for Cow<'a, T>
+where
+    T: StaticType + ToOwned + ?Sized,
 {
     fn static_type() -> SimpleType {
         T::static_type()
     }
 }
+
+impl StaticType for str {
+    fn static_type() -> SimpleType {
+        SimpleType::Text
+    }
+}
+
+impl<T> StaticType for [T]
+where
+    T: StaticType,
+{
+    fn static_type() -> SimpleType {
+        SimpleType::List(Box::new(T::static_type()))
+    }
+}
+
+macro_rules! derive_array {
+    ($($len:literal)*) => {
+        $(
+            impl<T> StaticType for [T; $len]
+            where
+                T: StaticType,
+            {
+                fn static_type() -> SimpleType {
+                    SimpleType::List(Box::new(T::static_type()))
+                }
+            }
+        )*
+    };
+}
+
+derive_array!(
+    0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16
+    17 18 19 20 21 22 23 24 25 26 27 28 29 30 31 32
+);
+
+/// The derive fails to compile for types that have no Dhall type.
+///
+/// A `PhantomData` field must be skipped:
+///
+/// ```compile_fail
+/// use serde_dhall::StaticType;
+///
+/// #[derive(StaticType)]
+/// struct Tagged<T> {
+///     value: u64,
+///     tag: std::marker::PhantomData<T>,
+/// }
+/// ```
+///
+/// ```
+/// use serde_dhall::StaticType;
+///
+/// #[derive(StaticType)]
+/// struct Tagged<T> {
+///     value: u64,
+///     #[dhall(skip)]
+///     tag: std::marker::PhantomData<T>,
+/// }
+/// ```
+///
+/// Every field must have a type that implements `StaticType`:
+///
+/// ```compile_fail
+/// use serde_dhall::StaticType;
+///
+/// struct Opaque;
+///
+/// #[derive(StaticType)]
+/// struct Wrapper {
+///     inner: Opaque,
+/// }
+/// ```
+///
+/// A transparent struct must have exactly one field:
+///
+/// ```compile_fail
+/// use serde_dhall::StaticType;
+///
+/// #[derive(StaticType)]
+/// #[dhall(transparent)]
+/// struct Pair {
+///     x: u64,
+///     y: u64,
+/// }
+/// ```
+///
+/// A flattened field must have a record type:
+///
+/// ```compile_fail
+/// use serde_dhall::StaticType;
+///
+/// #[derive(StaticType)]
+/// struct Extra {
+///     name: String,
+///     #[dhall(flatten)]
+///     rest: std::collections::HashMap<String, String>,
+/// }
+/// ```
+///
+/// `default` can't change the type:
+///
+/// ```compile_fail
+/// use serde_dhall::StaticType;
+///
+/// #[derive(StaticType)]
+/// struct Config {
+///     #[dhall(default)]
+///     port: u64,
+/// }
+/// ```
+///
+/// A flattened field must have a type that is known to be a record type, even if it is generic:
+///
+/// ```compile_fail
+/// use serde_dhall::StaticType;
+///
+/// #[derive(StaticType)]
+/// enum Mode {
+///     Fast,
+///     Slow,
+/// }
+///
+/// #[derive(StaticType)]
+/// struct Extra<T> {
+///     name: String,
+///     #[dhall(flatten)]
+///     rest: T,
+/// }
+///
+/// let _ = Extra::<Mode>::static_type();
+/// ```
+///
+/// Rust unions are not supported:
+///
+/// ```compile_fail
+/// use serde_dhall::StaticType;
+///
+/// #[derive(StaticType)]
+/// union Bits {
+///     int: u64,
+///     float: f64,
+/// }
+/// ```
+#[cfg(doctest)]
+pub struct DeriveErrors;
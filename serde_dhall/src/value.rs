@@ -4,21 +4,50 @@ use std::result::Result as StdResult;
 use dhall::builtins::Builtin;
 use dhall::operations::OpKind;
 use dhall::semantics::{Hir, HirKind, Nir, NirKind};
-pub use dhall::syntax::NumKind;
+pub use dhall::syntax::{ColorChoice, Diff, NumKind};
 use dhall::syntax::{Expr, ExprKind, Span};
-use dhall::Ctxt;
+use dhall::{Ctxt, SharedCtxt};
 
-use crate::{Error, ErrorKind, FromDhall, Result, ToDhall};
+use crate::{Error, ErrorKind, FromDhall, Function, Result, ToDhall};
 
 #[derive(Debug, Clone)]
 enum ValueKind {
     /// Invariant: the value must be printable with the given type.
     Val(SimpleValue, Option<SimpleType>),
     Ty(SimpleType),
+    Function(Function),
 }
 
-#[doc(hidden)]
-/// An arbitrary Dhall value.
+/// An arbitrary Dhall value, along with its type when it is known.
+///
+/// A `Value` can be a simple value (like [`SimpleValue`]), a type (like [`SimpleType`]) or a
+/// [`Function`]. It can be inspected without knowing its shape in advance, using the `as_*`
+/// accessors, [`get()`](Value::get) and [`pointer()`](Value::pointer). Displaying a value prints
+/// a valid Dhall expression.
+///
+/// # Example
+///
+/// ```rust
+/// # fn main() -> serde_dhall::Result<()> {
+/// use serde_dhall::{SimpleType, Value};
+///
+/// let config: Value = serde_dhall::from_str(
+///     "{ db = { hosts = [\"a\", \"b\"], port = Some 5432 }, mode = < Dev | Prod >.Prod }",
+/// )
+/// .parse()?;
+///
+/// let db = config.get("db").unwrap();
+/// assert_eq!(db.get("port").unwrap().as_optional().unwrap().unwrap().as_natural(), Some(5432));
+/// assert_eq!(config.pointer(".db.hosts[1]").unwrap().as_text(), Some("b"));
+/// assert_eq!(config.pointer(".db.port").unwrap().to_string(), "Some 5432");
+/// assert_eq!(config.get("mode").unwrap().as_union().unwrap().0, "Prod");
+///
+/// let hosts = db.get("hosts").unwrap();
+/// assert_eq!(hosts.ty(), Some(&SimpleType::List(Box::new(SimpleType::Text))));
+/// assert_eq!(hosts.to_string(), "[\"a\", \"b\"]");
+/// # Ok(())
+/// # }
+/// ```
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Value {
     kind: ValueKind,
@@ -97,7 +126,7 @@ pub struct Value {
 /// [`from_str()`]: crate::from_str()
 /// [`from_file()`]: crate::from_file()
 /// [`from_simple_value()`]: crate::from_simple_value()
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum SimpleValue {
     /// Numbers and booleans - `True`, `1`, `+2`, `3.24`
     Num(NumKind),
@@ -107,7 +136,9 @@ pub enum SimpleValue {
     Optional(Option<Box<SimpleValue>>),
     /// A list of values - `[a, b, c, d, e]`
     List(Vec<SimpleValue>),
-    /// A record value - `{ k1 = v1, k2 = v2 }`
+    /// A record value - `{ k1 = v1, k2 = v2 }`. Maps with `Text` keys, i.e. lists of
+    /// `{ mapKey, mapValue }` records like those produced by `toMap`, are also represented as
+    /// records; maps with other keys are lists of such records.
     Record(BTreeMap<String, SimpleValue>),
     /// A union value (both the name of the variant and the variant's value) - `Left e`
     Union(String, Option<Box<SimpleValue>>),
@@ -123,7 +154,8 @@ pub enum SimpleValue {
 /// You would typically not manipulate `SimpleType`s by hand but rather let Rust infer it for your
 /// datatype by deriving the [`StaticType`] trait, and using
 /// [`Deserializer::static_type_annotation`]. If you need to supply a `SimpleType` manually, you
-/// can either deserialize it like any other Dhall value, or construct it manually.
+/// can either deserialize it like any other Dhall value, parse it from text with
+/// [`str::parse`] or [`SimpleType::from_file`], or construct it manually.
 ///
 /// [`Deserializer::static_type_annotation`]: crate::Deserializer::static_type_annotation()
 /// [`StaticType`]: crate::StaticType
@@ -135,20 +167,20 @@ pub enum SimpleValue {
 /// Dhall  | Rust
 /// -------|------
 /// `Bool`  | `bool`
-/// `Natural`  | `u64`, `u32`, ...
-/// `Integer`  | `i64`, `i32`, ...
+/// `Natural`  | `u64`, `u32`, `NonZeroU64`, ...
+/// `Integer`  | `i64`, `i32`, `NonZeroI64`, ...
 /// `Double`  | `f64`, `f32`, ...
-/// `Text`  | `String`
-/// `List T`  | `Vec<T>`
+/// `Text`  | `String`, `char`, `PathBuf`, `IpAddr`, `SocketAddr`, `url::Url`, ...
+/// `List T`  | `Vec<T>`, `[T; N]`, `HashSet<T>`, `BTreeSet<T>`
 /// `Optional T`  | `Option<T>`
 /// `{ x: T, y: U }`  | structs
 /// `{ _1: T, _2: U }`  | `(T, U)`, structs
 /// `{ x: T, y: T }`  | `HashMap<String, T>`, structs
 /// `< x: T \| y: U >`  | enums
 /// `Prelude.Map.Type Text T`  | `HashMap<String, T>`, structs
-/// `T -> U`  | unsupported
+/// `T -> U`  | [`Function`]
 /// `Prelude.JSON.Type`  | unsupported
-/// `Prelude.Map.Type T U`  | unsupported
+/// `Prelude.Map.Type T U`  | `HashMap<T, U>`, `BTreeMap<T, U>`
 ///
 /// # Examples
 ///
@@ -212,6 +244,7 @@ impl Value {
         cx: Ctxt<'cx>,
         x: &Nir<'cx>,
         ty: &Nir<'cx>,
+        shared: &SharedCtxt,
     ) -> Result<Self> {
         Ok(if let Ok(val) = SimpleValue::from_nir(x) {
             // The type must be simple if the value is simple.
@@ -223,6 +256,10 @@ impl Value {
             Value {
                 kind: ValueKind::Ty(ty),
             }
+        } else if let Some(f) = Function::from_nir_and_ty(cx, x, ty, shared) {
+            Value {
+                kind: ValueKind::Function(f),
+            }
         } else {
             let expr = x.to_hir_noenv().to_expr(cx, Default::default());
             return Err(Error(ErrorKind::Deserialize(format!(
@@ -232,36 +269,374 @@ impl Value {
         })
     }
 
-    /// Converts a Value into a SimpleValue.
-    pub(crate) fn to_simple_value(&self) -> Option<SimpleValue> {
+    pub(crate) fn from_val(val: SimpleValue, ty: Option<SimpleType>) -> Self {
+        Value {
+            kind: ValueKind::Val(val, ty),
+        }
+    }
+
+    /// Converts a Value into a SimpleValue, if it is one. The type is lost.
+    pub fn to_simple_value(&self) -> Option<SimpleValue> {
         match &self.kind {
             ValueKind::Val(val, _) => Some(val.clone()),
             _ => None,
         }
     }
 
-    /// Converts a Value into a SimpleType.
-    pub(crate) fn to_simple_type(&self) -> Option<SimpleType> {
+    /// Converts a Value into a SimpleType, if this value is a type.
+    pub fn to_simple_type(&self) -> Option<SimpleType> {
         match &self.kind {
             ValueKind::Ty(ty) => Some(ty.clone()),
             _ => None,
         }
     }
 
+    /// Converts a Value into a Function, if it is one.
+    pub fn to_function(&self) -> Option<Function> {
+        match &self.kind {
+            ValueKind::Function(f) => Some(f.clone()),
+            _ => None,
+        }
+    }
+
+    /// The Dhall type of this value. This is `None` for types and functions, and for values
+    /// serialized without a type annotation.
+    pub fn ty(&self) -> Option<&SimpleType> {
+        match &self.kind {
+            ValueKind::Val(_, ty) => ty.as_ref(),
+            _ => None,
+        }
+    }
+
+    fn as_val(&self) -> Option<&SimpleValue> {
+        match &self.kind {
+            ValueKind::Val(val, _) => Some(val),
+            _ => None,
+        }
+    }
+
+    /// The value of a `Bool`.
+    pub fn as_bool(&self) -> Option<bool> {
+        match self.as_val()? {
+            SimpleValue::Num(NumKind::Bool(x)) => Some(*x),
+            _ => None,
+        }
+    }
+
+    /// The value of a `Natural`.
+    pub fn as_natural(&self) -> Option<u64> {
+        match self.as_val()? {
+            SimpleValue::Num(NumKind::Natural(x)) => Some(*x),
+            _ => None,
+        }
+    }
+
+    /// The value of an `Integer`.
+    pub fn as_integer(&self) -> Option<i64> {
+        match self.as_val()? {
+            SimpleValue::Num(NumKind::Integer(x)) => Some(*x),
+            _ => None,
+        }
+    }
+
+    /// The value of a `Double`.
+    pub fn as_double(&self) -> Option<f64> {
+        match self.as_val()? {
+            SimpleValue::Num(NumKind::Double(x)) => Some((*x).into()),
+            _ => None,
+        }
+    }
+
+    /// The contents of a `Text`.
+    pub fn as_text(&self) -> Option<&str> {
+        match self.as_val()? {
+            SimpleValue::Text(x) => Some(x),
+            _ => None,
+        }
+    }
+
+    /// The contents of an `Optional`: `Some(None)` for `None T`, and `Some(Some(x))` for
+    /// `Some x`.
+    pub fn as_optional(&self) -> Option<Option<Value>> {
+        let t = match self.ty() {
+            Some(SimpleType::Optional(t)) => Some((**t).clone()),
+            _ => None,
+        };
+        match self.as_val()? {
+            SimpleValue::Optional(x) => {
+                Some(x.as_ref().map(|x| Value::from_val((**x).clone(), t)))
+            }
+            _ => None,
+        }
+    }
+
+    /// The elements of a `List`.
+    ///
+    /// Maps, i.e. lists of `{ mapKey, mapValue }` records like those produced by `toMap`, are
+    /// returned as a list of such records.
+    pub fn as_list(&self) -> Option<Vec<Value>> {
+        let t = match self.ty() {
+            Some(SimpleType::List(t)) => Some((**t).clone()),
+            _ => None,
+        };
+        match self.as_val()? {
+            SimpleValue::List(xs) => Some(
+                xs.iter()
+                    .map(|x| Value::from_val(x.clone(), t.clone()))
+                    .collect(),
+            ),
+            SimpleValue::Record(kvs) if t.is_some() => Some(
+                kvs.iter()
+                    .map(|(k, v)| {
+                        let entry = vec![
+                            ("mapKey".to_owned(), SimpleValue::Text(k.clone())),
+                            ("mapValue".to_owned(), v.clone()),
+                        ];
+                        Value::from_val(
+                            SimpleValue::Record(entry.into_iter().collect()),
+                            t.clone(),
+                        )
+                    })
+                    .collect(),
+            ),
+            _ => None,
+        }
+    }
+
+    /// The fields of a record.
+    ///
+    /// Maps with `Text` keys, i.e. lists of `{ mapKey, mapValue }` records like those produced
+    /// by `toMap`, are also returned as records, from `mapKey` to `mapValue`.
+    pub fn as_record(&self) -> Option<BTreeMap<String, Value>> {
+        match self.as_val()? {
+            SimpleValue::Record(kvs) => Some(
+                kvs.iter()
+                    .map(|(k, v)| {
+                        (
+                            k.clone(),
+                            Value::from_val(v.clone(), self.field_ty(k)),
+                        )
+                    })
+                    .collect(),
+            ),
+            _ => None,
+        }
+    }
+
+    /// A field of a record, or the value for a key of a map. See [`as_record()`](Value::as_record).
+    pub fn get(&self, field: &str) -> Option<Value> {
+        match self.as_val()? {
+            SimpleValue::Record(kvs) => {
+                let v = kvs.get(field)?;
+                Some(Value::from_val(v.clone(), self.field_ty(field)))
+            }
+            _ => None,
+        }
+    }
+
+    // The type of a field of this record, or of the values of this map.
+    fn field_ty(&self, field: &str) -> Option<SimpleType> {
+        field_ty(self.ty(), field).cloned()
+    }
+
+    /// The alternative of a union value, and its contents if it has any.
+    pub fn as_union(&self) -> Option<(&str, Option<Value>)> {
+        match self.as_val()? {
+            SimpleValue::Union(variant, x) => {
+                let t = match self.ty() {
+                    Some(SimpleType::Union(kts)) => {
+                        kts.get(variant).cloned().flatten()
+                    }
+                    _ => None,
+                };
+                Some((
+                    variant.as_str(),
+                    x.as_ref().map(|x| Value::from_val((**x).clone(), t)),
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    /// Compares this value with a newer version of it. See [`Diff`] for how the differences are
+    /// represented and rendered.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # fn main() -> serde_dhall::Result<()> {
+    /// use serde_dhall::Value;
+    ///
+    /// let old: Value = serde_dhall::from_str("{ port = 80, hosts = [\"a\"] }").parse()?;
+    /// let new: Value =
+    ///     serde_dhall::from_str("{ port = 80 + 8000, hosts = [\"a\", \"b\"] }").parse()?;
+    /// assert_eq!(
+    ///     old.diff(&new).to_string(),
+    ///     "+ .hosts[1] = \"b\"\n- .port = 80\n+ .port = 8080"
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn diff(&self, new: &Value) -> Diff {
+        self.to_expr().diff(&new.to_expr())
+    }
+
+    /// Finds a sub-value by its path, e.g. `.db.hosts[0]`. The path is made of record fields
+    /// (`.x`, or ``.`x y` `` for labels that need quoting), list indices (`[2]`) and union
+    /// alternatives (`.Foo`, which looks into the contents of `Foo x` if the value is `Foo x`).
+    /// `Some` values are looked through when descending into them, like in the paths of
+    /// deserialization errors. The empty path points to the value itself.
+    ///
+    /// Returns `None` if the path is malformed or does not point to a value.
+    pub fn pointer(&self, path: &str) -> Option<Value> {
+        let segments = parse_pointer(path)?;
+        if segments.is_empty() {
+            return Some(self.clone());
+        }
+        // Walk the borrowed value, and only clone what is found.
+        let mut value = self.as_val()?;
+        let mut ty = self.ty();
+        let mut segments = segments.into_iter();
+        while let Some(segment) = segments.next() {
+            if let SimpleValue::Optional(Some(x)) = value {
+                value = x;
+                ty = match ty {
+                    Some(SimpleType::Optional(t)) => Some(t),
+                    _ => None,
+                };
+            }
+            let elem_ty = match ty {
+                Some(SimpleType::List(t)) => Some(&**t),
+                _ => None,
+            };
+            match (segment, value) {
+                (PointerSegment::Index(i), SimpleValue::List(xs)) => {
+                    value = xs.get(i)?;
+                    ty = elem_ty;
+                }
+                // A map stored as a record, whose `{ mapKey, mapValue }` entries have to be
+                // built.
+                (PointerSegment::Index(i), SimpleValue::Record(kvs))
+                    if elem_ty.is_some() =>
+                {
+                    let (k, v) = kvs.iter().nth(i)?;
+                    match segments.next() {
+                        None => {
+                            let entry = vec![
+                                (
+                                    "mapKey".to_owned(),
+                                    SimpleValue::Text(k.clone()),
+                                ),
+                                ("mapValue".to_owned(), v.clone()),
+                            ];
+                            return Some(Value::from_val(
+                                SimpleValue::Record(
+                                    entry.into_iter().collect(),
+                                ),
+                                elem_ty.cloned(),
+                            ));
+                        }
+                        Some(PointerSegment::Field("mapKey"))
+                            if segments.next().is_none() =>
+                        {
+                            return Some(Value::from_val(
+                                SimpleValue::Text(k.clone()),
+                                field_ty(elem_ty, "mapKey").cloned(),
+                            ));
+                        }
+                        Some(PointerSegment::Field("mapValue")) => {
+                            value = v;
+                            ty = field_ty(elem_ty, "mapValue");
+                        }
+                        Some(_) => return None,
+                    }
+                }
+                (
+                    PointerSegment::Field(label),
+                    SimpleValue::Union(variant, x),
+                ) => {
+                    match x {
+                        Some(x) if variant == label => value = x,
+                        _ => return None,
+                    }
+                    ty = match ty {
+                        Some(SimpleType::Union(kts)) => {
+                            kts.get(label).and_then(Option::as_ref)
+                        }
+                        _ => None,
+                    };
+                }
+                (PointerSegment::Field(label), SimpleValue::Record(kvs)) => {
+                    value = kvs.get(label)?;
+                    ty = field_ty(ty, label);
+                }
+                _ => return None,
+            }
+        }
+        Some(Value::from_val(value.clone(), ty.cloned()))
+    }
+
     /// Converts a value back to the corresponding AST expression.
     pub(crate) fn to_expr(&self) -> Expr {
         match &self.kind {
             ValueKind::Val(val, ty) => val.to_expr(ty.as_ref()).unwrap(),
             ValueKind::Ty(ty) => ty.to_expr(),
+            ValueKind::Function(f) => f.to_expr(),
+        }
+    }
+}
+
+// The type of a field of a record, or of the values of a map.
+fn field_ty<'a>(
+    ty: Option<&'a SimpleType>,
+    field: &str,
+) -> Option<&'a SimpleType> {
+    match ty? {
+        SimpleType::Record(kts) => kts.get(field),
+        SimpleType::List(t) => match &**t {
+            SimpleType::Record(kts) => kts.get("mapValue"),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// A step of a path given to `Value::pointer`.
+enum PointerSegment<'p> {
+    Field(&'p str),
+    Index(usize),
+}
+
+fn parse_pointer(path: &str) -> Option<Vec<PointerSegment<'_>>> {
+    let mut segments = Vec::new();
+    let mut rest = path;
+    while !rest.is_empty() {
+        if let Some(r) = rest.strip_prefix('[') {
+            let end = r.find(']')?;
+            segments.push(PointerSegment::Index(r[..end].parse().ok()?));
+            rest = &r[end + 1..];
+        } else if let Some(r) = rest.strip_prefix('.') {
+            let (label, r) = if let Some(r) = r.strip_prefix('`') {
+                let end = r.find('`')?;
+                (&r[..end], &r[end + 1..])
+            } else {
+                let end = r.find(&['.', '['][..]).unwrap_or(r.len());
+                (&r[..end], &r[end..])
+            };
+            segments.push(PointerSegment::Field(label));
+            rest = r;
+        } else {
+            return None;
         }
     }
+    Some(segments)
 }
 
 #[derive(Debug)]
-struct NotSimpleValue;
+pub(crate) struct NotSimpleValue;
 
 impl SimpleValue {
-    fn from_nir(nir: &Nir) -> StdResult<Self, NotSimpleValue> {
+    pub(crate) fn from_nir(nir: &Nir) -> StdResult<Self, NotSimpleValue> {
         Ok(match nir.kind() {
             NirKind::Num(lit) => SimpleValue::Num(lit.clone()),
             NirKind::TextLit(x) => SimpleValue::Text(
@@ -276,8 +651,11 @@ impl SimpleValue {
                 // Detect and handle the special records that make assoc maps
                 if let NirKind::RecordType(kts) = t.kind() {
                     if kts.len() == 2
-                        && kts.contains_key("mapKey")
                         && kts.contains_key("mapValue")
+                        && matches!(
+                            kts.get("mapKey").map(Nir::kind),
+                            Some(NirKind::BuiltinType(Builtin::Text))
+                        )
                     {
                         return Ok(SimpleValue::Record(Default::default()));
                     }
@@ -285,40 +663,39 @@ impl SimpleValue {
                 SimpleValue::List(vec![])
             }
             NirKind::NEListLit(xs) => {
-                // Detect and handle the special records that make assoc maps
-                if let NirKind::RecordLit(kvs) = xs[0].kind() {
-                    if kvs.len() == 2
-                        && kvs.contains_key("mapKey")
-                        && kvs.contains_key("mapValue")
-                    {
-                        let convert_entry = |x: &Nir| match x.kind() {
-                            NirKind::RecordLit(kvs) => {
-                                let k = match kvs.get("mapKey").unwrap().kind()
-                                {
-                                    NirKind::TextLit(t)
-                                        if t.as_text().is_some() =>
-                                    {
-                                        t.as_text().unwrap()
-                                    }
-                                    // TODO
-                                    _ => panic!(
-                                        "Expected `mapKey` to be a text \
-                                         literal"
-                                    ),
-                                };
-                                let v = Self::from_nir(
-                                    kvs.get("mapValue").unwrap(),
-                                )?;
-                                Ok((k, v))
-                            }
-                            _ => unreachable!("Internal type error"),
-                        };
-                        return Ok(SimpleValue::Record(
-                            xs.iter()
-                                .map(convert_entry)
-                                .collect::<StdResult<_, _>>()?,
-                        ));
+                // Detect and handle the special records that make assoc maps. Only maps with
+                // `Text` keys become records; other maps stay lists of entries.
+                let is_text_map = match xs[0].kind() {
+                    NirKind::RecordLit(kvs) => {
+                        kvs.len() == 2
+                            && kvs.contains_key("mapValue")
+                            && matches!(
+                                kvs.get("mapKey").map(Nir::kind),
+                                Some(NirKind::TextLit(_))
+                            )
                     }
+                    _ => false,
+                };
+                if is_text_map {
+                    let convert_entry = |x: &Nir| match x.kind() {
+                        NirKind::RecordLit(kvs) => {
+                            let k = match kvs.get("mapKey").unwrap().kind() {
+                                NirKind::TextLit(t) => {
+                                    t.as_text().ok_or(NotSimpleValue)?
+                                }
+                                _ => unreachable!("Internal type error"),
+                            };
+                            let v =
+                                Self::from_nir(kvs.get("mapValue").unwrap())?;
+                            Ok((k, v))
+                        }
+                        _ => unreachable!("Internal type error"),
+                    };
+                    return Ok(SimpleValue::Record(
+                        xs.iter()
+                            .map(convert_entry)
+                            .collect::<StdResult<_, _>>()?,
+                    ));
                 }
                 SimpleValue::List(
                     xs.iter()
@@ -346,7 +723,10 @@ impl SimpleValue {
 
     // Converts this to `Hir`, using the optional type annotation. Without the type, things like
     // empty lists and unions will fail to convert.
-    fn to_hir<'cx>(&self, ty: Option<&SimpleType>) -> Result<Hir<'cx>> {
+    pub(crate) fn to_hir<'cx>(
+        &self,
+        ty: Option<&SimpleType>,
+    ) -> Result<Hir<'cx>> {
         use SimpleType as T;
         use SimpleValue as V;
         let hir = |k| Hir::new(HirKind::Expr(k), Span::Artificial);
@@ -413,6 +793,38 @@ impl SimpleValue {
                     .collect::<Result<_>>()?,
             ),
 
+            // Maps and arrays are serialized as records, but they may have a list type.
+            (V::Record(v), Some(T::List(t))) => {
+                let is_map_entry = match &**t {
+                    T::Record(kts) => {
+                        kts.len() == 2
+                            && kts.get("mapKey") == Some(&T::Text)
+                            && kts.contains_key("mapValue")
+                    }
+                    _ => false,
+                };
+                let elems = if is_map_entry {
+                    v.iter()
+                        .map(|(k, v)| {
+                            V::Record(
+                                vec![
+                                    ("mapKey".to_owned(), V::Text(k.clone())),
+                                    ("mapValue".to_owned(), v.clone()),
+                                ]
+                                .into_iter()
+                                .collect(),
+                            )
+                        })
+                        .collect()
+                } else {
+                    (1..=v.len())
+                        .map(|i| v.get(&format!("_{}", i)).cloned())
+                        .collect::<Option<Vec<_>>>()
+                        .ok_or_else(type_error)?
+                };
+                return V::List(elems).to_hir(ty);
+            }
+
             (V::Union(..), None) => return Err(type_missing()),
             (V::Union(variant, Some(v)), Some(T::Union(t))) => {
                 match t.get(variant) {
@@ -440,6 +852,14 @@ impl SimpleValue {
         };
         Ok(hir(kind))
     }
+    /// Compares this value with a newer version of it. See [`Diff`].
+    ///
+    /// This fails if one of the values can't be printed without knowing its type, e.g. if it
+    /// contains an empty list. Use [`Value::diff`] for those.
+    pub fn diff(&self, new: &SimpleValue) -> Result<Diff> {
+        Ok(self.to_expr(None)?.diff(&new.to_expr(None)?))
+    }
+
     pub(crate) fn into_value(self, ty: Option<&SimpleType>) -> Result<Value> {
         // Check that the value is printable with the given type.
         self.to_hir(ty)?;
@@ -457,10 +877,10 @@ impl SimpleValue {
 }
 
 #[derive(Debug)]
-struct NotSimpleType;
+pub(crate) struct NotSimpleType;
 
 impl SimpleType {
-    fn from_nir(nir: &Nir) -> StdResult<Self, NotSimpleType> {
+    pub(crate) fn from_nir(nir: &Nir) -> StdResult<Self, NotSimpleType> {
         Ok(match nir.kind() {
             NirKind::BuiltinType(b) => match b {
                 Builtin::Bool => SimpleType::Bool,
@@ -564,6 +984,7 @@ impl PartialEq for ValueKind {
         match (self, other) {
             (Val(a, _), Val(b, _)) => a == b,
             (Ty(a), Ty(b)) => a == b,
+            (Function(a), Function(b)) => a == b,
             _ => false,
         }
     }
@@ -577,6 +998,46 @@ impl std::fmt::Display for Value {
     }
 }
 
+impl SimpleType {
+    /// Reads a type from a file of Dhall code. Imports are resolved relative to that file.
+    ///
+    /// This is useful to keep the expected type of a configuration alongside it, and check it
+    /// with [`Deserializer::type_annotation`] without recompiling.
+    ///
+    /// [`Deserializer::type_annotation`]: crate::Deserializer::type_annotation()
+    pub fn from_file<P: AsRef<std::path::Path>>(path: P) -> Result<Self> {
+        crate::from_file(path).parse()
+    }
+}
+
+/// Parses a type from Dhall code. Imports are resolved relative to the current directory.
+///
+/// # Example
+///
+/// ```rust
+/// # fn main() -> serde_dhall::Result<()> {
+/// use serde_dhall::{SimpleType, Value};
+///
+/// let ty: SimpleType = "{ host : Text, port : Natural }".parse()?;
+/// let data: Value = serde_dhall::from_str("{ host = \"localhost\", port = 8080 }")
+///     .type_annotation(&ty)
+///     .parse()?;
+/// assert_eq!(data.get("port").unwrap().as_natural(), Some(8080));
+///
+/// assert!(serde_dhall::from_str("{ host = \"localhost\" }")
+///     .type_annotation(&ty)
+///     .parse::<Value>()
+///     .is_err());
+/// # Ok(())
+/// # }
+/// ```
+impl std::str::FromStr for SimpleType {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        crate::from_str(s).parse()
+    }
+}
+
 impl std::fmt::Display for SimpleType {
     fn fmt(
         &self,
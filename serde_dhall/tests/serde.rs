@@ -56,6 +56,122 @@ mod serde {
 
         assert_serde("1.0", 1.0f64);
         assert_serde("1.0", 1.0f32);
+
+        assert_serde("1", 1u16);
+        assert_serde("1", 1u8);
+        assert_serde("+1", 1i16);
+        assert_serde("-1", -1i8);
+        assert_serde("1", std::num::NonZeroU64::new(1).unwrap());
+        assert_serde("-1", std::num::NonZeroI32::new(-1).unwrap());
+        assert!(from_str("0")
+            .static_type_annotation()
+            .parse::<std::num::NonZeroU8>()
+            .is_err());
+    }
+
+    #[test]
+    fn std_types() {
+        use std::borrow::Cow;
+        use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+        use std::net::{IpAddr, SocketAddr};
+        use std::path::PathBuf;
+        use std::rc::Rc;
+        use std::sync::Arc;
+        use std::time::Duration;
+
+        assert_serde("\"a\"", 'a');
+        assert_serde("\"/tmp/x.dhall\"", PathBuf::from("/tmp/x.dhall"));
+        assert_serde("\"::1\"", IpAddr::from([0, 0, 0, 0, 0, 0, 0, 1u16]));
+        assert_serde(
+            "\"127.0.0.1:80\"",
+            SocketAddr::from(([127, 0, 0, 1], 80)),
+        );
+        assert_serde(
+            "\"https://example.com/a\"",
+            url::Url::parse("https://example.com/a").unwrap(),
+        );
+        assert_serde(
+            "{ nanos = 5000, secs = 1 }",
+            Duration::from_secs(1) + Duration::from_micros(5),
+        );
+
+        assert_serde("1", Box::new(1u64));
+        assert_serde("\"a\"", Rc::new("a".to_owned()));
+        assert_serde("[True]", Arc::new(vec![true]));
+        assert_serde::<Cow<'static, str>>("\"a\"", Cow::Borrowed("a"));
+
+        assert_serde("[1, 2, 3]", [1u64, 2, 3]);
+        assert_serde("[] : List Natural", [0u64; 0]);
+        assert_serde("[1, 2]", (1u64..=2).collect::<BTreeSet<_>>());
+        assert_serde("[1]", Some(1u64).into_iter().collect::<HashSet<_>>());
+
+        let mut map = BTreeMap::new();
+        assert_serde(
+            "[] : List { mapKey : Text, mapValue : Natural }",
+            map.clone(),
+        );
+        map.insert("a".to_owned(), 1u64);
+        map.insert("b".to_owned(), 2);
+        assert_serde(
+            "[{ mapKey = \"a\", mapValue = 1 }, { mapKey = \"b\", mapValue = 2 }]",
+            map.clone(),
+        );
+        assert_de(
+            "toMap { a = 1, b = 2 }",
+            map.into_iter().collect::<HashMap<_, _>>(),
+        );
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn chrono_types() {
+        use chrono::{NaiveDate, TimeZone, Utc};
+        let date = NaiveDate::from_ymd_opt(2020, 1, 31).unwrap();
+        assert_serde("\"2020-01-31\"", date);
+        assert_serde(
+            "\"2020-01-31T12:00:00\"",
+            date.and_hms_opt(12, 0, 0).unwrap(),
+        );
+        assert_serde(
+            "\"2020-01-31T12:00:00Z\"",
+            Utc.with_ymd_and_hms(2020, 1, 31, 12, 0, 0).unwrap(),
+        );
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn time_types() {
+        let date =
+            time::Date::from_calendar_date(2020, time::Month::January, 31)
+                .unwrap();
+        assert_serde("\"2020-01-31\"", date);
+        assert_serde(
+            "\"2020-01-31 12:00:00.0 +00:00:00\"",
+            date.with_hms(12, 0, 0).unwrap().assume_utc(),
+        );
+    }
+
+    #[cfg(feature = "uuid")]
+    #[test]
+    fn uuid_types() {
+        assert_serde(
+            "\"67e55044-10b1-426f-9247-bb680e5fe0c8\"",
+            uuid::Uuid::parse_str("67e55044-10b1-426f-9247-bb680e5fe0c8")
+                .unwrap(),
+        );
+    }
+
+    #[cfg(feature = "semver")]
+    #[test]
+    fn semver_types() {
+        assert_serde(
+            "\"1.2.3-beta.1\"",
+            semver::Version::parse("1.2.3-beta.1").unwrap(),
+        );
+        assert_serde(
+            "\">=1.2, <2\"",
+            semver::VersionReq::parse(">=1.2, <2").unwrap(),
+        );
     }
 
     #[test]
@@ -102,17 +218,24 @@ mod serde {
 
     #[test]
     fn structs() {
-        // #[derive(
-        //     Debug, Clone, PartialEq, Eq, Deserialize, Serialize, StaticType,
-        // )]
-        // struct Foo;
-        // assert_serde::<Foo>("{=}", Foo);
-
-        // #[derive(
-        //     Debug, Clone, PartialEq, Eq, Deserialize, Serialize, StaticType,
-        // )]
-        // struct Bar(u64);
-        // assert_serde::<Bar>("{ _1 = 1 }", Bar (1));
+        #[derive(
+            Debug, Clone, PartialEq, Eq, Deserialize, Serialize, StaticType,
+        )]
+        struct Foo;
+        assert_serde::<Foo>("{=}", Foo);
+
+        // Newtype structs are transparent.
+        #[derive(
+            Debug, Clone, PartialEq, Eq, Deserialize, Serialize, StaticType,
+        )]
+        struct Bar(u64);
+        assert_serde::<Bar>("1", Bar(1));
+
+        #[derive(
+            Debug, Clone, PartialEq, Eq, Deserialize, Serialize, StaticType,
+        )]
+        struct Qux(u64, String);
+        assert_serde::<Qux>(r#"{ _1 = 1, _2 = "foo" }"#, Qux(1, "foo".into()));
 
         #[derive(
             Debug, Clone, PartialEq, Eq, Deserialize, Serialize, StaticType,
@@ -122,6 +245,27 @@ mod serde {
             y: i64,
         }
         assert_serde::<Baz>("{ x = 1, y = -2 }", Baz { x: 1, y: -2 });
+
+        // The static type follows serde attributes.
+        #[derive(
+            Debug, Clone, PartialEq, Eq, Deserialize, Serialize, StaticType,
+        )]
+        #[serde(rename_all = "camelCase")]
+        struct Renamed {
+            first_field: u64,
+            #[serde(skip)]
+            skipped: u64,
+            #[serde(flatten)]
+            baz: Baz,
+        }
+        assert_serde::<Renamed>(
+            "{ firstField = 1, x = 2, y = +3 }",
+            Renamed {
+                first_field: 1,
+                skipped: 0,
+                baz: Baz { x: 2, y: 3 },
+            },
+        );
     }
 
     #[test]
@@ -148,6 +292,46 @@ mod serde {
             .static_type_annotation()
             .parse::<Bar>()
             .is_err());
+
+        #[derive(
+            Debug, Clone, PartialEq, Eq, Deserialize, Serialize, StaticType,
+        )]
+        enum Shape {
+            Circle { radius: u64 },
+            Point,
+        }
+        assert_serde::<Shape>(
+            "< Circle: { radius : Natural } | Point >.Circle { radius = 1 }",
+            Shape::Circle { radius: 1 },
+        );
+
+        #[derive(
+            Debug, Clone, PartialEq, Eq, Deserialize, Serialize, StaticType,
+        )]
+        enum Pair {
+            P(u64, String),
+        }
+        assert_serde::<Pair>(
+            r#"< P: { _1 : Natural, _2 : Text } >.P { _1 = 1, _2 = "a" }"#,
+            Pair::P(1, "a".into()),
+        );
+    }
+
+    #[test]
+    fn bytes() {
+        struct Bytes(Vec<u8>);
+        impl Serialize for Bytes {
+            fn serialize<S>(&self, s: S) -> Result<S::Ok, S::Error>
+            where
+                S: serde::Serializer,
+            {
+                s.serialize_bytes(&self.0)
+            }
+        }
+        assert_eq!(
+            serialize(&Bytes(vec![0, 255])).to_string().unwrap(),
+            "[0, 255]"
+        );
     }
 
     #[test]
@@ -227,6 +411,1247 @@ mod serde {
         );
     }
 
+    #[test]
+    fn with_builtin_function() {
+        use serde_dhall::{SimpleType, SimpleValue};
+
+        let lookup = |data: &str| {
+            from_str(data)
+                .with_builtin_function(
+                    "lookup".to_string(),
+                    vec![SimpleType::Text],
+                    SimpleType::Optional(Box::new(SimpleType::Natural)),
+                    |args| match &args[0] {
+                        SimpleValue::Text(key) => {
+                            let val = match key.as_str() {
+                                "port" => Some(Box::new(SimpleValue::Num(
+                                    serde_dhall::NumKind::Natural(8080),
+                                ))),
+                                _ => None,
+                            };
+                            Some(SimpleValue::Optional(val))
+                        }
+                        _ => None,
+                    },
+                )
+                .parse::<Option<u64>>()
+        };
+
+        assert_eq!(lookup("lookup \"port\"").unwrap(), Some(8080));
+        assert_eq!(lookup("lookup (\"po\" ++ \"rt\")").unwrap(), Some(8080));
+        assert_eq!(lookup("lookup \"host\"").unwrap(), None);
+        assert_eq!(
+            lookup("let lookup = 1 in lookup@1 \"port\"").unwrap(),
+            Some(8080)
+        );
+        // Ill-typed calls are caught by typechecking.
+        assert!(lookup("lookup 1").is_err());
+        assert!(lookup("lookups \"port\"").is_err());
+
+        // A value of the wrong type leaves the call unevaluated.
+        let wrong = from_str("wrong 1")
+            .with_builtin_function(
+                "wrong".to_string(),
+                vec![SimpleType::Natural],
+                SimpleType::Natural,
+                |_| Some(SimpleValue::Text("one".to_string())),
+            )
+            .parse::<u64>();
+        assert!(wrong.is_err());
+    }
+
+    #[test]
+    fn with_builtin_value() {
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Facts {
+            host: String,
+            cores: u64,
+        }
+        let facts = Facts {
+            host: "alpha".to_string(),
+            cores: 4,
+        };
+
+        assert_eq!(
+            from_str("facts // { cores = facts.cores * 2 }")
+                .with_builtin_value("facts".to_string(), &facts)
+                .unwrap()
+                .parse::<Facts>()
+                .unwrap(),
+            Facts {
+                host: "alpha".to_string(),
+                cores: 8,
+            }
+        );
+        assert_eq!(
+            from_str("double 21")
+                .with_builtin_expr(
+                    "double".to_string(),
+                    "\\(n: Natural) -> n * 2"
+                )
+                .unwrap()
+                .parse::<u64>()
+                .unwrap(),
+            42
+        );
+        // Ill-typed bindings are rejected upfront.
+        assert!(from_str("x")
+            .with_builtin_expr("x".to_string(), "1 + True")
+            .is_err());
+        assert!(from_str("x")
+            .with_builtin_expr("x".to_string(), "./foo.dhall")
+            .is_err());
+        assert!(from_str("x + 1")
+            .with_builtin_value("x".to_string(), "one")
+            .unwrap()
+            .parse::<u64>()
+            .is_err());
+    }
+
+    #[test]
+    fn function() {
+        use serde_dhall::{Function, SimpleType, SimpleValue};
+
+        #[derive(Debug, Serialize)]
+        struct Env {
+            name: String,
+            replicas: u64,
+        }
+
+        let f = from_str(
+            "\\(env: { name: Text, replicas: Natural }) -> \\(suffix: Text) -> \"${env.name}-${Natural/show env.replicas}${suffix}\"",
+        )
+        .parse::<Function>()
+        .unwrap();
+        let env = Env {
+            name: "prod".to_string(),
+            replicas: 3,
+        };
+        let g = Function::from_dhall(&f.call(&env).unwrap()).unwrap();
+        assert_eq!(
+            String::from_dhall(&g.call(&"!").unwrap()).unwrap(),
+            "prod-3!"
+        );
+
+        // The argument must have the expected type.
+        let err = f.call(&1u64).unwrap_err().to_string();
+        assert!(err.starts_with("Type error: "), "{}", err);
+        let err = f
+            .call(&collections::HashMap::<String, String>::new())
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("Type error: "), "{}", err);
+        assert!(from_str("\\(t: Type) -> t")
+            .parse::<Function>()
+            .unwrap()
+            .call(&1u64)
+            .is_err());
+
+        // Functions keep the builtins of the deserializer.
+        let f = from_str("\\(x: Natural) -> double x")
+            .with_builtin_function(
+                "double".to_string(),
+                vec![SimpleType::Natural],
+                SimpleType::Natural,
+                |args| match &args[0] {
+                    SimpleValue::Num(serde_dhall::NumKind::Natural(n)) => Some(
+                        SimpleValue::Num(serde_dhall::NumKind::Natural(n * 2)),
+                    ),
+                    _ => None,
+                },
+            )
+            .parse::<Function>()
+            .unwrap();
+        assert_eq!(u64::from_dhall(&f.call(&21u64).unwrap()).unwrap(), 42);
+
+        // Functions are equal if they were evaluated together.
+        let parse = || {
+            from_str("\\(x: Natural) -> x + 1")
+                .parse::<Function>()
+                .unwrap()
+        };
+        let f = parse();
+        assert_eq!(f, f.clone());
+        assert_ne!(f, parse());
+
+        // Each call gets the full evaluation budget, until one goes over it.
+        let f = from_str(
+            "\\(n: Natural) -> Natural/fold n Natural (\\(x: Natural) -> x + 1) 0",
+        )
+        .eval_limits(serde_dhall::EvalLimits {
+            max_fold_iterations: Some(10),
+            ..Default::default()
+        })
+        .parse::<Function>()
+        .unwrap();
+        for _ in 0..3 {
+            assert_eq!(u64::from_dhall(&f.call(&8u64).unwrap()).unwrap(), 8);
+        }
+        assert!(f.call(&20u64).is_err());
+        assert!(f.call(&8u64).is_err());
+    }
+
+    #[test]
+    fn sources() {
+        let dir = std::env::temp_dir()
+            .join(format!("serde_dhall_sources_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("common.dhall"), "{ port = 80 }").unwrap();
+        let data = "(./common.dhall).port + 1";
+
+        assert!(serde_dhall::from_str(data).parse::<u64>().is_err());
+        assert_eq!(
+            serde_dhall::from_str(data)
+                .base_dir(&dir)
+                .parse::<u64>()
+                .unwrap(),
+            81
+        );
+        assert_eq!(
+            serde_dhall::from_reader(data.as_bytes())
+                .base_dir(&dir)
+                .parse::<u64>()
+                .unwrap(),
+            81
+        );
+
+        let expr = dhall::Parsed::parse_str(data).unwrap().to_expr();
+        let binary = dhall::syntax::binary::encode(&expr).unwrap();
+        assert_eq!(
+            serde_dhall::from_binary(&binary)
+                .base_dir(&dir)
+                .parse::<u64>()
+                .unwrap(),
+            81
+        );
+
+        assert!(serde_dhall::from_url("not a url").parse::<u64>().is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn import_options() {
+        assert_eq!(
+            serde_dhall::from_str("env:FOO + 1")
+                .env_var("FOO", "41")
+                .parse::<u64>()
+                .unwrap(),
+            42
+        );
+        // The process environment is not visible anymore.
+        assert!(serde_dhall::from_str("env:PATH as Text")
+            .env_var("FOO", "41")
+            .parse::<String>()
+            .is_err());
+        assert!(serde_dhall::from_str("env:FOO + 1")
+            .env_var("FOO", "41")
+            .env_imports(false)
+            .parse::<u64>()
+            .is_err());
+        // Fails without trying to access the network.
+        assert!(serde_dhall::from_str("https://example.com/x.dhall")
+            .remote_imports(false)
+            .parse::<u64>()
+            .is_err());
+        // `as Location` does not fetch anything.
+        assert!(serde_dhall::from_str(
+            "https://example.com/x.dhall as Location"
+        )
+        .remote_imports(false)
+        .parse::<serde_dhall::Value>()
+        .is_ok());
+
+        let dir = std::env::temp_dir()
+            .join(format!("serde_dhall_import_options_{}", std::process::id()));
+        let cache_dir = dir.join("cache");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("x.dhall"), "1 + 1").unwrap();
+        let hash = dhall::syntax::Expr::new(
+            dhall::syntax::ExprKind::Num(dhall::syntax::NumKind::Natural(2)),
+            dhall::syntax::Span::Artificial,
+        )
+        .sha256_hash()
+        .unwrap();
+        let hash: String = hash.iter().map(|b| format!("{:02x}", b)).collect();
+        let data = format!("./x.dhall sha256:{}", hash);
+        let load = |de: serde_dhall::Deserializer<'_, _>| {
+            de.base_dir(&dir).parse::<u64>()
+        };
+
+        assert_eq!(load(from_str(&data).cache_dir(&cache_dir)).unwrap(), 2);
+        assert!(cache_dir.join(format!("1220{}", hash)).exists());
+        // Once cached, the file is not needed anymore.
+        std::fs::remove_file(dir.join("x.dhall")).unwrap();
+        assert_eq!(load(from_str(&data).cache_dir(&cache_dir)).unwrap(), 2);
+        assert!(
+            load(from_str(&data).cache_dir(&cache_dir).cache(false)).is_err()
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn to_string_pretty() {
+        #[derive(Serialize, StaticType)]
+        struct Port {
+            port: u64,
+            protocol: String,
+        }
+        #[derive(Serialize, StaticType)]
+        struct Labels {
+            app: String,
+        }
+        #[derive(Serialize, StaticType)]
+        struct Service {
+            name: String,
+            ports: Vec<Port>,
+            labels: Labels,
+        }
+
+        let data = Service {
+            name: "web".to_owned(),
+            ports: vec![
+                Port {
+                    port: 80,
+                    protocol: "tcp".to_owned(),
+                },
+                Port {
+                    port: 53,
+                    protocol: "udp".to_owned(),
+                },
+            ],
+            labels: Labels {
+                app: "web".to_owned(),
+            },
+        };
+
+        // Fits on one line.
+        assert_eq!(
+            serialize(&data.labels).to_string_pretty(80).unwrap(),
+            serialize(&data.labels).to_string().unwrap()
+        );
+        assert_eq!(
+            serialize(&data).to_string_pretty(40).unwrap(),
+            r#"{ labels = { app = "web" }
+, name = "web"
+, ports =
+    [ { port = 80, protocol = "tcp" }
+    , { port = 53, protocol = "udp" }
+    ]
+}"#
+        );
+        assert_eq!(
+            serialize(&data)
+                .static_type_annotation()
+                .sort_fields(false)
+                .to_string_pretty(40)
+                .unwrap(),
+            r#"{ name = "web"
+, ports =
+    [ { port = 80, protocol = "tcp" }
+    , { port = 53, protocol = "udp" }
+    ]
+, labels = { app = "web" }
+}"#
+        );
+        // Structs with the same fields keep their own order.
+        #[derive(Serialize)]
+        struct XY {
+            x: u64,
+            y: u64,
+        }
+        #[derive(Serialize)]
+        struct YX {
+            y: u64,
+            x: u64,
+        }
+        #[derive(Serialize)]
+        struct Both {
+            a: XY,
+            b: Vec<YX>,
+        }
+        let both = Both {
+            a: XY { x: 1, y: 2 },
+            b: vec![YX { y: 3, x: 4 }],
+        };
+        assert_eq!(
+            serialize(&both)
+                .sort_fields(false)
+                .to_string_pretty(80)
+                .unwrap(),
+            "{ a = { x = 1, y = 2 }, b = [ { y = 3, x = 4 } ] }"
+        );
+
+        // The output parses back to the same value.
+        let s = serialize(&data)
+            .static_type_annotation()
+            .to_string_pretty(10)
+            .unwrap();
+        assert_eq!(
+            from_str(&s).parse::<Value>().unwrap(),
+            from_str(
+                &serialize(&data)
+                    .static_type_annotation()
+                    .to_string()
+                    .unwrap()
+            )
+            .parse::<Value>()
+            .unwrap()
+        );
+
+        #[derive(Serialize, StaticType)]
+        enum Shape {
+            Circle(f64),
+            Square(f64),
+        }
+        assert_eq!(
+            serialize(&Shape::Circle(1.0))
+                .static_type_annotation()
+                .to_string_pretty(20)
+                .unwrap(),
+            "< Circle : Double\n| Square : Double\n>.Circle\n  1.0"
+        );
+        assert_eq!(
+            serialize(&Shape::Square(2.0))
+                .static_type_annotation()
+                .to_string_pretty(80)
+                .unwrap(),
+            "< Circle : Double | Square : Double >.Square 2.0"
+        );
+    }
+
+    #[test]
+    fn binary_and_files() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize, StaticType)]
+        struct Config {
+            name: String,
+            replicas: Option<u64>,
+        }
+        let data = Config {
+            name: "web".to_owned(),
+            replicas: None,
+        };
+
+        let bytes = serialize(&data)
+            .static_type_annotation()
+            .to_binary()
+            .unwrap();
+        assert_eq!(
+            serde_dhall::from_binary(&bytes).parse::<Config>().unwrap(),
+            data
+        );
+        let mut buf = Vec::new();
+        serialize(&data)
+            .static_type_annotation()
+            .to_writer(&mut buf)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            r#"{ name = "web", replicas = None Natural }"#
+        );
+
+        let dir = std::env::temp_dir().join(format!(
+            "serde_dhall_binary_and_files_{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let text_file = dir.join("config.dhall");
+        let binary_file = dir.join("config.dhallb");
+        let ser = serialize(&data).static_type_annotation();
+        ser.to_file(&text_file).unwrap();
+        ser.to_binary_file(&binary_file).unwrap();
+        assert_eq!(
+            serde_dhall::from_file(&text_file)
+                .parse::<Config>()
+                .unwrap(),
+            data
+        );
+        assert_eq!(
+            serde_dhall::from_binary_file(&binary_file)
+                .parse::<Config>()
+                .unwrap(),
+            data
+        );
+
+        // The hash can be used to import the written file with an integrity check.
+        let hash = ser.sha256().unwrap();
+        let import = format!("./config.dhall {}", hash);
+        assert_eq!(
+            from_str(&import)
+                .base_dir(&dir)
+                .cache(false)
+                .parse::<Config>()
+                .unwrap(),
+            data
+        );
+        let other = serialize(&5u64).sha256().unwrap();
+        assert!(from_str(&format!("./config.dhall {}", other))
+            .base_dir(&dir)
+            .cache(false)
+            .parse::<Config>()
+            .is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn schema() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize, StaticType)]
+        struct Server {
+            host: String,
+            port: u64,
+            tags: Vec<String>,
+            protocol: String,
+        }
+        impl Default for Server {
+            fn default() -> Self {
+                Server {
+                    host: "localhost".to_owned(),
+                    port: 8080,
+                    tags: Vec::new(),
+                    protocol: "http".to_owned(),
+                }
+            }
+        }
+
+        assert_eq!(
+            serde_dhall::schema::<Server>().to_string(),
+            "{ host : Text, port : Natural, protocol : Text, tags : List Text }"
+        );
+        assert!(serde_dhall::schema::<u64>().with_default(&5u64).is_err());
+        assert!(serde_dhall::schema::<Server>().with_default(&5u64).is_err());
+
+        assert!(serde_dhall::schema_with_default::<u64>().is_err());
+        let schema = serde_dhall::schema_with_default::<Server>().unwrap();
+        assert_eq!(
+            schema.to_string(),
+            serde_dhall::schema::<Server>()
+                .with_default(&Server::default())
+                .unwrap()
+                .to_string()
+        );
+        assert_eq!(
+            schema.to_string_pretty(40),
+            "{ `Type` =
+    { host : Text
+    , port : Natural
+    , protocol : Text
+    , tags : List Text
+    }
+, default =
+    { host = \"localhost\"
+    , port = 8080
+    , protocol = \"http\"
+    , tags = [] : List Text
+    }
+}"
+        );
+
+        let dir = std::env::temp_dir()
+            .join(format!("serde_dhall_schema_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        schema.render_to_file(dir.join("Server.dhall")).unwrap();
+        assert_eq!(
+            from_str("(./Server.dhall)::{ port = 80 }")
+                .base_dir(&dir)
+                .cache(false)
+                .static_type_annotation()
+                .parse::<Server>()
+                .unwrap(),
+            Server {
+                port: 80,
+                ..Server::default()
+            }
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn with_defaults() {
+        #[derive(
+            Debug, PartialEq, Default, Clone, Serialize, Deserialize, StaticType,
+        )]
+        struct Config {
+            name: String,
+            port: u64,
+            replicas: Option<u64>,
+            tags: Vec<String>,
+        }
+
+        let parse = |s: &str, defaults: &[&str]| {
+            defaults
+                .iter()
+                .try_fold(from_str(s), |de, d| de.with_defaults(d))
+                .and_then(|de| de.static_type_annotation().parse::<Config>())
+                .map_err(|e| e.to_string())
+        };
+        let expected = Config {
+            name: "app".to_owned(),
+            port: 8080,
+            replicas: None,
+            tags: vec!["a".to_owned()],
+        };
+
+        let defaults = "{ name = \"default\", port = 80, replicas = None Natural, tags = [\"a\"] }";
+        assert_eq!(
+            parse("{ name = \"app\", port = 8080 }", &[defaults]),
+            Ok(expected.clone())
+        );
+        // Schemas can be used directly, and later defaults take precedence.
+        let schema = serde_dhall::schema::<Config>()
+            .with_default(&Config::default())
+            .unwrap()
+            .to_string();
+        assert_eq!(
+            parse(
+                "{ name = \"app\" }",
+                &[&schema, "{ port = 8080, tags = [\"a\"] }"]
+            ),
+            Ok(expected.clone())
+        );
+        assert_eq!(
+            from_str("{ name = \"app\", port = 8080, tags = [\"a\"] }")
+                .with_default_value(&Config::default())
+                .and_then(|de| de.parse::<Config>())
+                .map_err(|e| e.to_string()),
+            Ok(expected)
+        );
+
+        assert_eq!(
+            parse("{ name = \"app\" }", &["1"]),
+            Err("the defaults must be a record, found: 1".to_owned())
+        );
+        assert!(parse("{ name = \"app\" }", &["{ port = 80 }"]).is_err());
+    }
+
+    #[test]
+    fn simple_type_from_source() {
+        use serde_dhall::SimpleType;
+
+        #[derive(Debug, PartialEq, Deserialize, StaticType)]
+        struct Config {
+            host: String,
+            port: u64,
+        }
+
+        let ty: SimpleType = "{ host : Text, port : Natural }".parse().unwrap();
+        assert_eq!(ty, Config::static_type());
+        assert!("1".parse::<SimpleType>().is_err());
+        assert!("{ host : ".parse::<SimpleType>().is_err());
+
+        let dir = std::env::temp_dir().join(format!(
+            "serde_dhall_simple_type_from_source_{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(dir.join("types")).unwrap();
+        std::fs::write(dir.join("types/Port.dhall"), "Natural").unwrap();
+        std::fs::write(
+            dir.join("types/Config.dhall"),
+            "{ host : Text, port : ./Port.dhall }",
+        )
+        .unwrap();
+        let ty = SimpleType::from_file(dir.join("types/Config.dhall")).unwrap();
+        assert_eq!(ty, Config::static_type());
+        assert_eq!(
+            from_str("{ host = \"localhost\", port = 80 }")
+                .type_annotation(&ty)
+                .parse::<Config>()
+                .unwrap(),
+            Config {
+                host: "localhost".to_owned(),
+                port: 80
+            }
+        );
+        assert!(from_str("{ host = \"localhost\", port = -1 }")
+            .type_annotation(&ty)
+            .parse::<Config>()
+            .is_err());
+        assert!(SimpleType::from_file(dir.join("missing.dhall")).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn lazy_evaluation() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Used {
+            used: u64,
+        }
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Skipped {
+            used: u64,
+            #[serde(skip)]
+            unused: u64,
+        }
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct All {
+            used: u64,
+            unused: u64,
+        }
+
+        let data = "{ used = 1, unused = Natural/fold 1000000 Natural (λ(x : Natural) → x + 1) 0 }";
+        let limits = serde_dhall::EvalLimits {
+            max_fold_iterations: Some(100),
+            ..serde_dhall::EvalLimits::default()
+        };
+        let de = from_str(data).eval_limits(limits);
+        // Fields that are not read are never evaluated.
+        assert_eq!(de.parse::<Used>().unwrap(), Used { used: 1 });
+        assert_eq!(
+            de.parse::<Skipped>().unwrap(),
+            Skipped { used: 1, unused: 0 }
+        );
+        assert!(de.parse::<All>().is_err());
+        assert!(de.parse::<Value>().is_err());
+
+        // Maps can also be read as lists of entries.
+        #[derive(Debug, PartialEq, Deserialize)]
+        #[allow(non_snake_case)]
+        struct Entry {
+            mapKey: String,
+            mapValue: u64,
+        }
+        assert_eq!(
+            from_str("toMap { a = 1 }").parse::<Vec<Entry>>().unwrap(),
+            vec![Entry {
+                mapKey: "a".to_owned(),
+                mapValue: 1
+            }]
+        );
+        assert_eq!(
+            from_str("[] : List { mapKey : Text, mapValue : Natural }")
+                .parse::<Vec<Entry>>()
+                .unwrap(),
+            vec![]
+        );
+
+        // Values that aren't simple are reported where they are.
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct WithFunction {
+            f: bool,
+        }
+        assert_eq!(
+            from_str("{ f = λ(x : Bool) → x }")
+                .parse::<WithFunction>()
+                .map_err(|e| e.to_string())
+                .unwrap_err(),
+            "error: at `.f`: this cannot be deserialized into the serde data model: λ(x : Bool) → x
+ --> <current file>:1:7
+  |
+1 | { f = λ(x : Bool) → x }
+  |       ^^^^^^^^^^^^^^^ this value
+  |"
+        );
+    }
+
+    #[test]
+    fn map_keys() {
+        use collections::{BTreeMap, HashMap};
+        use serde_dhall::{from_simple_value, SimpleValue};
+
+        #[derive(
+            Debug,
+            Clone,
+            Copy,
+            PartialEq,
+            Eq,
+            PartialOrd,
+            Ord,
+            Hash,
+            Serialize,
+            Deserialize,
+            StaticType,
+        )]
+        enum Color {
+            Red,
+            Green,
+        }
+
+        let naturals: BTreeMap<u32, String> =
+            vec![(2, "b".to_owned()), (1, "a".to_owned())]
+                .into_iter()
+                .collect();
+        assert_serde(
+            "[{ mapKey = 1, mapValue = \"a\" }, { mapKey = 2, mapValue = \"b\" }]",
+            naturals,
+        );
+        let integers: HashMap<i64, bool> =
+            vec![(1, true), (-3, false)].into_iter().collect();
+        assert_serde(
+            "[{ mapKey = -3, mapValue = False }, { mapKey = +1, mapValue = True }]",
+            integers,
+        );
+        let colors: BTreeMap<Color, u64> =
+            vec![(Color::Green, 2), (Color::Red, 1)]
+                .into_iter()
+                .collect();
+        assert_serde(
+            "[{ mapKey = < Green | Red >.Green, mapValue = 2 }, { mapKey = < Green | Red >.Red, mapValue = 1 }]",
+            colors.clone(),
+        );
+        assert_de("[] : List { mapKey : Natural, mapValue : Text }", {
+            BTreeMap::<u32, String>::new()
+        });
+        // Enum keys can also be read from text.
+        assert_eq!(
+            from_str("toMap { Red = 1, Green = 2 }")
+                .parse::<BTreeMap<Color, u64>>()
+                .unwrap(),
+            colors
+        );
+
+        // Maps with non-text keys stay lists in `SimpleValue`.
+        let value: SimpleValue = from_str("[{ mapKey = 1, mapValue = True }]")
+            .parse()
+            .unwrap();
+        assert!(matches!(value, SimpleValue::List(_)));
+        assert_eq!(
+            from_simple_value::<HashMap<u64, bool>>(value).unwrap(),
+            vec![(1, true)].into_iter().collect()
+        );
+
+        assert_eq!(
+            from_str("[{ mapKey = -1, mapValue = 1 }]")
+                .parse::<HashMap<u32, u64>>()
+                .map_err(|e| e.to_string())
+                .unwrap_err(),
+            "error: at `[0].mapKey`: invalid value: integer `-1`, expected u32
+ --> <current file>:1:13
+  |
+1 | [{ mapKey = -1, mapValue = 1 }]
+  |             ^^ this value
+  |"
+        );
+        assert_eq!(
+            from_str("toMap { Blue = 1 }")
+                .parse::<HashMap<Color, u64>>()
+                .map_err(|e| e.to_string())
+                .unwrap_err(),
+            "at `.Blue`: unknown variant `Blue`, expected `Red` or `Green`"
+        );
+    }
+
+    #[test]
+    fn enum_representations() {
+        use serde_dhall::EnumRepresentation;
+
+        fn assert_repr<T>(enums: &EnumRepresentation, s: &str, x: T)
+        where
+            T: FromDhall + ToDhall + StaticType + PartialEq + std::fmt::Debug,
+        {
+            assert_eq!(
+                serialize(&x)
+                    .static_type_annotation()
+                    .enum_representation(enums.clone())
+                    .to_string()
+                    .map_err(|e| e.to_string()),
+                Ok(s.to_string())
+            );
+            assert_eq!(
+                from_str(s)
+                    .static_type_annotation()
+                    .enum_representation(enums.clone())
+                    .parse::<T>()
+                    .map_err(|e| e.to_string()),
+                Ok(x)
+            );
+        }
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize, StaticType)]
+        struct Circle {
+            radius: u64,
+        }
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize, StaticType)]
+        #[serde(tag = "type")]
+        enum Internal {
+            Circle { radius: u64 },
+            Wrapped(Circle),
+            Point,
+        }
+        let internal = EnumRepresentation::Internal {
+            tag: "type".to_owned(),
+        };
+        let ty = "< Circle: { radius : Natural } | Point | Wrapped: { radius : Natural } >";
+        assert_repr(
+            &internal,
+            &format!("{}.Circle {{ radius = 1 }}", ty),
+            Internal::Circle { radius: 1 },
+        );
+        assert_repr(
+            &internal,
+            &format!("{}.Wrapped {{ radius = 2 }}", ty),
+            Internal::Wrapped(Circle { radius: 2 }),
+        );
+        assert_repr(&internal, &format!("{}.Point", ty), Internal::Point);
+        // Enums without a serde representation are still externally tagged.
+        #[derive(Debug, PartialEq, Serialize, Deserialize, StaticType)]
+        enum External {
+            A(u64),
+            B,
+        }
+        assert_repr(
+            &internal,
+            "[< A: Natural | B >.A 1, < A: Natural | B >.B]",
+            vec![External::A(1), External::B],
+        );
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize, StaticType)]
+        #[serde(tag = "t", content = "c")]
+        enum Adjacent {
+            Number(u64),
+            Pair(u64, bool),
+            Nothing,
+        }
+        let adjacent = EnumRepresentation::Adjacent {
+            tag: "t".to_owned(),
+            content: "c".to_owned(),
+        };
+        let ty =
+            "< Nothing | Number: Natural | Pair: { _1 : Natural, _2 : Bool } >";
+        assert_repr(
+            &adjacent,
+            &format!("{}.Number 3", ty),
+            Adjacent::Number(3),
+        );
+        assert_repr(
+            &adjacent,
+            &format!("{}.Pair {{ _1 = 4, _2 = True }}", ty),
+            Adjacent::Pair(4, true),
+        );
+        assert_repr(&adjacent, &format!("{}.Nothing", ty), Adjacent::Nothing);
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize, StaticType)]
+        #[serde(untagged)]
+        enum Untagged {
+            Number(u64),
+            Name(String),
+            Nothing,
+        }
+        let ty = "< Name: Text | Nothing | Number: Natural >";
+        assert_repr(
+            &EnumRepresentation::Untagged,
+            &format!("Some ({}.Number 80)", ty),
+            Some(Untagged::Number(80)),
+        );
+        assert_repr(
+            &EnumRepresentation::Untagged,
+            &format!("{}.Name \"http\"", ty),
+            Untagged::Name("http".to_owned()),
+        );
+        assert_repr(
+            &EnumRepresentation::Untagged,
+            &format!("{}.Nothing", ty),
+            Untagged::Nothing,
+        );
+
+        #[derive(Serialize, StaticType)]
+        #[serde(untagged)]
+        #[allow(dead_code)]
+        enum Same {
+            A { x: u64 },
+            B { x: u64 },
+        }
+        assert_eq!(
+            serialize(&Same::A { x: 1 })
+                .static_type_annotation()
+                .enum_representation(EnumRepresentation::Untagged)
+                .to_string()
+                .map_err(|e| e.to_string()),
+            Err("this value of an untagged enum fits several alternatives: `A`, `B`".to_string())
+        );
+
+        assert_eq!(
+            from_str("< A: Natural >.A 1")
+                .enum_representation(internal)
+                .parse::<Internal>()
+                .map_err(|e| e.to_string())
+                .unwrap_err(),
+            "the contents of `A` must be a record to be read as an internally tagged enum"
+        );
+    }
+
+    #[test]
+    fn edit_document() {
+        use serde_dhall::Document;
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Service {
+            host: String,
+            port: u64,
+        }
+
+        let text =
+            "-- Services\nlet port = 80\nin  { web = { host = \"a\", port } }";
+        let mut doc = Document::parse(text).unwrap();
+        doc.set(
+            ".db",
+            &Service {
+                host: "b".to_owned(),
+                port: 5432,
+            },
+        )
+        .unwrap();
+        doc.set(".web.port", &8080u64).unwrap();
+        assert_eq!(
+            doc.as_str(),
+            "-- Services\nlet port = 8080\nin  { web = { host = \"a\", port }, db = { host = \"b\", port = 5432 } }"
+        );
+        let db: Service = from_str(doc.as_str())
+            .parse::<collections::HashMap<String, Service>>()
+            .unwrap()
+            .remove("db")
+            .unwrap();
+        assert_eq!(db.port, 5432);
+
+        assert_eq!(
+            doc.set(".web.host.name", &1u64)
+                .map_err(|e| e.to_string())
+                .unwrap_err(),
+            "nothing is written at `.web.host.name`"
+        );
+
+        let text = "let Service = { Type = Service, default.port = 80 }\nin  { web = Service::{ host = \"a\" } // { port = 81 } }";
+        let mut doc = Document::parse(text).unwrap();
+        doc.set(".web.host", &"c").unwrap();
+        doc.set(".web.port", &8081u64).unwrap();
+        assert_eq!(
+            doc.as_str(),
+            text.replace("\"a\"", "\"c\"").replace("81", "8081")
+        );
+    }
+
+    #[test]
+    fn to_json() {
+        use serde_dhall::JsonOptions;
+
+        fn to_json(s: &str, options: &JsonOptions) -> Result<String, String> {
+            from_str(s)
+                .to_json(options)
+                .map(|json| json.to_string())
+                .map_err(|e| e.to_string())
+        }
+        let default = JsonOptions::new();
+
+        assert_eq!(
+            to_json(
+                "{ b = [1, 2], a = { x = +1, y = 1.5, z = True }, c = \"c\" }",
+                &default
+            ),
+            Ok(r#"{"a":{"x":1,"y":1.5,"z":true},"b":[1,2],"c":"c"}"#
+                .to_owned())
+        );
+        // Maps keep the order of their entries.
+        assert_eq!(
+            to_json(
+                "[{ mapKey = \"z\", mapValue = 1 }, { mapKey = \"a\", mapValue = 2 }]",
+                &default
+            ),
+            Ok(r#"{"z":1,"a":2}"#.to_owned())
+        );
+        assert_eq!(
+            to_json(
+                "[] : List { mapKey : Text, mapValue : Natural }",
+                &default
+            ),
+            Ok("{}".to_owned())
+        );
+        assert_eq!(
+            to_json("toMap { a = 1 }", &default.clone().maps(false)),
+            Ok(r#"[{"mapKey":"a","mapValue":1}]"#.to_owned())
+        );
+        assert_eq!(
+            to_json(
+                "[< A : Natural | B >.A 1, < A : Natural | B >.B]",
+                &default
+            ),
+            Ok(r#"[1,"B"]"#.to_owned())
+        );
+
+        // Null and empty fields.
+        let record = "{ a = None Natural, b = Some 1, c = [] : List Natural, d = {=}, e = [None Natural] }";
+        assert_eq!(
+            to_json(record, &default),
+            Ok(r#"{"b":1,"c":[],"d":{},"e":[null]}"#.to_owned())
+        );
+        assert_eq!(
+            to_json(record, &default.clone().preserve_null(true)),
+            Ok(r#"{"a":null,"b":1,"c":[],"d":{},"e":[null]}"#.to_owned())
+        );
+        assert_eq!(
+            to_json(record, &default.clone().omit_empty(true)),
+            Ok(r#"{"b":1,"e":[null]}"#.to_owned())
+        );
+
+        // Values of type `Prelude.JSON.Type`.
+        let json_value = "
+            λ(JSON : Type) →
+            λ ( json
+              : { array : List JSON → JSON
+                , bool : Bool → JSON
+                , double : Double → JSON
+                , integer : Integer → JSON
+                , null : JSON
+                , object : List { mapKey : Text, mapValue : JSON } → JSON
+                , string : Text → JSON
+                }
+              ) →
+              json.object
+                [ { mapKey = \"b\", mapValue = json.array [ json.null, json.bool True ] }
+                , { mapKey = \"a\", mapValue = json.integer -1 }
+                , { mapKey = \"c\", mapValue = json.object ([] : List { mapKey : Text, mapValue : JSON }) }
+                , { mapKey = \"d\", mapValue = json.string \"d\" }
+                ]
+        ";
+        assert_eq!(
+            to_json(json_value, &default),
+            Ok(r#"{"b":[null,true],"a":-1,"c":{},"d":"d"}"#.to_owned())
+        );
+
+        assert_eq!(
+            to_json("{ x = [ λ(x : Bool) → x ] }", &default),
+            Err("error: at `.x[0]`: this cannot be converted to JSON: λ(x : Bool) → x
+ --> <current file>:1:9
+  |
+1 | { x = [ λ(x : Bool) → x ] }
+  |         ^^^^^^^^^^^^^^^ this value
+  |"
+            .to_owned())
+        );
+        assert_eq!(
+            to_json("{ x = Infinity }", &default),
+            Err("error: at `.x`: this cannot be converted to JSON: Infinity
+ --> <current file>:1:7
+  |
+1 | { x = Infinity }
+  |       ^^^^^^^^ this value
+  |"
+            .to_owned())
+        );
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn to_yaml() {
+        use serde_dhall::JsonOptions;
+
+        let yaml = from_str(
+            "{ name = \"app\", env = toMap { b = \"1\", a = \"2\" }, tags = [] : List Text }",
+        )
+        .to_yaml(&JsonOptions::new());
+        assert_eq!(
+            yaml.unwrap(),
+            "env:\n  a: \"2\"\n  b: \"1\"\nname: app\ntags: []\n"
+        );
+    }
+
+    #[test]
+    fn from_json() {
+        use serde_dhall::SimpleType;
+        use serde_json::json;
+
+        fn from_json(
+            json: serde_json::Value,
+            ty: &str,
+        ) -> Result<String, String> {
+            let ty: SimpleType = ty.parse().unwrap();
+            Value::from_json(&json, &ty)
+                .map(|v| v.to_string())
+                .map_err(|e| e.to_string())
+        }
+
+        assert_eq!(
+            from_json(
+                json!({ "a": 1, "b": [-1, 2], "c": 1, "d": null, "e": "e" }),
+                "{ a : Natural, b : List Integer, c : Double, d : Optional Bool, e : Optional Text, f : Optional Text }"
+            ),
+            Ok("{ a = 1, b = [-1, +2], c = 1.0, d = None Bool, e = Some \"e\", f = None Text }".to_owned())
+        );
+        assert_eq!(
+            from_json(
+                json!({ "b": 2, "a": 1 }),
+                "List { mapKey : Text, mapValue : Natural }"
+            ),
+            Ok("[{ mapKey = \"a\", mapValue = 1 }, { mapKey = \"b\", mapValue = 2 }]".to_owned())
+        );
+        assert_eq!(
+            from_json(json!({}), "List { mapKey : Text, mapValue : Natural }"),
+            Ok("[] : List { mapKey : Text, mapValue : Natural }".to_owned())
+        );
+        assert_eq!(
+            from_json(
+                json!({ "A": 2 }),
+                "List { mapKey : < A | B >, mapValue : Natural }"
+            ),
+            Ok("[{ mapKey = < A | B >.A, mapValue = 2 }]".to_owned())
+        );
+        assert_eq!(
+            from_json(
+                json!([1, "x", "B", { "y": true }]),
+                "List < A : Natural | B | C : Text | D : { y : Bool } >"
+            ),
+            Ok("[< A: Natural | B | C: Text | D: { y : Bool } >.A 1, < A: Natural | B | C: Text | D: { y : Bool } >.C \"x\", < A: Natural | B | C: Text | D: { y : Bool } >.B, < A: Natural | B | C: Text | D: { y : Bool } >.D { y = True }]".to_owned())
+        );
+
+        assert_eq!(
+            from_json(json!({ "x": [1, -2] }), "{ x : List Natural }"),
+            Err("at `.x[1]`: expected a value of type `Natural`, found -2"
+                .to_owned())
+        );
+        assert_eq!(
+            from_json(json!({ "x": 1, "y": 2 }), "{ x : Natural }"),
+            Err("unknown field `y`".to_owned())
+        );
+        assert_eq!(
+            from_json(json!({}), "{ x : Natural }"),
+            Err("at `.x`: missing field `x`".to_owned())
+        );
+    }
+
+    #[test]
+    fn infer_from_json() {
+        use serde_json::json;
+
+        fn infer(json: serde_json::Value) -> Result<(String, String), String> {
+            Value::infer_from_json(&json)
+                .map(|v| (v.ty().unwrap().to_string(), v.to_string()))
+                .map_err(|e| e.to_string())
+        }
+        fn infer_ty(json: serde_json::Value) -> Result<String, String> {
+            infer(json).map(|(ty, _)| ty)
+        }
+
+        assert_eq!(
+            infer(json!({ "a": 1, "b": "b", "c": [true], "d": -1 })),
+            Ok((
+                "{ a : Natural, b : Text, c : List Bool, d : Integer }"
+                    .to_owned(),
+                "{ a = 1, b = \"b\", c = [True], d = -1 }".to_owned()
+            ))
+        );
+        assert_eq!(infer_ty(json!([1, -1, 2.5])), Ok("List Double".to_owned()));
+        assert_eq!(
+            infer(json!([null, 1])),
+            Ok((
+                "List (Optional Natural)".to_owned(),
+                "[None Natural, Some 1]".to_owned()
+            ))
+        );
+        assert_eq!(
+            infer_ty(json!([{ "x": 1, "y": [] }, { "y": [{ "z": null }] }])),
+            Ok(
+                "List { x : Optional Natural, y : List { z : Optional <  > } }"
+                    .to_owned()
+            )
+        );
+        assert_eq!(infer_ty(json!([])), Ok("List <  >".to_owned()));
+        assert_eq!(
+            infer_ty(json!({ "x": [1, "a"] })),
+            Err("at `.x`: the elements of this list have incompatible types `Natural` and `Text`".to_owned())
+        );
+    }
+
     #[test]
     fn test_de_untyped() {
         use std::collections::BTreeMap;
@@ -285,6 +1710,69 @@ mod serde {
         assert!(from_str("List/length [True, 42]").parse::<bool>().is_err());
     }
 
+    #[test]
+    fn error_path() {
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct Replica {
+            host: String,
+        }
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        enum Db {
+            Postgres { replicas: Vec<Replica> },
+        }
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct Config {
+            db: Db,
+        }
+
+        fn parse_err(s: &str) -> String {
+            from_str(s).parse::<Config>().unwrap_err().to_string()
+        }
+
+        let value = serde_dhall::from_str(
+            "{ db = < Postgres: { replicas: List { host: Natural } } >.Postgres \
+             { replicas = [ { host = 1 } ] } }",
+        )
+        .parse()
+        .unwrap();
+        assert_eq!(
+            serde_dhall::from_simple_value::<Config>(value)
+                .unwrap_err()
+                .to_string(),
+            "at `.db.Postgres.replicas[0].host`: invalid type: \
+             expected a string, found Natural `1`"
+        );
+
+        // Errors point to the value in the source when it was written out literally.
+        assert_eq!(
+            parse_err(
+                r#"let x = +0 in { db = < Postgres: { replicas: List { host: Integer } } >.Postgres { replicas = [ { host = x }, { host = x }, { host = +1 } ] } }"#
+            )
+            .lines()
+            .next(),
+            Some(
+                "error: at `.db.Postgres.replicas[0].host`: invalid type: \
+                 expected a string, found Integer `+0`"
+            )
+        );
+        assert!(parse_err(
+            r#"{ db = < Postgres: { replicas: List { hst: Text } } >.Postgres { replicas = [ { hst = "a" } ] } }"#
+        )
+        .starts_with("error: at `.db.Postgres.replicas[0]`: missing field `host`"));
+
+        // Values that were computed have no location in the source.
+        assert_eq!(
+            parse_err(
+                r#"{ db = < Postgres: { replicas: List { host: Integer } } >.Postgres { replicas = [ { host = +0 } ] # [ { host = +1 } ] } }"#
+            ),
+            "at `.db.Postgres.replicas[0].host`: invalid type: \
+             expected a string, found Integer `+0`"
+        );
+    }
+
     #[test]
     fn test_file() {
         assert_eq!(
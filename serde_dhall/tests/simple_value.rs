@@ -1,7 +1,8 @@
 mod simple_value {
     use serde::{Deserialize, Serialize};
     use serde_dhall::{
-        from_str, serialize, FromDhall, NumKind, SimpleValue, ToDhall, Value,
+        from_str, serialize, FromDhall, NumKind, SimpleType, SimpleValue,
+        ToDhall, Value,
     };
 
     fn assert_de<T>(s: &str, x: T)
@@ -67,4 +68,115 @@ mod simple_value {
             ))
         );
     }
+
+    #[test]
+    fn test_value() {
+        let value: Value = from_str(
+            r#"{ servers =
+                   [ { host = "a", ports = [] : List Natural, tls = None Bool }
+                   , { host = "b", ports = [ 80, 443 ], tls = Some True }
+                   ]
+               , env = toMap { HOME = "/root" }
+               , `max size` = +3
+               , ratio = 0.5
+               , mode = < Dev | Prod : { replicas : Natural } >.Prod { replicas = 2 }
+               }"#,
+        )
+        .parse()
+        .unwrap();
+
+        let servers = value.get("servers").unwrap().as_list().unwrap();
+        assert_eq!(servers.len(), 2);
+        assert_eq!(servers[0].get("host").unwrap().as_text(), Some("a"));
+        // Sub-values keep their type, so they can be printed.
+        assert_eq!(
+            servers[0].get("ports").unwrap().to_string(),
+            "[] : List Natural"
+        );
+        assert_eq!(servers[0].get("tls").unwrap().as_optional().unwrap(), None);
+        assert_eq!(value.get("max size").unwrap().as_integer(), Some(3));
+        assert_eq!(value.get("ratio").unwrap().as_double(), Some(0.5));
+        assert_eq!(value.get("ratio").unwrap().as_natural(), None);
+        assert_eq!(value.get("missing"), None);
+
+        // Maps can be read as records or as lists.
+        let env = value.get("env").unwrap();
+        assert_eq!(env.get("HOME").unwrap().as_text(), Some("/root"));
+        assert_eq!(env.get("HOME").unwrap().ty(), Some(&SimpleType::Text));
+        assert_eq!(
+            env.as_list().unwrap()[0].to_string(),
+            r#"{ mapKey = "HOME", mapValue = "/root" }"#
+        );
+
+        let mode = value.get("mode").unwrap();
+        let (variant, contents) = mode.as_union().unwrap();
+        assert_eq!(variant, "Prod");
+        assert_eq!(contents.unwrap().to_string(), "{ replicas = 2 }");
+
+        assert_eq!(
+            value.pointer(".servers[1].ports[1]").unwrap().as_natural(),
+            Some(443)
+        );
+        assert_eq!(
+            value.pointer(".servers[1].tls").unwrap().to_string(),
+            "Some True"
+        );
+        assert_eq!(
+            value.pointer(".mode.Prod.replicas").unwrap().as_natural(),
+            Some(2)
+        );
+        assert_eq!(value.pointer(".`max size`").unwrap().to_string(), "+3");
+        assert_eq!(value.pointer("").unwrap(), value);
+        assert_eq!(value.pointer(".mode.Dev"), None);
+        assert_eq!(value.pointer(".servers[2]"), None);
+        assert_eq!(value.pointer(".servers[x]"), None);
+        assert_eq!(value.pointer("servers"), None);
+        assert_eq!(
+            value.pointer(".env.HOME").unwrap().ty(),
+            Some(&SimpleType::Text)
+        );
+        assert_eq!(
+            value.pointer(".env[0]").unwrap().to_string(),
+            r#"{ mapKey = "HOME", mapValue = "/root" }"#
+        );
+        assert_eq!(
+            value.pointer(".env[0].mapValue").unwrap().as_text(),
+            Some("/root")
+        );
+        assert_eq!(
+            value.pointer(".env[0].mapKey").unwrap().as_text(),
+            Some("HOME")
+        );
+        assert_eq!(value.pointer(".env[0].mapKey.x"), None);
+
+        let ty: Value = from_str("{ x : Bool }").parse().unwrap();
+        assert_eq!(ty.ty(), None);
+        assert_eq!(ty.get("x"), None);
+        assert_eq!(ty.to_simple_type().unwrap().to_string(), "{ x : Bool }");
+        assert_eq!(
+            value.pointer(".servers[0].host").unwrap().to_simple_value(),
+            Some(SimpleValue::Text("a".to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_diff() {
+        let old: SimpleValue = from_str("{ name = \"app\", replicas = 1 }")
+            .parse()
+            .unwrap();
+        let new: SimpleValue = from_str("{ name = \"app\", replicas = 3 }")
+            .parse()
+            .unwrap();
+        let diff = old.diff(&new).unwrap();
+        assert_eq!(diff.to_string(), "- .replicas = 1\n+ .replicas = 3");
+        assert!(old.diff(&old).unwrap().is_same());
+
+        // Without a type, empty lists can't be compared.
+        let empty: SimpleValue = from_str("[] : List Natural").parse().unwrap();
+        let list: SimpleValue = from_str("[ 1 ]").parse().unwrap();
+        assert!(empty.diff(&list).is_err());
+        let empty: Value = from_str("[] : List Natural").parse().unwrap();
+        let list: Value = from_str("[ 1 ]").parse().unwrap();
+        assert_eq!(empty.diff(&list).to_string(), "+ [0] = 1");
+    }
 }
@@ -1,4 +1,4 @@
-use serde_dhall::{from_str, SimpleType, StaticType};
+use serde_dhall::{from_str, RecordStaticType, SimpleType, StaticType};
 
 #[test]
 fn test_static_type() {
@@ -77,3 +77,123 @@ fn test_static_type() {
         parse("< A | B: Bool | C: { a: Bool, b: Natural } >")
     )
 }
+
+#[test]
+fn test_static_type_attributes() {
+    use serde::{Deserialize, Serialize};
+
+    fn parse(s: &str) -> SimpleType {
+        from_str(s).parse().unwrap()
+    }
+
+    #[derive(StaticType, Serialize, Deserialize)]
+    #[allow(dead_code)]
+    struct Inner {
+        x: u64,
+    }
+
+    #[derive(StaticType, Serialize, Deserialize)]
+    #[allow(dead_code)]
+    #[serde(rename_all = "kebab-case")]
+    struct A {
+        field_one: bool,
+        #[serde(rename = "two")]
+        field_two: bool,
+        #[serde(skip)]
+        skipped: bool,
+        #[serde(default)]
+        with_default: bool,
+        #[serde(flatten)]
+        inner: Inner,
+        #[dhall(rename = "r")]
+        r#type: bool,
+    }
+    assert_eq!(
+        A::static_type(),
+        parse(
+            "{ field-one: Bool, two: Bool, with-default: Bool, x: Natural, r: Bool }"
+        )
+    );
+
+    #[derive(StaticType, Serialize, Deserialize)]
+    #[allow(dead_code)]
+    #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+    enum B {
+        FirstVariant,
+        #[serde(rename_all = "camelCase")]
+        Second {
+            some_field: bool,
+        },
+        #[serde(skip)]
+        Skipped,
+        #[serde(rename = "other")]
+        Third(u64),
+    }
+    assert_eq!(
+        B::static_type(),
+        parse(
+            "< FIRST_VARIANT | SECOND: { someField: Bool } | other: Natural >"
+        )
+    );
+
+    // Generic flattened fields only need to be records.
+    #[derive(StaticType)]
+    #[allow(dead_code)]
+    struct Generic<T> {
+        name: String,
+        #[dhall(flatten)]
+        rest: T,
+    }
+    assert_eq!(
+        <Generic<Inner>>::static_type(),
+        parse("{ name: Text, x: Natural }")
+    );
+    assert_eq!(
+        <Generic<Inner> as RecordStaticType>::record_fields(),
+        vec![
+            ("name".to_owned(), SimpleType::Text),
+            ("x".to_owned(), SimpleType::Natural)
+        ]
+        .into_iter()
+        .collect()
+    );
+}
+
+#[test]
+fn test_static_type_shapes() {
+    use serde::{Deserialize, Serialize};
+    use std::marker::PhantomData;
+
+    fn parse(s: &str) -> SimpleType {
+        from_str(s).parse().unwrap()
+    }
+
+    #[derive(StaticType)]
+    #[allow(dead_code)]
+    enum A<T> {
+        Pair(T, String),
+        Single(T),
+    }
+    assert_eq!(
+        <A<bool>>::static_type(),
+        parse("< Pair: { _1: Bool, _2: Text } | Single: Bool >")
+    );
+
+    #[derive(StaticType, Serialize, Deserialize)]
+    #[allow(dead_code)]
+    #[serde(transparent)]
+    struct B<T> {
+        items: Vec<u64>,
+        #[serde(skip)]
+        marker: PhantomData<T>,
+    }
+    assert_eq!(<B<String>>::static_type(), parse("List Natural"));
+
+    #[derive(StaticType)]
+    #[allow(dead_code)]
+    #[dhall(transparent)]
+    struct C {
+        name: String,
+    }
+    assert_eq!(C::static_type(), parse("Text"));
+}